@@ -1,12 +1,16 @@
 use plugin_test_api::{PluginInformation, SayHello, BaseAPI, GameEvent, CustomEvent, PluginContext, Plugin};
 use std::{net::ToSocketAddrs, sync::Arc};
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicBool, Ordering};
 use async_trait::async_trait;
-use tokio::sync::RwLock;
+use tokio::sync::{broadcast, Notify, RwLock};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
 use uuid::Uuid;
 use horizon_data_types::Player;
 use ez_logging::println;
+use tracing::{error, info, instrument, warn};
+use base64::Engine;
 use csv;
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -14,6 +18,59 @@ pub struct Ingredient {
     pub name: String,
     pub quantity: u32,
     pub recipe_craftable: bool,
+    // When set, `quantity` is the minimum and up to this many are consumed if available
+    #[serde(default)]
+    pub max_quantity: Option<u32>,
+    // Only items whose "quality" tag is at least this tier count towards the ingredient
+    #[serde(default)]
+    pub min_quality: Option<String>,
+    // Tools, e.g. a hammer, are false: they must be held but are never used up
+    #[serde(default = "default_consumed")]
+    pub consumed: bool,
+    // What each consumed unit turns into and is given back as, e.g. "Bucket of Water" -> "Bucket"
+    #[serde(default)]
+    pub transformed_to: Option<String>,
+}
+
+fn default_consumed() -> bool {
+    true
+}
+
+impl Ingredient {
+    // How many must be held for `count` crafts; a tool is needed only once
+    pub fn required_for(&self, count: u32) -> u32 {
+        if self.consumed {
+            self.quantity * count
+        } else {
+            self.quantity
+        }
+    }
+
+    pub fn accepts(&self, item: &Item) -> bool {
+        if item.name != self.name {
+            return false;
+        }
+        match &self.min_quality {
+            Some(min_quality) => match (quality_rank(min_quality), item.quality()) {
+                (Some(min), Some(quality)) => quality >= min,
+                _ => false,
+            },
+            None => true,
+        }
+    }
+}
+
+// Rank of a quality tier, higher is better: poor, common, fine, superior, masterwork,
+// or a plain number. None when the tier is not recognised.
+pub fn quality_rank(quality: &str) -> Option<u32> {
+    match quality.to_ascii_lowercase().as_str() {
+        "poor" => Some(0),
+        "common" => Some(1),
+        "fine" => Some(2),
+        "superior" => Some(3),
+        "masterwork" => Some(4),
+        other => other.parse().ok(),
+    }
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq, Hash)]
@@ -23,499 +80,7862 @@ pub struct Crafter {
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Recipe {
+    #[serde(default)]
+    pub id: String,
     pub name: String,
     pub ingredients: Vec<Ingredient>,
+    #[serde(alias = "output")]
     pub outcome: String,
     pub crafters: Vec<Crafter>,
+    #[serde(alias = "cook_time")]
     pub base_cook_time: u32,
+    #[serde(default)]
     pub cook_count: u32,
+    #[serde(default)]
+    pub byproducts: Vec<String>,
+    #[serde(default)]
+    pub unlock_condition: Option<UnlockCondition>,
+    // Minimum player attribute values (see `PlayerAttributeMapping`) needed to craft
+    #[serde(default)]
+    pub required_attributes: HashMap<String, f32>,
+    // Minimum overall player level (see `PlayerLevelMapping`) needed to craft
+    #[serde(default)]
+    pub required_level: Option<u32>,
+    #[serde(default)]
+    pub output_scaling: OutputScaling,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    // Fraction of the cook time each craft may randomly vary by, e.g. 0.1 for +/-10%
+    #[serde(default)]
+    pub cook_time_variance: f32,
+    // Lifetime number of crafts each player may make, e.g. Some(1) for unique quest items
+    #[serde(default)]
+    pub max_per_player: Option<u32>,
+    // Lifetime number of crafts across all players, counted in the recipe book's global craft stats
+    #[serde(default)]
+    pub max_global: Option<u32>,
+    // Makes the outcome a new pre-filled storage container, e.g. a crafted starter kit
+    #[serde(default)]
+    pub container_output: Option<ContainerOutput>,
+    // Name or id of a base recipe whose ingredients and crafters this one builds on
+    #[serde(default)]
+    pub extends: Option<String>,
+    // Crafter name -> everything produced when crafted there, replacing outcome and byproducts.
+    // The first entry counts as the main outcome.
+    #[serde(default)]
+    pub crafter_outputs: HashMap<String, Vec<String>>,
+    // Client-side sound and animation ids, forwarded in the craft events
+    #[serde(default)]
+    pub sound: Option<String>,
+    #[serde(default)]
+    pub animation: Option<String>,
+    // Mutually exclusive choices, e.g. specializations: a player can learn one recipe per group
+    #[serde(default)]
+    pub group: Option<String>,
 }
 
-impl Recipe {
-    fn increment_cook_count(&mut self) {
-        self.cook_count += 1;
-    }
-
-    fn is_mastered(&self) -> bool {
-        self.cook_count >= 10
-    }
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+pub struct ContainerOutput {
+    pub num_slots: u32,
+    pub contents: Vec<ContainerContent>,
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
-pub struct Item {
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+pub struct ContainerContent {
     pub name: String,
-    pub model: Option<String>,
-    pub meta_tags: HashMap<String, serde_json::Value>,
-}
-
-#[derive(Debug, Clone, Deserialize, Serialize)]
-pub struct PlayerInventory {
-    pub slots: HashMap<u32, Option<Item>>,
+    pub quantity: u32,
 }
 
-impl PlayerInventory {
-    pub fn new(num_slots: u32) -> Self {
-        let mut slots = HashMap::new();
-        for i in 0..num_slots {
-            slots.insert(i, None);
+impl ContainerOutput {
+    // Contents that don't fit in `num_slots` are dropped
+    pub fn build(&self, items: &ItemRegistry, policy: StackMergePolicy) -> StorageContainer {
+        let mut container = StorageContainer::new(self.num_slots);
+        for content in &self.contents {
+            let _ = container.inventory.deposit(items.create_item(&content.name, content.quantity), policy);
         }
-        Self { slots }
-    }
-
-    pub fn get_item(&self, slot: u32) -> Option<&Item> {
-        self.slots.get(&slot).and_then(|item| item.as_ref())
-    }
-
-    pub fn add_item(&mut self, slot: u32, item: Item) {
-        self.slots.insert(slot, Some(item));
-    }
-
-    pub fn remove_item(&mut self, slot: u32) -> Option<Item> {
-        self.slots.insert(slot, None).flatten()
-    }
-
-    pub fn empty_slot(&mut self, slot: u32) {
-        self.slots.insert(slot, None);
+        container
     }
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
-pub struct StorageContainer {
-    pub uuid: Uuid,
-    pub inventory: PlayerInventory,
+#[derive(Debug, Clone, Copy, Deserialize, Serialize, PartialEq, Eq, Default)]
+pub enum OutputScaling {
+    // Extra ingredients within a range are consumed without changing the output
+    #[default]
+    None,
+    // Each full multiple of a ranged ingredient's minimum yields another set of outputs,
+    // limited by the ranged ingredient with the lowest multiple
+    Proportional,
 }
 
-impl StorageContainer {
-    pub fn new(num_slots: u32) -> Self {
-        Self {
-            uuid: Uuid::new_v4(),
-            inventory: PlayerInventory::new(num_slots),
-        }
-    }
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConsumptionPlan {
+    pub consumed: Vec<(String, u32)>,
+    pub output_multiplier: u32,
 }
 
-#[derive(Debug, Clone, Deserialize, Serialize)]
-pub struct RecipeBook {
-    pub recipes: HashMap<String, Recipe>,
-    pub crafters: HashMap<Crafter, Vec<String>>,
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq)]
+pub enum UnlockCondition {
+    MasteredRecipe(String),
+    HasItem(String),
 }
 
-impl Clone for RecipeSmith {
-    fn clone(&self) -> Self {
-        RecipeSmith {
-            initialized: self.initialized,
-            recipe_book: Arc::clone(&self.recipe_book),
-            player_inventories: Arc::clone(&self.player_inventories),
+pub fn recipe_id_from_name(name: &str) -> String {
+    let mut id = String::with_capacity(name.len());
+    for c in name.trim().chars() {
+        if c.is_alphanumeric() {
+            id.extend(c.to_lowercase());
+        } else if !id.ends_with('_') {
+            id.push('_');
         }
     }
+    id.trim_matches('_').to_string()
 }
 
-impl RecipeBook {
-    pub fn new() -> Self {
-        Self {
-            recipes: HashMap::new(),
-            crafters: HashMap::new(),
-        }
-    }
+impl Recipe {
+    // Works out how much of each ingredient `count` crafts consume given what's available,
+    // or None if any ingredient is below its minimum
+    pub fn plan_consumption(&self, count: u32, available: impl Fn(&str) -> u32) -> Option<ConsumptionPlan> {
+        let mut consumed = Vec::with_capacity(self.ingredients.len());
+        let mut output_multiplier: Option<u32> = None;
 
-    pub fn add_recipe(&mut self, recipe: Recipe) {
-        for crafter in &recipe.crafters {
-            self.crafters.entry(crafter.clone()).or_insert_with(Vec::new).push(recipe.name.clone());
+        for ingredient in &self.ingredients {
+            let minimum = ingredient.required_for(count);
+            let have = available(&ingredient.name);
+            if have < minimum {
+                return None;
+            }
+            if !ingredient.consumed {
+                continue;
+            }
+            let amount = match ingredient.max_quantity {
+                Some(max_quantity) => {
+                    let amount = have.min(max_quantity.max(ingredient.quantity) * count);
+                    if self.output_scaling == OutputScaling::Proportional && minimum > 0 {
+                        let multiple = amount / minimum;
+                        output_multiplier = Some(output_multiplier.map_or(multiple, |m| m.min(multiple)));
+                    }
+                    amount
+                }
+                None => minimum,
+            };
+            consumed.push((ingredient.name.clone(), amount));
         }
-        self.recipes.insert(recipe.name.clone(), recipe);
+
+        Some(ConsumptionPlan {
+            consumed,
+            output_multiplier: output_multiplier.unwrap_or(1).max(1),
+        })
     }
 
-    pub fn get_recipe(&self, name: &str) -> Option<Recipe> {
-        self.recipes.get(name).cloned()
+    // Items given back for what was consumed, from ingredients with `transformed_to`
+    pub fn transformed_returns(&self, consumed: &[(String, u32)]) -> Vec<(String, u32)> {
+        consumed.iter()
+            .filter_map(|(name, amount)| {
+                let ingredient = self.ingredients.iter().find(|ingredient| &ingredient.name == name && ingredient.consumed)?;
+                ingredient.transformed_to.clone().map(|returned| (returned, *amount))
+            })
+            .collect()
     }
 
-    pub fn get_recipes_for_crafter(&self, crafter: &Crafter) -> Vec<Recipe> {
-        self.crafters.get(crafter)
-            .map(|recipe_names| recipe_names.iter().filter_map(|name| self.get_recipe(name)).collect())
-            .unwrap_or_else(Vec::new)
+    // `roll` is a uniform sample in [0, 1); the result never goes below zero
+    pub fn varied_cook_time(&self, cook_time: f64, roll: f32) -> f64 {
+        let variance = self.cook_time_variance.clamp(0.0, 1.0) as f64;
+        let offset = variance * (2.0 * roll.clamp(0.0, 1.0) as f64 - 1.0);
+        (cook_time * (1.0 + offset)).max(0.0)
     }
 
-    pub fn can_craft(&self, recipe_name: &str, inventory: &HashMap<String, Ingredient>) -> bool {
-        if let Some(recipe) = self.get_recipe(recipe_name) {
-            recipe.ingredients.iter().all(|ingredient| {
-                inventory.get(&ingredient.name)
-                    .map(|inv_ingredient| inv_ingredient.recipe_craftable && inv_ingredient.quantity >= ingredient.quantity)
-                    .unwrap_or(false)
+    // Ingredients that fall short for `count` crafts, with how many more of each are needed
+    pub fn missing_ingredients(&self, count: u32, available: impl Fn(&str) -> u32) -> Vec<(String, u32)> {
+        self.ingredients.iter()
+            .filter_map(|ingredient| {
+                let deficit = ingredient.required_for(count).saturating_sub(available(&ingredient.name));
+                (deficit > 0).then(|| (ingredient.name.clone(), deficit))
             })
-        } else {
-            false
-        }
+            .collect()
     }
 
-    pub async fn craft(&mut self, recipe_name: &str, inventory: &mut HashMap<String, Ingredient>) -> Option<String> {
-        if self.can_craft(recipe_name, inventory) {
-            let recipe = self.get_recipe(recipe_name)?;
-            
-            // Consume ingredients
-            for ingredient in &recipe.ingredients {
-                if let Some(inv_ingredient) = inventory.get_mut(&ingredient.name) {
-                    inv_ingredient.quantity -= ingredient.quantity;
-                }
-            }
-
-            // Simulate crafting time
-            tokio::time::sleep(tokio::time::Duration::from_secs(recipe.base_cook_time.into())).await;
+    pub fn outputs(&self) -> Vec<String> {
+        let mut outputs = vec![self.outcome.clone()];
+        outputs.extend(self.byproducts.iter().cloned());
+        outputs
+    }
 
-            // Update recipe
-            if let Some(recipe) = self.recipes.get_mut(recipe_name) {
-                recipe.increment_cook_count();
+    // Everything the recipe can produce at any crafter, without duplicates
+    pub fn all_outputs(&self) -> Vec<String> {
+        let mut outputs = self.outputs();
+        let mut names: Vec<&String> = self.crafter_outputs.keys().collect();
+        names.sort();
+        for name in names {
+            for output in &self.crafter_outputs[name] {
+                if !outputs.contains(output) {
+                    outputs.push(output.clone());
+                }
             }
-
-            Some(recipe.outcome.clone())
-        } else {
-            None
         }
+        outputs
+    }
+
+    // Falls back to `outputs` when no crafter is given or it has no override
+    pub fn outputs_for(&self, crafter: Option<&Crafter>) -> Vec<String> {
+        crafter.and_then(|crafter| self.crafter_outputs.get(&crafter.name))
+            .filter(|outputs| !outputs.is_empty())
+            .cloned()
+            .unwrap_or_else(|| self.outputs())
     }
 
-    pub fn import_recipes_from_file(&mut self, filename: &str) -> Result<(), Box<dyn std::error::Error>> {
-        let file = std::fs::File::open(filename)?;
-        let reader = std::io::BufReader::new(file);
 
-        if filename.ends_with(".json") {
-            let recipes: Vec<Recipe> = serde_json::from_reader(reader)?;
-            for recipe in recipes {
-                self.add_recipe(recipe);
+    // Base ingredients come first; ours replace same-named ones or are added after them.
+    // Crafters are the union of both lists.
+    pub fn inherit_from(&self, base: &Recipe) -> Recipe {
+        let mut merged = self.clone();
+
+        let mut ingredients = base.ingredients.clone();
+        for ingredient in &self.ingredients {
+            match ingredients.iter_mut().find(|existing| existing.name == ingredient.name) {
+                Some(existing) => *existing = ingredient.clone(),
+                None => ingredients.push(ingredient.clone()),
             }
-        } else if filename.ends_with(".csv") {
-            let mut csv_reader = csv::Reader::from_reader(reader);
-            for result in csv_reader.deserialize() {
-                let recipe: Recipe = result?;
-                self.add_recipe(recipe);
+        }
+        merged.ingredients = ingredients;
+
+        let mut crafters = base.crafters.clone();
+        for crafter in &self.crafters {
+            if !crafters.contains(crafter) {
+                crafters.push(crafter.clone());
             }
-        } else {
-            return Err(Box::new(std::io::Error::new(std::io::ErrorKind::Other, "Unsupported file format")));
         }
+        merged.crafters = crafters;
+        merged.sound = self.sound.clone().or_else(|| base.sound.clone());
+        merged.animation = self.animation.clone().or_else(|| base.animation.clone());
 
-        Ok(())
+        merged
+    }
+
+    // Recipes without crafters are made by hand and so can be crafted anywhere
+    pub fn is_handcraftable(&self) -> bool {
+        self.crafters.is_empty()
+    }
+
+    pub fn can_craft_at(&self, crafter: &Crafter) -> bool {
+        self.is_handcraftable() || self.crafters.contains(crafter)
     }
 }
 
-#[derive(Debug)]
-pub struct RecipeSmith {
-    initialized: bool,
-    recipe_book: Arc<RwLock<RecipeBook>>,
-    player_inventories: Arc<RwLock<HashMap<String, PlayerInventory>>>,
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Item {
+    pub name: String,
+    pub model: Option<String>,
+    pub meta_tags: HashMap<String, serde_json::Value>,
+    #[serde(default = "default_quantity")]
+    pub quantity: u32,
+    #[serde(default = "default_max_stack")]
+    pub max_stack: u32,
 }
 
-impl RecipeSmith {
-    pub fn new() -> Self {
-        Self {
-            initialized: false,
-            recipe_book: Arc::new(RwLock::new(RecipeBook::new())),
-            player_inventories: Arc::new(RwLock::new(HashMap::new())),
-        }
-    }
+fn default_quantity() -> u32 {
+    1
+}
 
-    async fn initialize_recipe_smith(&mut self, context: &mut PluginContext) {
-        if !self.initialized {
-            println!("RecipeSmith initializing...");
-            self.register_custom_event("recipe_learned", context).await;
-            self.register_custom_event("item_crafted", context).await;
-            self.register_custom_event("inventory_changed", context).await;
-            self.register_custom_event("recipe_mastered", context).await;
-            self.register_custom_event("crafting_failed", context).await;
-            self.register_custom_event("storage_container_created", context).await;
-            self.register_custom_event("storage_container_accessed", context).await;
+fn default_max_stack() -> u32 {
+    1
+}
 
-            // Load recipes from files
-            let mut recipe_book = self.recipe_book.write().await;
-            if let Err(e) = recipe_book.import_recipes_from_file("recipes.json") {
-                println!("Error importing recipes from JSON: {}", e);
-            }
-            if let Err(e) = recipe_book.import_recipes_from_file("recipes.csv") {
-                println!("Error importing recipes from CSV: {}", e);
-            }
+impl Item {
+    // A single untagged item with no model that doesn't stack
+    pub fn new(name: impl Into<String>) -> Self {
+        Item::with_quantity(name, default_quantity())
+    }
 
-            self.initialized = true;
-            println!("RecipeSmith initialized!");
+    pub fn with_quantity(name: impl Into<String>, quantity: u32) -> Self {
+        Item {
+            name: name.into(),
+            model: None,
+            meta_tags: HashMap::new(),
+            quantity,
+            max_stack: default_max_stack(),
         }
     }
 
-    async fn create_player_inventory(&self, player_id: &str, num_slots: u32) {
-        let mut inventories = self.player_inventories.write().await;
-        inventories.insert(player_id.to_string(), PlayerInventory::new(num_slots));
+    pub fn model(mut self, model: impl Into<String>) -> Self {
+        self.model = Some(model.into());
+        self
     }
 
-    async fn get_player_inventory(&self, player_id: &str) -> Option<PlayerInventory> {
-        let inventories = self.player_inventories.read().await;
-        inventories.get(player_id).cloned()
+    pub fn tag(mut self, key: impl Into<String>, value: impl Into<serde_json::Value>) -> Self {
+        self.meta_tags.insert(key.into(), value.into());
+        self
     }
 
-    async fn update_player_inventory(&self, player_id: &str, inventory: PlayerInventory) {
-        let mut inventories = self.player_inventories.write().await;
-        inventories.insert(player_id.to_string(), inventory);
+    pub fn max_stack(mut self, max_stack: u32) -> Self {
+        self.max_stack = max_stack;
+        self
     }
 
-    async fn craft_item(&self, player_id: &str, recipe_name: &str, context: &mut PluginContext) -> Option<String> {
-        let mut recipe_book = self.recipe_book.write().await;
-        let mut player_inventory = self.get_player_inventory(player_id).await?;
-
-        let mut inventory_map: HashMap<String, Ingredient> = player_inventory.slots.iter()
-            .filter_map(|(_slot, item_opt)| item_opt.as_ref().map(|item| (item.name.clone(), Ingredient {
-                name: item.name.clone(),
-                quantity: 1,
-                recipe_craftable: true,
-            })))
-            .collect();
+    pub fn is_stackable(&self) -> bool {
+        self.max_stack > 1
+    }
 
-        if let Some(crafted_item) = recipe_book.craft(recipe_name, &mut inventory_map).await {
-            // Update player inventory
-            for (_slot, item) in player_inventory.slots.iter_mut() {
-                if let Some(inv_item) = item {
-                    if let Some(ingredient) = inventory_map.get(&inv_item.name) {
-                        if ingredient.quantity == 0 {
-                            *item = None;
-                        }
-                    }
-                }
-            }
+    pub fn can_stack_with(&self, other: &Item, policy: StackMergePolicy) -> bool {
+        // A non-stackable item never joins a stack, even one left with a larger `max_stack`
+        if !self.is_stackable() || !other.is_stackable() || self.name != other.name {
+            return false;
+        }
+        match policy {
+            StackMergePolicy::ExactTags => canonical_tags(&self.meta_tags) == canonical_tags(&other.meta_tags),
+            StackMergePolicy::CompatibleTags => canonical_tags(&self.meta_tags).is_compatible_with(&canonical_tags(&other.meta_tags)),
+            StackMergePolicy::IgnoreTags => true,
+        }
+    }
 
-            // Add crafted item to inventory
-            for (_slot, item) in player_inventory.slots.iter_mut() {
-                if item.is_none() {
-                    *item = Some(Item {
-                        name: crafted_item.clone(),
-                        model: None,
-                        meta_tags: HashMap::new(),
-                    });
-                    break;
-                }
+    // Folds `other`'s tags into this stack after a merge; existing tags win
+    pub fn merge_tags_from(&mut self, other: &Item, policy: StackMergePolicy) {
+        if policy == StackMergePolicy::CompatibleTags {
+            for (key, value) in &other.meta_tags {
+                self.meta_tags.entry(key.clone()).or_insert_with(|| value.clone());
             }
+        }
+    }
 
-            self.update_player_inventory(player_id, player_inventory).await;
-
-            // Emit custom events
-            self.emit_custom_event(CustomEvent {
-                event_type: "item_crafted".to_string(),
-                data: Arc::new(crafted_item.clone()),
-            }, context).await;
-
-            self.emit_custom_event(CustomEvent {
-                event_type: "inventory_changed".to_string(),
-                data: Arc::new(player_id.to_string()),
-            }, context).await;
+    // The "category" tag, e.g. "potion", used for per-category inventory caps
+    pub fn category(&self) -> Option<&str> {
+        self.meta_tags.get("category").and_then(|category| category.as_str())
+    }
 
-            if recipe_book.get_recipe(recipe_name).map(|r| r.is_mastered()).unwrap_or(false) {
-                self.emit_custom_event(CustomEvent {
-                    event_type: "recipe_mastered".to_string(),
-                    data: Arc::new(recipe_name.to_string()),
-                }, context).await;
-            }
+    // Rank of the "rarity" tag, higher is rarer: common, uncommon, rare, epic, legendary,
+    // or a plain number. None when the tag is missing or not recognised.
+    pub fn rarity(&self) -> Option<u32> {
+        match self.meta_tags.get("rarity")? {
+            serde_json::Value::Number(rank) => rank.as_u64().map(|rank| rank.min(u32::MAX as u64) as u32),
+            serde_json::Value::String(rarity) => match rarity.to_ascii_lowercase().as_str() {
+                "common" => Some(0),
+                "uncommon" => Some(1),
+                "rare" => Some(2),
+                "epic" => Some(3),
+                "legendary" => Some(4),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
 
-            Some(crafted_item)
-        } else {
-            self.emit_custom_event(CustomEvent {
-                event_type: "crafting_failed".to_string(),
-                data: Arc::new(recipe_name.to_string()),
-            }, context).await;
-            None
+    // Rank of the "quality" tag, see `quality_rank`
+    pub fn quality(&self) -> Option<u32> {
+        match self.meta_tags.get("quality")? {
+            serde_json::Value::Number(rank) => rank.as_u64().map(|rank| rank.min(u32::MAX as u64) as u32),
+            serde_json::Value::String(quality) => quality_rank(quality),
+            _ => None,
         }
     }
 }
 
+// Items are the same item when name, model and tags match; quantity and stack size don't matter
+impl PartialEq for Item {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name && self.model == other.model && canonical_tags(&self.meta_tags) == canonical_tags(&other.meta_tags)
+    }
+}
 
-#[async_trait]
-impl BaseAPI for RecipeSmith {
-    async fn on_game_event(&self, event: &GameEvent) {
-        match event {
-            GameEvent::PlayerJoined(player) => {
-                println!("RecipeSmith: Player {} joined. Initializing crafting data...", player.id);
-                self.create_player_inventory(&player.id, 20).await; // Assuming 20 inventory slots
-            }
-            GameEvent::Custom(custom_event) => {
-                match custom_event.event_type.as_str() {
-                    "recipe_learned" => println!("RecipeSmith: New recipe learned!"),
-                    "item_crafted" => println!("RecipeSmith: Item crafted!"),
-                    "inventory_changed" => println!("RecipeSmith: Inventory updated!"),
-                    "recipe_mastered" => println!("RecipeSmith: Recipe mastered!"),
-                    "crafting_failed" => println!("RecipeSmith: Crafting failed!"),
-                    "storage_container_created" => println!("RecipeSmith: New storage container created!"),
-                    "storage_container_accessed" => println!("RecipeSmith: Storage container accessed!"),
-                    _ => {}
-                }
-            }
-            _ => {}
-        }
+impl Eq for Item {}
+
+impl Hash for Item {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.name.hash(state);
+        self.model.hash(state);
+        canonical_tags(&self.meta_tags).hash(state);
     }
+}
+
+// Tags in a form that compares and hashes the same however they were built: keys are
+// sorted at every level and numbers are normalised, so 1 and 1.0 match. Strings and
+// numbers stay distinct, so "1" and 1 don't.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct CanonicalTags(BTreeMap<String, String>);
 
-    async fn on_game_tick(&self, _delta_time: f64) {
-        // Implement tick logic if needed
+impl CanonicalTags {
+    // The canonical JSON text of a tag's value
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).map(|value| value.as_str())
     }
 
-    async fn register_custom_event(&self, event_type: &str, context: &mut PluginContext) {
-        context.register_for_custom_event(event_type, Arc::new(self.clone())).await;
+    // Tags both sides have must agree; tags only one side has don't matter
+    pub fn is_compatible_with(&self, other: &CanonicalTags) -> bool {
+        self.0.iter().all(|(key, value)| other.0.get(key).map(|other_value| other_value == value).unwrap_or(true))
     }
+}
 
-    async fn emit_custom_event(&self, event: CustomEvent, context: &mut PluginContext) {
-        context.dispatch_custom_event(event).await;
+pub fn canonical_tags(tags: &HashMap<String, serde_json::Value>) -> CanonicalTags {
+    CanonicalTags(tags.iter().map(|(key, value)| (key.clone(), canonical_value(value))).collect())
+}
+
+fn canonical_value(value: &serde_json::Value) -> String {
+    match value {
+        serde_json::Value::Null => "null".to_string(),
+        serde_json::Value::Bool(b) => b.to_string(),
+        serde_json::Value::Number(n) => match (n.as_i64(), n.as_u64(), n.as_f64()) {
+            (Some(i), _, _) => i.to_string(),
+            (_, Some(u), _) => u.to_string(),
+            (_, _, Some(f)) if f.fract() == 0.0 && f >= i64::MIN as f64 && f <= i64::MAX as f64 => (f as i64).to_string(),
+            _ => n.to_string(),
+        },
+        serde_json::Value::String(s) => serde_json::Value::String(s.clone()).to_string(),
+        serde_json::Value::Array(values) => {
+            let values: Vec<String> = values.iter().map(canonical_value).collect();
+            format!("[{}]", values.join(","))
+        }
+        serde_json::Value::Object(map) => {
+            let mut entries: Vec<(&String, String)> = map.iter().map(|(key, value)| (key, canonical_value(value))).collect();
+            entries.sort_by(|a, b| a.0.cmp(b.0));
+            let entries: Vec<String> = entries.into_iter()
+                .map(|(key, value)| format!("{}:{}", serde_json::Value::String(key.clone()), value))
+                .collect();
+            format!("{{{}}}", entries.join(","))
+        }
     }
+}
 
-    fn as_any(&self) -> &dyn std::any::Any {
-        self
+// FNV-1a with fixed-width little-endian integers, so a checksum means the same thing
+// across Rust versions, processes and platforms (DefaultHasher guarantees none of that)
+struct StableHasher(u64);
+
+impl StableHasher {
+    fn new() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
     }
 }
 
-impl Plugin for RecipeSmith {
-    fn on_load(&self) {
-        println!("RecipeSmith plugin loaded");
+impl Hasher for StableHasher {
+    fn finish(&self) -> u64 {
+        self.0
     }
 
-    fn on_unload(&self) {
-        println!("RecipeSmith plugin unloaded");
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(0x0000_0100_0000_01b3);
+        }
     }
 
-    fn execute(&self) {
-        println!("RecipeSmith plugin executed");
+    fn write_u16(&mut self, i: u16) {
+        self.write(&i.to_le_bytes());
     }
 
-    fn initialize(&self, context: &mut PluginContext) {
-        println!("RecipeSmith plugin initializing");
-        let mut recipe_smith = self.clone();
-        
-        // Instead of spawning a new task, we'll call initialize_recipe_smith directly
-        tokio::runtime::Runtime::new()
-            .unwrap()
-            .block_on(async {
-                recipe_smith.initialize_recipe_smith(context).await;
-            });
-        
-        println!("RecipeSmith plugin initialized");
+    fn write_u32(&mut self, i: u32) {
+        self.write(&i.to_le_bytes());
     }
 
-    fn shutdown(&self, _context: &mut PluginContext) {
-        println!("RecipeSmith plugin shut down");
+    fn write_u64(&mut self, i: u64) {
+        self.write(&i.to_le_bytes());
     }
 
-    fn on_enable(&self, _context: &mut PluginContext) {
-        println!("RecipeSmith plugin enabled");
+    fn write_u128(&mut self, i: u128) {
+        self.write(&i.to_le_bytes());
     }
 
-    fn on_disable(&self, _context: &mut PluginContext) {
-        println!("RecipeSmith plugin disabled");
+    fn write_usize(&mut self, i: usize) {
+        self.write_u64(i as u64);
     }
 }
 
-impl PluginInformation for RecipeSmith {
-    fn name(&self) -> String {
-        "RecipeSmith".to_string()
-    }
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum StackMergePolicy {
+    // Only stack items whose meta_tags are identical
+    #[default]
+    ExactTags,
+    // Stack when every tag both items carry has the same value; the merged stack keeps the union
+    CompatibleTags,
+    // Stack by name alone, keeping the destination stack's tags
+    IgnoreTags,
+}
 
-    fn get_instance(&self) -> Box<dyn SayHello> {
-        Box::new(self.clone())
-    }
+// Flat CSV row for an item; meta_tags are stored as one JSON-encoded column since they can nest
+#[derive(Debug, Clone, Deserialize, Serialize)]
+struct ItemCsvRecord {
+    name: String,
+    model: Option<String>,
+    quantity: u32,
+    max_stack: u32,
+    meta_tags: String,
 }
 
-impl SayHello for RecipeSmith {
-    fn say_hello(&self) -> String {
-        "Hello from RecipeSmith! Ready to craft some amazing items?".to_string()
+impl ItemCsvRecord {
+    fn from_item(item: &Item) -> Result<Self, serde_json::Error> {
+        // Sort keys so the same tags always encode to the same string
+        let tags: std::collections::BTreeMap<&String, &serde_json::Value> = item.meta_tags.iter().collect();
+        let meta_tags = if tags.is_empty() { String::new() } else { serde_json::to_string(&tags)? };
+        Ok(Self {
+            name: item.name.clone(),
+            model: item.model.clone(),
+            quantity: item.quantity,
+            max_stack: item.max_stack,
+            meta_tags,
+        })
+    }
+
+    fn into_item(self) -> Result<Item, serde_json::Error> {
+        let meta_tags = if self.meta_tags.trim().is_empty() {
+            HashMap::new()
+        } else {
+            serde_json::from_str(&self.meta_tags)?
+        };
+        Ok(Item {
+            name: self.name,
+            model: self.model.filter(|model| !model.is_empty()),
+            meta_tags,
+            quantity: self.quantity,
+            max_stack: self.max_stack,
+        })
     }
 }
 
-pub fn create_plugin_metadata() -> RecipeSmith {
-    RecipeSmith::new()
+// Item CSV goes through plain readers and writers, so errors name the data rather than a file
+const ITEM_CSV_SOURCE: &str = "items";
+
+pub fn export_items_to_csv<W: std::io::Write>(items: &[Item], writer: W) -> Result<(), RecipeSmithError> {
+    let mut csv_writer = csv::Writer::from_writer(writer);
+    for (index, item) in items.iter().enumerate() {
+        let record = ItemCsvRecord::from_item(item)
+            .map_err(|e| RecipeSmithError::Parse(RecipeImportError::new(ITEM_CSV_SOURCE, Some(ImportLocation::Record(index as u64 + 1)), e)))?;
+        csv_writer.serialize(record).map_err(|e| RecipeSmithError::from_item_csv(index as u64 + 1, e))?;
+    }
+    csv_writer.flush().map_err(|source| RecipeSmithError::Io { path: None, source })?;
+    Ok(())
 }
 
-impl RecipeSmith {
-    pub async fn get_all_recipes(&self) -> Vec<Recipe> {
-        let recipe_book = self.recipe_book.read().await;
-        recipe_book.recipes.values().cloned().collect()
+pub fn import_items_from_csv<R: std::io::Read>(reader: R) -> Result<Vec<Item>, RecipeSmithError> {
+    let mut csv_reader = csv::Reader::from_reader(reader);
+    let mut items = Vec::new();
+    for (index, result) in csv_reader.deserialize::<ItemCsvRecord>().enumerate() {
+        // Record numbers are 1-based and count the header row
+        let record = index as u64 + 1;
+        let item = result.map_err(|e| RecipeSmithError::from_item_csv(record, e))?
+            .into_item()
+            .map_err(|e| RecipeSmithError::Parse(RecipeImportError::new(ITEM_CSV_SOURCE, Some(ImportLocation::Record(record)), e)))?;
+        items.push(item);
     }
+    Ok(items)
+}
 
-    pub async fn get_recipes_by_crafter(&self, crafter_name: &str) -> Vec<Recipe> {
-        let recipe_book = self.recipe_book.read().await;
-        let crafter = Crafter { name: crafter_name.to_string() };
-        recipe_book.get_recipes_for_crafter(&crafter)
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ItemDefinition {
+    pub name: String,
+    #[serde(default)]
+    pub model: Option<String>,
+    #[serde(default = "default_max_stack")]
+    pub max_stack: u32,
+    #[serde(default)]
+    pub meta_tags: HashMap<String, serde_json::Value>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct ItemRegistry {
+    pub items: HashMap<String, ItemDefinition>,
+}
+
+impl ItemRegistry {
+    pub fn register(&mut self, definition: ItemDefinition) {
+        self.items.insert(definition.name.clone(), definition);
     }
 
-    pub async fn add_new_recipe(&self, recipe: Recipe) {
-        let mut recipe_book = self.recipe_book.write().await;
-        recipe_book.add_recipe(recipe);
+    pub fn get(&self, name: &str) -> Option<&ItemDefinition> {
+        self.items.get(name)
     }
 
-    pub async fn get_player_inventory_contents(&self, player_id: &str) -> Option<Vec<Item>> {
-        let inventory = self.get_player_inventory(player_id).await?;
-        Some(inventory.slots.values().filter_map(|item| item.clone()).collect())
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
     }
 
-    pub async fn add_item_to_player_inventory(&self, player_id: &str, item: Item) -> Result<(), String> {
-        let mut inventory = self.get_player_inventory(player_id).await.ok_or("Player inventory not found")?;
-        
-        for (slot, item_opt) in inventory.slots.iter_mut() {
-            if item_opt.is_none() {
-                *item_opt = Some(item);
-                self.get_player_inventory("player_id");
-                return Ok(());
+    // Unknown names get a bare placeholder item with no model
+    pub fn create_item(&self, name: &str, quantity: u32) -> Item {
+        match self.get(name) {
+            Some(definition) => Item {
+                name: definition.name.clone(),
+                model: definition.model.clone(),
+                meta_tags: definition.meta_tags.clone(),
+                quantity,
+                max_stack: definition.max_stack,
+            },
+            None => Item::with_quantity(name, quantity),
+        }
+    }
+}
+
+fn net_item_changes<'a>(added: impl IntoIterator<Item = &'a Item>, removed: impl IntoIterator<Item = &'a Item>) -> Vec<(String, i64)> {
+    let mut changes: BTreeMap<String, i64> = BTreeMap::new();
+    for item in added {
+        *changes.entry(item.name.clone()).or_insert(0) += item.quantity as i64;
+    }
+    for item in removed {
+        *changes.entry(item.name.clone()).or_insert(0) -= item.quantity as i64;
+    }
+    changes.into_iter().filter(|(_, change)| *change != 0).collect()
+}
+
+// Sparse form of a `PlayerInventory` that only writes occupied slots. Empty slots are
+// rebuilt from `capacity`, since inventories are numbered from 0 without gaps.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct CompactInventory {
+    pub capacity: u32,
+    #[serde(default)]
+    pub hotbar_size: u32,
+    pub items: BTreeMap<u32, Item>,
+}
+
+impl From<&PlayerInventory> for CompactInventory {
+    fn from(inventory: &PlayerInventory) -> Self {
+        CompactInventory {
+            capacity: inventory.slots.keys().max().map(|slot| slot + 1).unwrap_or(0),
+            hotbar_size: inventory.hotbar_size,
+            items: inventory.slots.iter()
+                .filter_map(|(slot, item)| item.clone().map(|item| (*slot, item)))
+                .collect(),
+        }
+    }
+}
+
+impl From<CompactInventory> for PlayerInventory {
+    fn from(compact: CompactInventory) -> Self {
+        let mut inventory = PlayerInventory::with_hotbar(compact.capacity, compact.hotbar_size);
+        for (slot, item) in compact.items {
+            inventory.add_item(slot, item);
+        }
+        inventory
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct PlayerInventory {
+    pub slots: HashMap<u32, Option<Item>>,
+    // Slots below this index make up the hotbar; the rest are backpack
+    #[serde(default)]
+    pub hotbar_size: u32,
+}
+
+impl PlayerInventory {
+    pub fn new(num_slots: u32) -> Self {
+        Self::with_hotbar(num_slots, 0)
+    }
+
+    pub fn with_hotbar(num_slots: u32, hotbar_size: u32) -> Self {
+        let mut slots = HashMap::new();
+        for i in 0..num_slots {
+            slots.insert(i, None);
+        }
+        Self { slots, hotbar_size: hotbar_size.min(num_slots) }
+    }
+
+    // The verbose form is the plain serde output, with a null for every empty slot
+    pub fn to_compact(&self) -> CompactInventory {
+        CompactInventory::from(self)
+    }
+
+    pub fn hotbar_slots(&self) -> Vec<u32> {
+        let mut slots: Vec<u32> = self.slots.keys().copied().filter(|slot| *slot < self.hotbar_size).collect();
+        slots.sort_unstable();
+        slots
+    }
+
+    pub fn backpack_slots(&self) -> Vec<u32> {
+        let mut slots: Vec<u32> = self.slots.keys().copied().filter(|slot| *slot >= self.hotbar_size).collect();
+        slots.sort_unstable();
+        slots
+    }
+
+    // Puts the item into an empty slot or tops up a matching stack there; whatever doesn't fit is handed back
+    pub fn place_in_slot(&mut self, slot: u32, mut item: Item, policy: StackMergePolicy) -> Result<(), Item> {
+        match self.slots.get_mut(&slot) {
+            Some(entry @ None) => {
+                *entry = Some(item);
+                Ok(())
             }
+            Some(Some(existing)) if existing.can_stack_with(&item, policy) => {
+                let merged = existing.max_stack.min(item.max_stack).saturating_sub(existing.quantity).min(item.quantity);
+                if merged > 0 {
+                    existing.merge_tags_from(&item, policy);
+                    existing.quantity += merged;
+                    item.quantity -= merged;
+                }
+                if item.quantity == 0 { Ok(()) } else { Err(item) }
+            }
+            _ => Err(item),
         }
-        
-        Err("Inventory is full".to_string())
     }
 
-    pub async fn remove_item_from_player_inventory(&self, player_id: &str, item_name: &str) -> Result<(), String> {
-        let mut inventory = self.get_player_inventory(player_id).await.ok_or("Player inventory not found")?;
-        
-        for (slot, item_opt) in inventory.slots.iter_mut() {
-            if let Some(item) = item_opt {
-                if item.name == item_name {
-                    *item_opt = None;
-                    self.get_player_inventory("player_id");
-                    return Ok(());
+    // Tops up partial stacks first, fullest first, then opens new stacks in slot order.
+    // Whatever doesn't fit is handed back; what did fit stays deposited.
+    pub fn deposit(&mut self, item: Item, policy: StackMergePolicy) -> Result<(), Item> {
+        let mut slots: Vec<u32> = self.slots.keys().copied().collect();
+        slots.sort_unstable();
+        self.deposit_into(item, policy, slots)
+    }
+
+    // Like `deposit`, but new stacks go to the backpack before the hotbar
+    pub fn deposit_output(&mut self, item: Item, policy: StackMergePolicy) -> Result<(), Item> {
+        let slots: Vec<u32> = self.backpack_slots().into_iter().chain(self.hotbar_slots()).collect();
+        self.deposit_into(item, policy, slots)
+    }
+
+    fn deposit_into(&mut self, mut item: Item, policy: StackMergePolicy, empty_slot_order: Vec<u32>) -> Result<(), Item> {
+        let mut partial: Vec<(u32, u32)> = self.slots.iter()
+            .filter_map(|(slot, existing)| existing.as_ref().map(|existing| (*slot, existing)))
+            .filter(|(_, existing)| existing.can_stack_with(&item, policy) && existing.quantity < existing.max_stack.min(item.max_stack))
+            .map(|(slot, existing)| (slot, existing.max_stack.min(item.max_stack) - existing.quantity))
+            .collect();
+        partial.sort_by_key(|(slot, space)| (*space, *slot));
+
+        for (slot, space) in partial {
+            if item.quantity == 0 {
+                break;
+            }
+            if let Some(Some(existing)) = self.slots.get_mut(&slot) {
+                let merged = space.min(item.quantity);
+                existing.merge_tags_from(&item, policy);
+                existing.quantity += merged;
+                item.quantity -= merged;
+            }
+        }
+
+        for slot in empty_slot_order {
+            if item.quantity == 0 {
+                break;
+            }
+            if self.get_item(slot).is_none() {
+                let mut stack = item.clone();
+                stack.quantity = item.quantity.min(item.max_stack.max(1));
+                item.quantity -= stack.quantity;
+                self.add_item(slot, stack);
+            }
+        }
+
+        if item.quantity == 0 { Ok(()) } else { Err(item) }
+    }
+
+    // Crafting output goes to the backpack first so the hotbar stays under the player's control
+    pub fn insert_output(&mut self, item: Item) -> Result<u32, Item> {
+        let slot = self.backpack_slots().into_iter()
+            .chain(self.hotbar_slots())
+            .find(|slot| self.get_item(*slot).is_none());
+        match slot {
+            Some(slot) => {
+                self.add_item(slot, item);
+                Ok(slot)
+            }
+            None => Err(item),
+        }
+    }
+
+    pub fn get_item(&self, slot: u32) -> Option<&Item> {
+        self.slots.get(&slot).and_then(|item| item.as_ref())
+    }
+
+    pub fn add_item(&mut self, slot: u32, item: Item) {
+        self.slots.insert(slot, Some(item));
+    }
+
+    pub fn remove_item(&mut self, slot: u32) -> Option<Item> {
+        self.slots.insert(slot, None).flatten()
+    }
+
+    pub fn empty_slot(&mut self, slot: u32) {
+        self.slots.insert(slot, None);
+    }
+
+    // Empties every slot, returning what was in them in slot order
+    pub fn clear(&mut self) -> Vec<Item> {
+        let mut slots: Vec<u32> = self.slots.keys().copied().collect();
+        slots.sort_unstable();
+        slots.into_iter().filter_map(|slot| self.remove_item(slot)).collect()
+    }
+
+    // Checks the invariants every inventory change has to keep
+    pub fn validate(&self) -> Result<(), String> {
+        for (slot, item) in &self.slots {
+            if let Some(item) = item {
+                if item.quantity == 0 {
+                    return Err(format!("Slot {} holds an empty stack of {}", slot, item.name));
+                }
+                if item.quantity > item.max_stack.max(1) {
+                    return Err(format!("Slot {} holds {} {} but the stack limit is {}", slot, item.quantity, item.name, item.max_stack));
                 }
             }
         }
-        
-        Err("Item not found in inventory".to_string())
+        Ok(())
     }
 
-    pub async fn create_storage_container(&self, num_slots: u32) -> StorageContainer {
-        StorageContainer::new(num_slots)
+    pub fn first_empty_slot(&self) -> Option<u32> {
+        self.slots.iter()
+            .filter(|(_, item)| item.is_none())
+            .map(|(slot, _)| *slot)
+            .min()
     }
 
-    pub async fn access_storage_container(&self, container: &mut StorageContainer, player_id: &str, context: &mut PluginContext) {
-        // Here you would implement the logic for a player accessing a storage container
-        // For now, we'll just emit an event
-        self.emit_custom_event(CustomEvent {
-            event_type: "storage_container_accessed".to_string(),
-            data: Arc::new((player_id.to_string(), container.uuid.to_string())),
-        }, context).await;
+    // Places the item in the first empty slot, handing it back if there's no room
+    pub fn insert_item(&mut self, item: Item) -> Result<u32, Item> {
+        match self.first_empty_slot() {
+            Some(slot) => {
+                self.add_item(slot, item);
+                Ok(slot)
+            }
+            None => Err(item),
+        }
     }
 
-    pub async fn transfer_item(&self, from_inventory: &mut PlayerInventory, to_inventory: &mut PlayerInventory, item_name: &str) -> Result<(), String> {
-        let mut item_to_transfer: Option<Item> = None;
+    pub fn count_item(&self, name: &str) -> u32 {
+        self.slots.values().flatten()
+            .filter(|item| item.name == name)
+            .map(|item| item.quantity)
+            .sum()
+    }
 
-        // Find and remove the item from the source inventory
-        for (slot, item_opt) in from_inventory.slots.iter_mut() {
-            if let Some(item) = item_opt {
-                if item.name == item_name {
-                    item_to_transfer = item_opt.take();
-                    break;
+    // Stable hash of what's in each slot, for spotting client/server divergence. Slots are
+    // visited in order, so it doesn't depend on how the map happens to be laid out.
+    pub fn checksum(&self) -> u64 {
+        let mut slots: Vec<(&u32, &Option<Item>)> = self.slots.iter().collect();
+        slots.sort_by_key(|(slot, _)| **slot);
+
+        let mut hasher = StableHasher::new();
+        for (slot, item) in slots {
+            slot.hash(&mut hasher);
+            match item {
+                Some(item) => {
+                    1u8.hash(&mut hasher);
+                    item.hash(&mut hasher);
+                    item.quantity.hash(&mut hasher);
+                    item.max_stack.hash(&mut hasher);
                 }
+                None => 0u8.hash(&mut hasher),
             }
         }
+        hasher.finish()
+    }
 
-        // If we found the item, add it to the destination inventory
-        if let Some(item) = item_to_transfer {
-            for (slot, item_opt) in to_inventory.slots.iter_mut() {
-                if item_opt.is_none() {
-                    *item_opt = Some(item);
-                    return Ok(());
+    // Net change per item name since `before`, sorted by name; unchanged names are left out
+    pub fn diff(&self, before: &PlayerInventory) -> Vec<(String, i64)> {
+        net_item_changes(self.slots.values().flatten(), before.slots.values().flatten())
+    }
+
+    pub fn count_for(&self, ingredient: &Ingredient) -> u32 {
+        self.slots.values().flatten()
+            .filter(|item| ingredient.accepts(item))
+            .map(|item| item.quantity)
+            .sum()
+    }
+
+    // How much of `name` the recipe can use; quality-restricted ingredients only count qualifying stacks
+    pub fn available_for(&self, recipe: &Recipe, name: &str) -> u32 {
+        match recipe.ingredients.iter().find(|ingredient| ingredient.name == name) {
+            Some(ingredient) => self.count_for(ingredient),
+            None => self.count_item(name),
+        }
+    }
+
+    pub fn count_category(&self, category: &str) -> u32 {
+        self.slots.values().flatten()
+            .filter(|item| item.category() == Some(category))
+            .map(|item| item.quantity)
+            .sum()
+    }
+
+    // Caps apply to the total held across all slots, however many slots are free
+    pub fn check_category_cap(&self, item: &Item, caps: &HashMap<String, u32>) -> Result<(), String> {
+        let category = match item.category() {
+            Some(category) => category,
+            None => return Ok(()),
+        };
+        match caps.get(category) {
+            Some(cap) if self.count_category(category).saturating_add(item.quantity) > *cap => {
+                Err(format!("Category limit reached: at most {} {} items can be held", cap, category))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    pub fn take_items(&mut self, name: &str, quantity: u32, order: ConsumeOrder) -> Vec<Item> {
+        self.take_matching(|item| item.name == name, quantity, order)
+    }
+
+    // Like `take_items`, but leaves stacks that don't meet the recipe's quality requirement
+    pub fn take_for(&mut self, recipe: &Recipe, name: &str, quantity: u32, order: ConsumeOrder) -> Vec<Item> {
+        match recipe.ingredients.iter().find(|ingredient| ingredient.name == name) {
+            Some(ingredient) => self.take_matching(|item| ingredient.accepts(item), quantity, order),
+            None => self.take_items(name, quantity, order),
+        }
+    }
+
+    fn take_matching<F: Fn(&Item) -> bool>(&mut self, matches: F, quantity: u32, order: ConsumeOrder) -> Vec<Item> {
+        let mut stacks: Vec<(u32, u32)> = self.slots.iter()
+            .filter_map(|(slot, item)| item.as_ref().filter(|item| matches(item)).map(|item| (*slot, item.quantity)))
+            .collect();
+        match order {
+            ConsumeOrder::LowestSlotFirst => stacks.sort_by_key(|(slot, _)| *slot),
+            ConsumeOrder::SmallestFirst => stacks.sort_by_key(|(slot, quantity)| (*quantity, *slot)),
+            ConsumeOrder::LargestFirst => stacks.sort_by_key(|(slot, quantity)| (std::cmp::Reverse(*quantity), *slot)),
+        }
+
+        let mut taken = Vec::new();
+        let mut remaining = quantity;
+        for (slot, _) in stacks {
+            if remaining == 0 {
+                break;
+            }
+            let item_opt = match self.slots.get_mut(&slot) {
+                Some(item_opt) => item_opt,
+                None => continue,
+            };
+            let item = match item_opt.as_mut() {
+                Some(item) => item,
+                None => continue,
+            };
+            if item.quantity <= remaining {
+                remaining -= item.quantity;
+                taken.push(item_opt.take().unwrap());
+            } else {
+                item.quantity -= remaining;
+                let mut split = item.clone();
+                split.quantity = remaining;
+                remaining = 0;
+                taken.push(split);
+            }
+        }
+        taken
+    }
+
+    pub fn has_ingredients(&self, ingredients: &[Ingredient], count: u32) -> bool {
+        ingredients.iter().all(|ingredient| self.count_for(ingredient) >= ingredient.required_for(count))
+    }
+
+    // Removes `count` crafts worth of ingredients, or nothing if any are short
+    pub fn take_ingredients(&mut self, ingredients: &[Ingredient], count: u32, order: ConsumeOrder) -> Option<Vec<Item>> {
+        if !self.has_ingredients(ingredients, count) {
+            return None;
+        }
+        Some(ingredients.iter()
+            .filter(|ingredient| ingredient.consumed)
+            .flat_map(|ingredient| self.take_matching(|item| ingredient.accepts(item), ingredient.quantity * count, order))
+            .collect())
+    }
+
+    // Renames matching items and merges the renamed stacks where they fit; returns whether anything changed
+    pub fn rename_item(&mut self, old_name: &str, new_name: &str, policy: StackMergePolicy) -> bool {
+        let mut renamed = false;
+        for item in self.slots.values_mut().flatten() {
+            if item.name == old_name {
+                item.name = new_name.to_string();
+                renamed = true;
+            }
+        }
+        if renamed {
+            self.consolidate(new_name, policy);
+        }
+        renamed
+    }
+
+    // Tops up earlier stacks of `name` from later ones, freeing slots that empty out
+    pub fn consolidate(&mut self, name: &str, policy: StackMergePolicy) {
+        let slots = self.slots_containing(name);
+        for (index, &source) in slots.iter().enumerate() {
+            for &target in &slots[..index] {
+                let mut moving = match self.remove_item(source) {
+                    Some(item) => item,
+                    None => break,
+                };
+                if let Some(Some(stack)) = self.slots.get_mut(&target) {
+                    if stack.can_stack_with(&moving, policy) {
+                        let merged = stack.max_stack.saturating_sub(stack.quantity).min(moving.quantity);
+                        if merged > 0 {
+                            stack.merge_tags_from(&moving, policy);
+                            stack.quantity += merged;
+                            moving.quantity -= merged;
+                        }
+                    }
+                }
+                if moving.quantity > 0 {
+                    self.add_item(source, moving);
                 }
             }
-            // If we couldn't add the item to the destination inventory, put it back in the source
-            for (slot, item_opt) in from_inventory.slots.iter_mut() {
-                if item_opt.is_none() {
-                    *item_opt = Some(item);
-                    break;
+        }
+    }
+
+    // Merges every partial stack, then lays the items out from the lowest slot in `key` order
+    pub fn sort_and_consolidate(&mut self, key: SortKey, policy: StackMergePolicy) {
+        let names: BTreeSet<String> = self.slots.values().flatten().map(|item| item.name.clone()).collect();
+        for name in &names {
+            self.consolidate(name, policy);
+        }
+
+        let mut items: Vec<Item> = self.slots.values_mut().filter_map(Option::take).collect();
+        items.sort_by(|a, b| key.compare(a, b));
+
+        let mut slots: Vec<u32> = self.slots.keys().copied().collect();
+        slots.sort_unstable();
+        for (slot, item) in slots.into_iter().zip(items) {
+            self.add_item(slot, item);
+        }
+    }
+
+    pub fn slots_containing(&self, name: &str) -> Vec<u32> {
+        let mut slots: Vec<u32> = self.slots.iter()
+            .filter(|(_, item)| item.as_ref().map(|item| item.name == name).unwrap_or(false))
+            .map(|(slot, _)| *slot)
+            .collect();
+        slots.sort_unstable();
+        slots
+    }
+
+    // Moves into an empty slot, merges into a matching stack, or swaps with a different item
+    pub fn move_item(&mut self, from_slot: u32, to_slot: u32, policy: StackMergePolicy) -> Result<(), String> {
+        if !self.slots.contains_key(&from_slot) {
+            return Err(format!("Slot {} is out of range", from_slot));
+        }
+        if !self.slots.contains_key(&to_slot) {
+            return Err(format!("Slot {} is out of range", to_slot));
+        }
+        if from_slot == to_slot {
+            return Ok(());
+        }
+
+        let mut moving = self.remove_item(from_slot).ok_or(format!("Slot {} is empty", from_slot))?;
+        match self.remove_item(to_slot) {
+            None => {
+                self.add_item(to_slot, moving);
+            }
+            Some(mut target) if target.can_stack_with(&moving, policy) => {
+                let space = target.max_stack.saturating_sub(target.quantity);
+                let merged = space.min(moving.quantity);
+                if merged > 0 {
+                    target.merge_tags_from(&moving, policy);
+                }
+                target.quantity += merged;
+                moving.quantity -= merged;
+                self.add_item(to_slot, target);
+                if moving.quantity > 0 {
+                    self.add_item(from_slot, moving);
                 }
             }
-            Err("Destination inventory is full".to_string())
-        } else {
-            Err("Item not found in source inventory".to_string())
+            Some(target) => {
+                self.add_item(to_slot, moving);
+                self.add_item(from_slot, target);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SortKey {
+    // Alphabetical, larger stacks first within a name
+    #[default]
+    Name,
+    // Rarest first; items without a rarity tag go last
+    Rarity,
+    // Largest stacks first
+    Quantity,
+}
+
+impl SortKey {
+    pub fn compare(&self, a: &Item, b: &Item) -> std::cmp::Ordering {
+        let by_name = a.name.cmp(&b.name).then(b.quantity.cmp(&a.quantity));
+        match self {
+            SortKey::Name => by_name,
+            // Option orders None first, so compare reversed to put the rarest first and None last
+            SortKey::Rarity => b.rarity().cmp(&a.rarity()).then(by_name),
+            SortKey::Quantity => b.quantity.cmp(&a.quantity).then(a.name.cmp(&b.name)),
+        }
+    }
+}
+
+// Which stacks of an ingredient get drained first when it spans several slots
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ConsumeOrder {
+    SmallestFirst,
+    LargestFirst,
+    #[default]
+    LowestSlotFirst,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct StorageContainer {
+    pub uuid: Uuid,
+    pub inventory: PlayerInventory,
+    #[serde(default)]
+    pub owner: Option<String>,
+    // Players other than the owner who have been given access
+    #[serde(default)]
+    pub accessors: HashSet<String>,
+}
+
+impl StorageContainer {
+    pub fn new(num_slots: u32) -> Self {
+        Self {
+            uuid: Uuid::new_v4(),
+            inventory: PlayerInventory::new(num_slots),
+            owner: None,
+            accessors: HashSet::new(),
         }
     }
+
+    pub fn can_access(&self, player_id: &str) -> bool {
+        self.owner.as_deref() == Some(player_id) || self.accessors.contains(player_id)
+    }
+
+    pub fn contents(&self) -> Vec<Item> {
+        let mut slots: Vec<(&u32, &Item)> = self.inventory.slots.iter()
+            .filter_map(|(slot, item)| item.as_ref().map(|item| (slot, item)))
+            .collect();
+        slots.sort_by_key(|(slot, _)| **slot);
+        slots.into_iter().map(|(_, item)| item.clone()).collect()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BatchTimeCurve {
+    // Every item after the first costs `multiplier` x base; 1.0 means N x base
+    Linear { multiplier: f32 },
+    // base x (1 + ln N)
+    Logarithmic,
+}
+
+impl Default for BatchTimeCurve {
+    fn default() -> Self {
+        BatchTimeCurve::Linear { multiplier: 1.0 }
+    }
+}
+
+impl BatchTimeCurve {
+    pub fn total_time(&self, base_cook_time: u32, batch_size: u32) -> f64 {
+        if batch_size == 0 {
+            return 0.0;
+        }
+        let base = base_cook_time as f64;
+        let extra = (batch_size - 1) as f64;
+        match self {
+            BatchTimeCurve::Linear { multiplier } => base * (1.0 + extra * (*multiplier as f64).max(0.0)),
+            BatchTimeCurve::Logarithmic => base * (1.0 + (batch_size as f64).ln()),
+        }
+    }
+}
+
+// Bounded retry for IO errors while reading recipe files, e.g. on networked filesystems.
+// Off by default. Only errors that may clear up on their own are retried, never parse errors.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ImportRetry {
+    // Total tries including the first; 1 disables retrying
+    pub max_attempts: u32,
+    // Wait before the first retry, doubled for each one after
+    pub initial_backoff: std::time::Duration,
+}
+
+impl Default for ImportRetry {
+    fn default() -> Self {
+        Self::none()
+    }
+}
+
+impl ImportRetry {
+    pub fn none() -> Self {
+        Self { max_attempts: 1, initial_backoff: std::time::Duration::ZERO }
+    }
+
+    // For the synchronous `RecipeBook` imports; the backoff blocks the calling thread
+    pub fn run<T>(&self, mut read: impl FnMut() -> std::io::Result<T>) -> std::io::Result<T> {
+        let mut backoff = self.initial_backoff;
+        let mut attempt = 1;
+        loop {
+            match read() {
+                Ok(value) => return Ok(value),
+                Err(e) if attempt >= self.max_attempts || !Self::is_transient(&e) => return Err(e),
+                Err(e) => {
+                    warn!(attempt, error = %e, "Retrying recipe file read");
+                    std::thread::sleep(backoff);
+                    backoff = backoff.saturating_mul(2);
+                    attempt += 1;
+                }
+            }
+        }
+    }
+
+    // Same as `run`, but waits without blocking the runtime
+    pub async fn run_async<T, F, Fut>(&self, mut read: F) -> std::io::Result<T>
+    where
+        F: FnMut() -> Fut,
+        Fut: std::future::Future<Output = std::io::Result<T>>,
+    {
+        let mut backoff = self.initial_backoff;
+        let mut attempt = 1;
+        loop {
+            match read().await {
+                Ok(value) => return Ok(value),
+                Err(e) if attempt >= self.max_attempts || !Self::is_transient(&e) => return Err(e),
+                Err(e) => {
+                    warn!(attempt, error = %e, "Retrying recipe file read");
+                    tokio::time::sleep(backoff).await;
+                    backoff = backoff.saturating_mul(2);
+                    attempt += 1;
+                }
+            }
+        }
+    }
+
+    fn is_transient(error: &std::io::Error) -> bool {
+        matches!(
+            error.kind(),
+            std::io::ErrorKind::TimedOut
+                | std::io::ErrorKind::Interrupted
+                | std::io::ErrorKind::WouldBlock
+                | std::io::ErrorKind::ConnectionReset
+                | std::io::ErrorKind::ConnectionAborted
+                | std::io::ErrorKind::NotConnected
+                | std::io::ErrorKind::BrokenPipe
+        )
+    }
+}
+
+// Pulls crafting-relevant attributes off a joining player, e.g. a "smithing" skill
+#[derive(Clone)]
+pub struct PlayerAttributeMapping(pub Arc<dyn Fn(&Player) -> HashMap<String, f32> + Send + Sync>);
+
+impl PlayerAttributeMapping {
+    pub fn new(mapping: impl Fn(&Player) -> HashMap<String, f32> + Send + Sync + 'static) -> Self {
+        Self(Arc::new(mapping))
+    }
+}
+
+impl std::fmt::Debug for PlayerAttributeMapping {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("PlayerAttributeMapping")
+    }
+}
+
+// Reads a joining player's overall level
+#[derive(Clone)]
+pub struct PlayerLevelMapping(pub Arc<dyn Fn(&Player) -> u32 + Send + Sync>);
+
+impl PlayerLevelMapping {
+    pub fn new(mapping: impl Fn(&Player) -> u32 + Send + Sync + 'static) -> Self {
+        Self(Arc::new(mapping))
+    }
+}
+
+impl std::fmt::Debug for PlayerLevelMapping {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("PlayerLevelMapping")
+    }
+}
+
+// Crafted items get better as a recipe is made more often: each step is a craft count and
+// the quality tier (see `quality_rank`) outputs get once the recipe reaches it
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct QualityProgression {
+    pub steps: Vec<(u32, String)>,
+}
+
+impl QualityProgression {
+    pub fn new(steps: Vec<(u32, String)>) -> Self {
+        Self { steps }
+    }
+
+    // The tier of the highest step reached, if any
+    pub fn quality_for(&self, craft_count: u32) -> Option<&str> {
+        self.steps.iter()
+            .filter(|(threshold, _)| *threshold <= craft_count)
+            .max_by_key(|(threshold, _)| *threshold)
+            .map(|(_, quality)| quality.as_str())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum BuffEffect {
+    // Multiplies the cook time, e.g. 0.5 crafts twice as fast
+    CookTime(f32),
+    // Multiplies the outputs of every craft, e.g. 2 for double yield
+    Yield(u32),
+}
+
+// A temporary crafting modifier; see `RecipeSmith::apply_buff`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CraftingBuff {
+    pub name: String,
+    pub effect: BuffEffect,
+    // Clock reading at which the buff stops applying
+    pub expires_at: std::time::Duration,
+}
+
+#[derive(Debug, Clone)]
+pub struct RecipeSmithConfig {
+    pub batch_time_curve: BatchTimeCurve,
+    pub auto_load_recipes: bool,
+    // None processes every finished craft in the tick it finishes
+    pub max_craft_completions_per_tick: Option<usize>,
+    pub player_attribute_mapping: Option<PlayerAttributeMapping>,
+    pub player_level_mapping: Option<PlayerLevelMapping>,
+    pub consume_order: ConsumeOrder,
+    pub stack_merge_policy: StackMergePolicy,
+    pub unknown_output_policy: UnknownOutputPolicy,
+    pub hotbar_size: u32,
+    // Applied to the cook time of mastered recipes; 1.0 means no bonus
+    pub mastery_cook_time_multiplier: f32,
+    pub quality_progression: QualityProgression,
+    // Prepended to every event name, separator included, e.g. "recipesmith."
+    pub event_prefix: String,
+    pub import_retry: ImportRetry,
+    // Most items of each category (the "category" tag) a player may hold when given items
+    pub category_caps: HashMap<String, u32>,
+    // Consulted for crafted items whose registry definition has no model
+    pub model_resolver: Option<Arc<dyn ModelResolver>>,
+    // Broadcast a `storage_container_purged` event per container removed by `purge_empty_containers`
+    pub emit_container_purge_events: bool,
+    // Joining players get their saved inventory back from here, and changed inventories are saved to it
+    pub inventory_store: Option<Arc<dyn InventoryStore>>,
+    // How often game ticks save changed inventories to `inventory_store`; None only saves on request
+    pub auto_save_interval: Option<std::time::Duration>,
+    // Most recent inventory operations kept per player for diagnosing item loss; 0 disables the log
+    pub inventory_log_capacity: usize,
+    pub overflow_policy: OverflowPolicy,
+    // Size of the container created for a player the first time `OverflowPolicy::Container` needs one
+    pub overflow_container_slots: u32,
+    // Recipe names and aliases resolve whatever their case, e.g. "iron sword" finds "Iron Sword"
+    pub case_insensitive_recipe_names: bool,
+    // Largest inventory `import_player_inventory` accepts for a player who has none yet
+    pub max_inventory_slots: u32,
+}
+
+impl Default for RecipeSmithConfig {
+    fn default() -> Self {
+        Self {
+            batch_time_curve: BatchTimeCurve::default(),
+            auto_load_recipes: true,
+            max_craft_completions_per_tick: None,
+            player_attribute_mapping: None,
+            player_level_mapping: None,
+            consume_order: ConsumeOrder::default(),
+            stack_merge_policy: StackMergePolicy::default(),
+            unknown_output_policy: UnknownOutputPolicy::default(),
+            hotbar_size: 0,
+            mastery_cook_time_multiplier: 1.0,
+            quality_progression: QualityProgression::default(),
+            event_prefix: String::new(),
+            import_retry: ImportRetry::default(),
+            category_caps: HashMap::new(),
+            model_resolver: None,
+            emit_container_purge_events: false,
+            inventory_store: None,
+            auto_save_interval: None,
+            inventory_log_capacity: 0,
+            overflow_policy: OverflowPolicy::default(),
+            overflow_container_slots: 20,
+            case_insensitive_recipe_names: false,
+            max_inventory_slots: 256,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CraftError {
+    RecipeNotFound,
+    MissingIngredients,
+    CraftLimitReached,
+    WrongCrafter,
+    NoCrafterAvailable,
+    LevelTooLow,
+    RequirementsNotMet,
+    NotInitialized,
+    GlobalLimitReached,
+    Internal(String),
+}
+
+impl std::fmt::Display for CraftError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CraftError::RecipeNotFound => write!(f, "Recipe not found"),
+            CraftError::MissingIngredients => write!(f, "Missing ingredients"),
+            CraftError::CraftLimitReached => write!(f, "Craft limit reached"),
+            CraftError::WrongCrafter => write!(f, "Recipe can't be crafted with this crafter"),
+            CraftError::NoCrafterAvailable => write!(f, "No crafter for this recipe is available"),
+            CraftError::LevelTooLow => write!(f, "Player level is too low for this recipe"),
+            CraftError::RequirementsNotMet => write!(f, "Player does not meet the recipe's requirements"),
+            CraftError::NotInitialized => write!(f, "RecipeSmith has not been initialized"),
+            CraftError::GlobalLimitReached => write!(f, "Server-wide craft limit reached"),
+            CraftError::Internal(message) => write!(f, "Internal crafting error: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for CraftError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum UnknownOutputPolicy {
+    // Keep the recipe and craft a placeholder item for the undefined output
+    #[default]
+    WarnAndPlaceholder,
+    // Drop the recipe when it's loaded
+    Reject,
+}
+
+// Where crafted or given items go when they don't fit in the player's inventory
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OverflowPolicy {
+    // Gives and direct crafts fail without changing anything; finished queued crafts wait for room
+    #[default]
+    Error,
+    // Emit `item_dropped` for each item so the host can spawn it in the world
+    Drop,
+    // Move the items into the player's overflow container, dropping whatever doesn't fit there
+    Container,
+}
+
+// How `RecipeBook::merge` treats a recipe whose name is already in the book
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MergePolicy {
+    #[default]
+    KeepExisting,
+    // The incoming recipe replaces the existing one but keeps its id
+    Overwrite,
+    // Refuse the whole merge if any name collides
+    Error,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RecipeValidationError {
+    EmptyName,
+    ZeroQuantity { recipe: String, ingredient: String },
+    InvalidRange { recipe: String, ingredient: String },
+    UnknownOutput { recipe: String, item: String },
+    UnknownBase { recipe: String, base: String },
+    InheritanceCycle { recipe: String },
+    UnknownQuality { recipe: String, ingredient: String, quality: String },
+}
+
+impl std::fmt::Display for RecipeValidationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RecipeValidationError::EmptyName => write!(f, "Recipe has no name"),
+            RecipeValidationError::ZeroQuantity { recipe, ingredient } => write!(f, "Recipe {} needs zero {}", recipe, ingredient),
+            RecipeValidationError::InvalidRange { recipe, ingredient } => write!(f, "Recipe {} has a maximum below the minimum for {}", recipe, ingredient),
+            RecipeValidationError::UnknownOutput { recipe, item } => write!(f, "Recipe {} produces undefined item {}", recipe, item),
+            RecipeValidationError::UnknownBase { recipe, base } => write!(f, "Recipe {} extends unknown recipe {}", recipe, base),
+            RecipeValidationError::InheritanceCycle { recipe } => write!(f, "Recipe {} extends itself through its base recipes", recipe),
+            RecipeValidationError::UnknownQuality { recipe, ingredient, quality } => write!(f, "Recipe {} requires unknown quality {} for {}", recipe, quality, ingredient),
+        }
+    }
+}
+
+impl std::error::Error for RecipeValidationError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ImportLocation {
+    Line { line: usize, column: usize },
+    Record(u64),
+}
+
+pub trait CraftRng: std::fmt::Debug + Send + Sync {
+    // Uniform sample in [0, 1)
+    fn next_f32(&mut self) -> f32;
+}
+
+// xorshift64*; good enough for gameplay rolls and reproducible from a seed
+#[derive(Debug, Clone)]
+pub struct SeededRng {
+    state: u64,
+}
+
+impl SeededRng {
+    pub fn new(seed: u64) -> Self {
+        // A zero state would only ever produce zeros
+        Self { state: if seed == 0 { 0x9E37_79B9_7F4A_7C15 } else { seed } }
+    }
+
+    pub fn from_time() -> Self {
+        let nanos = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_nanos() as u64)
+            .unwrap_or(0);
+        Self::new(nanos)
+    }
+}
+
+impl CraftRng for SeededRng {
+    fn next_f32(&mut self) -> f32 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        let value = self.state.wrapping_mul(0x2545_F491_4F6C_DD1D);
+        (value >> 40) as f32 / (1u64 << 24) as f32
+    }
+}
+
+// Time source for everything scheduled by wall time. Only differences between readings
+// matter, so a `ManualClock` can stand in to drive time by hand.
+pub trait Clock: std::fmt::Debug + Send + Sync {
+    fn now(&self) -> std::time::Duration;
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> std::time::Duration {
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+    }
+}
+
+// Only moves when told to; clones share the same time
+#[derive(Debug, Clone, Default)]
+pub struct ManualClock {
+    now: Arc<std::sync::Mutex<std::time::Duration>>,
+}
+
+impl ManualClock {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn advance(&self, by: std::time::Duration) {
+        if let Ok(mut now) = self.now.lock() {
+            *now += by;
+        }
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> std::time::Duration {
+        self.now.lock().map(|now| *now).unwrap_or_default()
+    }
+}
+
+// Where player inventories are persisted between sessions
+pub trait InventoryStore: std::fmt::Debug + Send + Sync {
+    fn save_inventory(&self, player_id: &str, inventory: &PlayerInventory) -> std::io::Result<()>;
+    // Ok(None) when nothing has been saved for the player yet
+    fn load_inventory(&self, player_id: &str) -> std::io::Result<Option<PlayerInventory>>;
+
+    // Queued crafts hold their reserved ingredients outside any inventory, so they're saved too.
+    // Stores that don't override these lose the queues on restart.
+    fn save_craft_queues(&self, _snapshot: &CraftQueueSnapshot) -> std::io::Result<()> {
+        Ok(())
+    }
+
+    fn load_craft_queues(&self) -> std::io::Result<Option<CraftQueueSnapshot>> {
+        Ok(None)
+    }
+
+    // Server-wide craft counts by recipe id, so `max_global` caps survive a restart
+    fn save_global_craft_counts(&self, _counts: &HashMap<String, u32>) -> std::io::Result<()> {
+        Ok(())
+    }
+
+    fn load_global_craft_counts(&self) -> std::io::Result<Option<HashMap<String, u32>>> {
+        Ok(None)
+    }
+}
+
+// Every queued craft in queue order, as of `saved_at`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CraftQueueSnapshot {
+    // Clock reading when the queues were saved, used to catch up on downtime
+    pub saved_at: std::time::Duration,
+    pub jobs: Vec<QueuedCraft>,
+}
+
+// One compact JSON file per player in `dir`, named after the player id
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JsonFileStore {
+    pub dir: std::path::PathBuf,
+}
+
+impl JsonFileStore {
+    pub fn new(dir: impl Into<std::path::PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    // Anything that isn't safe in a file name is percent-encoded, so ids never collide
+    fn inventory_path(&self, player_id: &str) -> std::path::PathBuf {
+        let mut name = String::with_capacity(player_id.len());
+        for byte in player_id.bytes() {
+            if byte.is_ascii_alphanumeric() || byte == b'-' || byte == b'_' {
+                name.push(byte as char);
+            } else {
+                name.push_str(&format!("%{:02X}", byte));
+            }
+        }
+        self.dir.join(format!("{}.json", name))
+    }
+
+    // Encoded player ids never contain a '.', so this can't clash with an inventory file
+    fn craft_queues_path(&self) -> std::path::PathBuf {
+        self.dir.join("craft.queues.json")
+    }
+
+    fn global_craft_counts_path(&self) -> std::path::PathBuf {
+        self.dir.join("craft.global_counts.json")
+    }
+}
+
+impl InventoryStore for JsonFileStore {
+    fn save_inventory(&self, player_id: &str, inventory: &PlayerInventory) -> std::io::Result<()> {
+        std::fs::create_dir_all(&self.dir)?;
+        let json = serde_json::to_vec(&inventory.to_compact())?;
+        // Write then rename so a crash mid-save never leaves a truncated file behind
+        let path = self.inventory_path(player_id);
+        let temp = path.with_extension("json.tmp");
+        std::fs::write(&temp, json)?;
+        std::fs::rename(temp, path)
+    }
+
+    fn load_inventory(&self, player_id: &str) -> std::io::Result<Option<PlayerInventory>> {
+        let json = match std::fs::read(self.inventory_path(player_id)) {
+            Ok(json) => json,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        let compact: CompactInventory = serde_json::from_slice(&json)?;
+        Ok(Some(PlayerInventory::from(compact)))
+    }
+
+    fn save_craft_queues(&self, snapshot: &CraftQueueSnapshot) -> std::io::Result<()> {
+        std::fs::create_dir_all(&self.dir)?;
+        let json = serde_json::to_vec(snapshot)?;
+        let path = self.craft_queues_path();
+        let temp = path.with_extension("json.tmp");
+        std::fs::write(&temp, json)?;
+        std::fs::rename(temp, path)
+    }
+
+    fn load_craft_queues(&self) -> std::io::Result<Option<CraftQueueSnapshot>> {
+        let json = match std::fs::read(self.craft_queues_path()) {
+            Ok(json) => json,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        Ok(Some(serde_json::from_slice(&json)?))
+    }
+
+    fn save_global_craft_counts(&self, counts: &HashMap<String, u32>) -> std::io::Result<()> {
+        std::fs::create_dir_all(&self.dir)?;
+        let json = serde_json::to_vec(counts)?;
+        let path = self.global_craft_counts_path();
+        let temp = path.with_extension("json.tmp");
+        std::fs::write(&temp, json)?;
+        std::fs::rename(temp, path)
+    }
+
+    fn load_global_craft_counts(&self) -> std::io::Result<Option<HashMap<String, u32>>> {
+        let json = match std::fs::read(self.global_craft_counts_path()) {
+            Ok(json) => json,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        Ok(Some(serde_json::from_slice(&json)?))
+    }
+}
+
+// Supplies a model for crafted items the item registry has none for
+pub trait ModelResolver: std::fmt::Debug + Send + Sync {
+    fn resolve_model(&self, item_name: &str) -> Option<String>;
+}
+
+// Derives the model path from the item name, e.g. "Iron Sword" -> "models/iron_sword.glb"
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConventionModelResolver {
+    pub prefix: String,
+    pub suffix: String,
+}
+
+impl Default for ConventionModelResolver {
+    fn default() -> Self {
+        Self {
+            prefix: "models/".to_string(),
+            suffix: ".glb".to_string(),
+        }
+    }
+}
+
+impl ModelResolver for ConventionModelResolver {
+    fn resolve_model(&self, item_name: &str) -> Option<String> {
+        let stem = recipe_id_from_name(item_name);
+        if stem.is_empty() {
+            return None;
+        }
+        Some(format!("{}{}{}", self.prefix, stem, self.suffix))
+    }
+}
+
+#[derive(Debug)]
+pub struct RecipeImportError {
+    pub filename: String,
+    pub location: Option<ImportLocation>,
+    pub message: String,
+}
+
+impl RecipeImportError {
+    fn new(filename: &str, location: Option<ImportLocation>, message: impl ToString) -> Self {
+        Self {
+            filename: filename.to_string(),
+            location,
+            message: message.to_string(),
+        }
+    }
+
+    fn from_json(filename: &str, err: serde_json::Error) -> Self {
+        let location = if err.line() > 0 {
+            Some(ImportLocation::Line { line: err.line(), column: err.column() })
+        } else {
+            None
+        };
+        Self::new(filename, location, err)
+    }
+
+    fn from_csv(filename: &str, record: u64, err: csv::Error) -> Self {
+        let record = err.position().map(|pos| pos.record()).unwrap_or(record);
+        Self::new(filename, Some(ImportLocation::Record(record)), err)
+    }
+}
+
+impl std::fmt::Display for RecipeImportError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.location {
+            Some(ImportLocation::Line { line, column }) => write!(f, "{}:{}:{}: {}", self.filename, line, column, self.message),
+            Some(ImportLocation::Record(record)) => write!(f, "{} (record {}): {}", self.filename, record, self.message),
+            None => write!(f, "{}: {}", self.filename, self.message),
+        }
+    }
+}
+
+impl std::error::Error for RecipeImportError {}
+
+// Error for the import and export APIs
+#[derive(Debug)]
+pub enum RecipeSmithError {
+    // `path` is None when reading from or writing to a caller-supplied stream
+    Io { path: Option<String>, source: std::io::Error },
+    Parse(RecipeImportError),
+    UnsupportedFormat(String),
+    Validation(RecipeValidationError),
+}
+
+impl RecipeSmithError {
+    fn from_item_csv(record: u64, err: csv::Error) -> Self {
+        if err.is_io_error() {
+            RecipeSmithError::Io { path: None, source: err.into() }
+        } else {
+            RecipeSmithError::Parse(RecipeImportError::from_csv(ITEM_CSV_SOURCE, record, err))
+        }
+    }
+}
+
+impl From<RecipeImportError> for RecipeSmithError {
+    fn from(err: RecipeImportError) -> Self {
+        RecipeSmithError::Parse(err)
+    }
+}
+
+impl std::fmt::Display for RecipeSmithError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RecipeSmithError::Io { path: Some(path), source } => write!(f, "{}: {}", path, source),
+            RecipeSmithError::Io { path: None, source } => write!(f, "{}", source),
+            RecipeSmithError::Parse(e) => write!(f, "{}", e),
+            RecipeSmithError::UnsupportedFormat(path) => write!(f, "{}: Unsupported file format", path),
+            RecipeSmithError::Validation(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for RecipeSmithError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            RecipeSmithError::Io { source, .. } => Some(source),
+            RecipeSmithError::Parse(e) => Some(e),
+            RecipeSmithError::UnsupportedFormat(_) => None,
+            RecipeSmithError::Validation(e) => Some(e),
+        }
+    }
+}
+
+impl From<RecipeValidationError> for RecipeSmithError {
+    fn from(err: RecipeValidationError) -> Self {
+        RecipeSmithError::Validation(err)
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct RecipeBook {
+    pub recipes: HashMap<String, Recipe>,
+    pub crafters: HashMap<Crafter, Vec<String>>,
+    #[serde(default)]
+    pub outputs: HashMap<String, Vec<String>>,
+    #[serde(default)]
+    pub names: HashMap<String, String>,
+    #[serde(default)]
+    pub items: ItemRegistry,
+    #[serde(default)]
+    pub tags: HashMap<String, Vec<String>>,
+    // Extra names (translations, shortcuts) that resolve to a recipe id
+    #[serde(default)]
+    pub aliases: HashMap<String, String>,
+    // Crafts per recipe id, kept out of the definitions so those can be reloaded without losing
+    // progress. A definition's `cook_count` is only ever used as the starting value.
+    #[serde(default)]
+    pub craft_counts: HashMap<String, u32>,
+    // Crafts per recipe id across all players for `max_global`. Unlike `craft_counts` this
+    // is never reset, so clearing mastery progress doesn't lift a production cap. RecipeSmith
+    // keeps it in the inventory store across restarts.
+    #[serde(default)]
+    pub global_craft_counts: HashMap<String, u32>,
+    // Broken or offline stations; recipes treat them as if they didn't exist
+    #[serde(default)]
+    pub disabled_crafters: HashSet<Crafter>,
+    // Lets names and aliases resolve whatever their case; see `set_case_insensitive`
+    #[serde(skip)]
+    case_insensitive: bool,
+    // Folded name or alias -> recipe id, only kept while `case_insensitive` is on
+    #[serde(skip)]
+    folded_names: HashMap<String, String>,
+}
+
+// Key for case-insensitive lookups; display names themselves are never changed
+fn fold_recipe_name(name: &str) -> String {
+    name.trim().to_lowercase()
+}
+
+const MASTERY_CRAFT_COUNT: u32 = 10;
+
+// Frozen copy of a recipe book for readers that can't take the async lock, e.g. render
+// threads. Later changes to the live book never show up here; take a new snapshot instead.
+#[derive(Debug)]
+pub struct RecipeBookView {
+    book: RecipeBook,
+}
+
+impl RecipeBookView {
+    pub fn get_recipe(&self, name: &str) -> Option<Recipe> {
+        self.book.get_recipe(name)
+    }
+
+    pub fn search_recipes(&self, query: &str) -> Vec<Recipe> {
+        self.book.search_recipes(query)
+    }
+
+    pub fn recipes(&self, crafter: Option<&Crafter>, output: Option<&str>, tag: Option<&str>) -> Vec<Recipe> {
+        self.book.recipes(crafter, output, tag)
+    }
+
+    pub fn get_recipes_for_crafter(&self, crafter: &Crafter) -> Vec<Recipe> {
+        self.book.get_recipes_for_crafter(crafter)
+    }
+
+    pub fn handcraftable_recipes(&self) -> Vec<Recipe> {
+        self.book.handcraftable_recipes()
+    }
+
+    pub fn find_duplicates(&self) -> Vec<Vec<String>> {
+        self.book.find_duplicates()
+    }
+
+    pub fn cheapest_recipe_for(&self, output: &str, cost_fn: impl Fn(&Ingredient) -> f64) -> Option<Recipe> {
+        self.book.cheapest_recipe_for(output, cost_fn)
+    }
+
+    pub fn recipes_producing(&self, output_name: &str) -> Vec<Recipe> {
+        self.book.recipes_producing(output_name)
+    }
+
+    pub fn recipes_in_group(&self, group: &str) -> Vec<Recipe> {
+        self.book.recipes_in_group(group)
+    }
+
+    pub fn all_ingredients(&self) -> BTreeSet<String> {
+        self.book.all_ingredients()
+    }
+
+    pub fn items(&self) -> &ItemRegistry {
+        &self.book.items
+    }
+
+    pub fn get_recipe_craft_count(&self, recipe_name: &str) -> u32 {
+        self.book.get_recipe_craft_count(recipe_name)
+    }
+
+    pub fn is_mastered(&self, recipe_name: &str) -> bool {
+        self.book.is_mastered(recipe_name)
+    }
+}
+
+impl Clone for RecipeSmith {
+    fn clone(&self) -> Self {
+        RecipeSmith {
+            initialized: Arc::clone(&self.initialized),
+            ready: Arc::clone(&self.ready),
+            rng: Arc::clone(&self.rng),
+            recipe_book: Arc::clone(&self.recipe_book),
+            player_inventories: Arc::clone(&self.player_inventories),
+            storage_containers: Arc::clone(&self.storage_containers),
+            learned_recipes: Arc::clone(&self.learned_recipes),
+            craft_queues: Arc::clone(&self.craft_queues),
+            completed_crafts: Arc::clone(&self.completed_crafts),
+            deferred_events: Arc::clone(&self.deferred_events),
+            player_attributes: Arc::clone(&self.player_attributes),
+            player_levels: Arc::clone(&self.player_levels),
+            player_buffs: Arc::clone(&self.player_buffs),
+            craftable_recipes: Arc::clone(&self.craftable_recipes),
+            player_craft_counts: Arc::clone(&self.player_craft_counts),
+            craft_callbacks: Arc::clone(&self.craft_callbacks),
+            events: self.events.clone(),
+            event_recorder: Arc::clone(&self.event_recorder),
+            clock: Arc::clone(&self.clock),
+            saved_checksums: Arc::clone(&self.saved_checksums),
+            last_auto_save: Arc::clone(&self.last_auto_save),
+            inventory_logs: Arc::clone(&self.inventory_logs),
+            overflow_containers: Arc::clone(&self.overflow_containers),
+            event_handler: Arc::clone(&self.event_handler),
+            config: self.config.clone(),
+        }
+    }
+}
+
+impl RecipeBook {
+    pub fn new() -> Self {
+        Self {
+            recipes: HashMap::new(),
+            crafters: HashMap::new(),
+            outputs: HashMap::new(),
+            names: HashMap::new(),
+            items: ItemRegistry::default(),
+            tags: HashMap::new(),
+            aliases: HashMap::new(),
+            craft_counts: HashMap::new(),
+            global_craft_counts: HashMap::new(),
+            disabled_crafters: HashSet::new(),
+            case_insensitive: false,
+            folded_names: HashMap::new(),
+        }
+    }
+
+    pub fn snapshot(&self) -> Arc<RecipeBookView> {
+        Arc::new(RecipeBookView { book: self.clone() })
+    }
+
+    pub fn add_recipe(&mut self, mut recipe: Recipe) {
+        if recipe.id.is_empty() {
+            recipe.id = self.generate_recipe_id(&recipe.name);
+        }
+        if self.recipes.contains_key(&recipe.id) {
+            self.unindex_recipe(&recipe.id.clone());
+        }
+
+        for crafter in &recipe.crafters {
+            self.crafters.entry(crafter.clone()).or_insert_with(Vec::new).push(recipe.id.clone());
+        }
+        for output in recipe.all_outputs() {
+            let producers = self.outputs.entry(output).or_insert_with(Vec::new);
+            if !producers.contains(&recipe.id) {
+                producers.push(recipe.id.clone());
+            }
+        }
+        for tag in &recipe.tags {
+            let tagged = self.tags.entry(tag.clone()).or_insert_with(Vec::new);
+            if !tagged.contains(&recipe.id) {
+                tagged.push(recipe.id.clone());
+            }
+        }
+        self.names.insert(recipe.name.clone(), recipe.id.clone());
+        if self.case_insensitive {
+            self.folded_names.insert(fold_recipe_name(&recipe.name), recipe.id.clone());
+        }
+        self.recipes.insert(recipe.id.clone(), recipe);
+    }
+
+    // Exact names, ids and aliases always win; the folded lookup is only the fallback
+    pub fn set_case_insensitive(&mut self, case_insensitive: bool) {
+        self.case_insensitive = case_insensitive;
+        self.refresh_folded_names();
+    }
+
+    pub fn is_case_insensitive(&self) -> bool {
+        self.case_insensitive
+    }
+
+    fn refresh_folded_names(&mut self) {
+        self.folded_names.clear();
+        if !self.case_insensitive {
+            return;
+        }
+        // Names go in last so they win over an alias that folds to the same key
+        for (name, id) in self.aliases.iter().chain(self.names.iter()) {
+            self.folded_names.insert(fold_recipe_name(name), id.clone());
+        }
+    }
+
+    // Folds another book's recipes, item definitions and aliases into this one. Incoming
+    // recipes whose id is taken by a differently named recipe get a fresh id.
+    pub fn merge(&mut self, other: RecipeBook, policy: MergePolicy) -> Result<(), String> {
+        if policy == MergePolicy::Error {
+            let mut collisions: Vec<&String> = other.recipes.values()
+                .map(|recipe| &recipe.name)
+                .filter(|name| self.names.contains_key(*name))
+                .collect();
+            if !collisions.is_empty() {
+                collisions.sort();
+                let names: Vec<&str> = collisions.into_iter().map(String::as_str).collect();
+                return Err(format!("Recipes already exist: {}", names.join(", ")));
+            }
+        }
+
+        // Aliases point at ids, which may change on the way in, so carry them over by name
+        let alias_targets: Vec<(String, String)> = other.aliases.iter()
+            .filter_map(|(alias, id)| other.recipes.get(id).map(|recipe| (alias.clone(), recipe.name.clone())))
+            .collect();
+
+        let mut incoming: Vec<Recipe> = other.recipes.into_values().collect();
+        incoming.sort_by(|a, b| a.id.cmp(&b.id));
+        for mut recipe in incoming {
+            match self.names.get(&recipe.name) {
+                Some(_) if policy == MergePolicy::KeepExisting => continue,
+                Some(existing_id) => recipe.id = existing_id.clone(),
+                None if self.recipes.contains_key(&recipe.id) => recipe.id = String::new(),
+                None => {}
+            }
+            self.add_recipe(recipe);
+        }
+
+        for (name, definition) in other.items.items {
+            if policy == MergePolicy::Overwrite || self.items.get(&name).is_none() {
+                self.items.register(definition);
+            }
+        }
+        for (alias, name) in alias_targets {
+            // An alias that clashes with an existing name or alias is dropped
+            let _ = self.add_recipe_alias(&alias, &name);
+        }
+        Ok(())
+    }
+
+    pub fn remove_recipe(&mut self, name_or_id: &str) -> Option<Recipe> {
+        let id = self.resolve_recipe_id(name_or_id)?;
+        self.unindex_recipe(&id);
+        self.aliases.retain(|_, target| target != &id);
+        self.refresh_folded_names();
+        self.craft_counts.remove(&id);
+        self.global_craft_counts.remove(&id);
+        self.recipes.remove(&id)
+    }
+
+    pub fn add_recipe_alias(&mut self, alias: &str, canonical: &str) -> Result<(), String> {
+        let id = self.resolve_recipe_id(canonical).ok_or("Recipe not found")?;
+        if let Some(existing) = self.resolve_recipe_id(alias) {
+            if existing != id {
+                return Err(format!("{} already refers to another recipe", alias));
+            }
+            if self.recipes.contains_key(alias) || self.names.contains_key(alias) {
+                return Ok(());
+            }
+        }
+        self.aliases.insert(alias.to_string(), id);
+        self.refresh_folded_names();
+        Ok(())
+    }
+
+    pub fn remove_recipe_alias(&mut self, alias: &str) -> bool {
+        let removed = self.aliases.remove(alias).is_some();
+        self.refresh_folded_names();
+        removed
+    }
+
+    pub fn validate_recipe(&self, recipe: &Recipe) -> Result<(), RecipeValidationError> {
+        Self::validate_structure(recipe)?;
+        self.validate_outputs(recipe)
+    }
+
+    // Checks that don't depend on the rest of the book
+    fn validate_structure(recipe: &Recipe) -> Result<(), RecipeValidationError> {
+        if recipe.name.trim().is_empty() {
+            return Err(RecipeValidationError::EmptyName);
+        }
+        for ingredient in &recipe.ingredients {
+            if ingredient.quantity == 0 {
+                return Err(RecipeValidationError::ZeroQuantity { recipe: recipe.name.clone(), ingredient: ingredient.name.clone() });
+            }
+            if ingredient.max_quantity.map(|max| max < ingredient.quantity).unwrap_or(false) {
+                return Err(RecipeValidationError::InvalidRange { recipe: recipe.name.clone(), ingredient: ingredient.name.clone() });
+            }
+            if let Some(quality) = &ingredient.min_quality {
+                if quality_rank(quality).is_none() {
+                    return Err(RecipeValidationError::UnknownQuality { recipe: recipe.name.clone(), ingredient: ingredient.name.clone(), quality: quality.clone() });
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Adds every valid recipe and reports per recipe, in order, without stopping at failures
+    pub fn add_recipes(&mut self, recipes: Vec<Recipe>) -> Vec<Result<(), RecipeValidationError>> {
+        recipes.into_iter()
+            .map(|recipe| {
+                self.validate_recipe(&recipe)?;
+                self.add_recipe(recipe);
+                Ok(())
+            })
+            .collect()
+    }
+
+    // Only checked once an item registry is in use; an empty registry accepts everything
+    pub fn validate_outputs(&self, recipe: &Recipe) -> Result<(), RecipeValidationError> {
+        if self.items.is_empty() {
+            return Ok(());
+        }
+        match recipe.all_outputs().into_iter().find(|output| self.items.get(output).is_none()) {
+            Some(item) => Err(RecipeValidationError::UnknownOutput { recipe: recipe.name.clone(), item }),
+            None => Ok(()),
+        }
+    }
+
+    // Every ingredient and output name that `registry` doesn't define, ordered by recipe name
+    pub fn validate_against_registry(&self, registry: &ItemRegistry) -> RegistryValidationReport {
+        let mut recipes: Vec<&Recipe> = self.recipes.values().collect();
+        recipes.sort_by(|a, b| a.name.cmp(&b.name));
+
+        let mut report = RegistryValidationReport::default();
+        for recipe in recipes {
+            let ingredients = recipe.ingredients.iter().map(|ingredient| (ingredient.name.clone(), ReferenceKind::Ingredient));
+            let returned = recipe.ingredients.iter().filter_map(|ingredient| ingredient.transformed_to.clone());
+            let outputs = recipe.all_outputs().into_iter().chain(returned).map(|output| (output, ReferenceKind::Output));
+            for (item, kind) in ingredients.chain(outputs) {
+                if registry.get(&item).is_none() {
+                    report.unresolved.push(UnresolvedReference { recipe: recipe.name.clone(), item, kind });
+                }
+            }
+        }
+        report
+    }
+
+    // Recipes loaded without an id get one derived from their name; re-adding the
+    // same name reuses its existing id so the recipe is replaced rather than duplicated
+    fn generate_recipe_id(&self, name: &str) -> String {
+        if let Some(id) = self.names.get(name) {
+            return id.clone();
+        }
+        let base = recipe_id_from_name(name);
+        let mut id = base.clone();
+        let mut suffix = 2;
+        while self.recipes.contains_key(&id) {
+            id = format!("{}_{}", base, suffix);
+            suffix += 1;
+        }
+        id
+    }
+
+    fn unindex_recipe(&mut self, id: &str) {
+        if let Some(recipe) = self.recipes.get(id) {
+            if self.names.get(&recipe.name).map(|mapped| mapped == id).unwrap_or(false) {
+                self.names.remove(&recipe.name);
+            }
+        }
+        for ids in self.crafters.values_mut() {
+            ids.retain(|existing| existing != id);
+        }
+        self.crafters.retain(|_, ids| !ids.is_empty());
+        for ids in self.outputs.values_mut() {
+            ids.retain(|existing| existing != id);
+        }
+        self.outputs.retain(|_, ids| !ids.is_empty());
+        for ids in self.tags.values_mut() {
+            ids.retain(|existing| existing != id);
+        }
+        self.tags.retain(|_, ids| !ids.is_empty());
+        self.refresh_folded_names();
+    }
+
+    // Accepts a recipe id, a display name or an alias, in that order, then a name or alias
+    // in any case when the book is case-insensitive
+    pub fn resolve_recipe_id(&self, name_or_id: &str) -> Option<String> {
+        if self.recipes.contains_key(name_or_id) {
+            Some(name_or_id.to_string())
+        } else {
+            self.names.get(name_or_id)
+                .or_else(|| self.aliases.get(name_or_id))
+                .or_else(|| self.case_insensitive.then(|| self.folded_names.get(&fold_recipe_name(name_or_id))).flatten())
+                .cloned()
+        }
+    }
+
+    pub fn get_recipe(&self, name: &str) -> Option<Recipe> {
+        let id = self.resolve_recipe_id(name)?;
+        self.recipes.get(&id).cloned()
+    }
+
+    pub fn rename_recipe(&mut self, name_or_id: &str, new_name: &str) -> Result<(), String> {
+        let id = self.resolve_recipe_id(name_or_id).ok_or("Recipe not found")?;
+        if let Some(existing) = self.names.get(new_name).or_else(|| self.aliases.get(new_name)) {
+            if existing != &id {
+                return Err(format!("A recipe named {} already exists", new_name));
+            }
+        }
+
+        let recipe = self.recipes.get_mut(&id).ok_or("Recipe not found")?;
+        let old_name = std::mem::replace(&mut recipe.name, new_name.to_string());
+        self.names.remove(&old_name);
+        self.names.insert(new_name.to_string(), id);
+        self.refresh_folded_names();
+        Ok(())
+    }
+
+    // Includes hand recipes, which every crafter can make
+    pub fn get_recipes_for_crafter(&self, crafter: &Crafter) -> Vec<Recipe> {
+        let mut recipes: Vec<Recipe> = self.crafters.get(crafter)
+            .map(|recipe_ids| recipe_ids.iter().filter_map(|id| self.get_recipe(id)).collect())
+            .unwrap_or_else(Vec::new);
+        recipes.extend(self.handcraftable_recipes());
+        recipes
+    }
+
+    pub fn handcraftable_recipes(&self) -> Vec<Recipe> {
+        let mut recipes: Vec<Recipe> = self.recipes.values()
+            .filter(|recipe| recipe.is_handcraftable())
+            .cloned()
+            .collect();
+        recipes.sort_by(|a, b| a.name.cmp(&b.name));
+        recipes
+    }
+
+    // Case-insensitive; best matches first: exact name, name prefix, name substring, then
+    // recipes that only use a matching ingredient. Ties are ordered by name.
+    pub fn search_recipes(&self, query: &str) -> Vec<Recipe> {
+        let query = query.trim().to_lowercase();
+        if query.is_empty() {
+            return Vec::new();
+        }
+        let mut matches: Vec<(u8, &Recipe)> = self.recipes.values()
+            .filter_map(|recipe| Self::search_relevance(recipe, &query).map(|score| (score, recipe)))
+            .collect();
+        matches.sort_by(|(a_score, a), (b_score, b)| b_score.cmp(a_score).then_with(|| a.name.cmp(&b.name)));
+        matches.into_iter().map(|(_, recipe)| recipe.clone()).collect()
+    }
+
+    fn search_relevance(recipe: &Recipe, query: &str) -> Option<u8> {
+        let name = recipe.name.to_lowercase();
+        if name == query {
+            Some(3)
+        } else if name.starts_with(query) {
+            Some(2)
+        } else if name.contains(query) {
+            Some(1)
+        } else if recipe.ingredients.iter().any(|ingredient| ingredient.name.to_lowercase().contains(query)) {
+            Some(0)
+        } else {
+            None
+        }
+    }
+
+    pub fn recipes_in_group(&self, group: &str) -> Vec<Recipe> {
+        let mut recipes: Vec<Recipe> = self.recipes.values()
+            .filter(|recipe| recipe.group.as_deref() == Some(group))
+            .cloned()
+            .collect();
+        recipes.sort_by(|a, b| a.name.cmp(&b.name));
+        recipes
+    }
+
+    pub fn recipes_producing(&self, output_name: &str) -> Vec<Recipe> {
+        self.outputs.get(output_name)
+            .map(|recipe_ids| recipe_ids.iter().filter_map(|id| self.get_recipe(id)).collect())
+            .unwrap_or_else(Vec::new)
+    }
+
+    // A recipe's cost is the sum of `cost_fn` over its ingredients, which should account for
+    // the ingredient's quantity. Equal costs go to the recipe that sorts first by name.
+    pub fn cheapest_recipe_for(&self, output: &str, cost_fn: impl Fn(&Ingredient) -> f64) -> Option<Recipe> {
+        self.recipes_producing(output).into_iter()
+            .map(|recipe| (recipe.ingredients.iter().map(&cost_fn).sum::<f64>(), recipe))
+            .min_by(|(a_cost, a), (b_cost, b)| a_cost.total_cmp(b_cost).then_with(|| a.name.cmp(&b.name)))
+            .map(|(_, recipe)| recipe)
+    }
+
+    // Every ingredient name any recipe asks for, e.g. to check the item registry covers them
+    pub fn all_ingredients(&self) -> BTreeSet<String> {
+        self.recipes.values()
+            .flat_map(|recipe| recipe.ingredients.iter().map(|ingredient| ingredient.name.clone()))
+            .collect()
+    }
+
+    // Names of recipes that take the same ingredients and make the same outputs, grouped.
+    // Names, cook times and crafters are ignored, but a tool or transformed ingredient never
+    // matches a plainly consumed one. Groups and the names in them are sorted.
+    pub fn find_duplicates(&self) -> Vec<Vec<String>> {
+        type IngredientKey = (String, Option<u32>, Option<String>, bool, Option<String>);
+        type Signature = (Vec<(IngredientKey, u32)>, Vec<String>);
+        let mut groups: HashMap<Signature, Vec<String>> = HashMap::new();
+        for recipe in self.recipes.values() {
+            let mut ingredients: BTreeMap<IngredientKey, u32> = BTreeMap::new();
+            for ingredient in &recipe.ingredients {
+                let key = (
+                    ingredient.name.clone(),
+                    ingredient.max_quantity,
+                    ingredient.min_quality.clone(),
+                    ingredient.consumed,
+                    ingredient.transformed_to.clone(),
+                );
+                *ingredients.entry(key).or_insert(0) += ingredient.quantity;
+            }
+            let mut outputs = recipe.outputs();
+            outputs.sort();
+            groups.entry((ingredients.into_iter().collect(), outputs)).or_default().push(recipe.name.clone());
+        }
+
+        let mut duplicates: Vec<Vec<String>> = groups.into_values()
+            .filter(|names| names.len() > 1)
+            .map(|mut names| {
+                names.sort();
+                names
+            })
+            .collect();
+        duplicates.sort();
+        duplicates
+    }
+
+    // Names of recipes that can never be crafted: some ingredient is neither in `obtainable`
+    // nor produced by a recipe that itself can be crafted. Catches chains and cycles too.
+    pub fn find_unsatisfiable_recipes(&self, obtainable: &HashSet<String>) -> Vec<String> {
+        let mut reachable: HashSet<String> = obtainable.clone();
+        let mut satisfiable: HashSet<&String> = HashSet::new();
+        loop {
+            let newly_satisfiable: Vec<&Recipe> = self.recipes.values()
+                .filter(|recipe| !satisfiable.contains(&recipe.id))
+                .filter(|recipe| recipe.ingredients.iter().all(|ingredient| reachable.contains(&ingredient.name)))
+                .collect();
+            if newly_satisfiable.is_empty() {
+                break;
+            }
+            for recipe in newly_satisfiable {
+                satisfiable.insert(&recipe.id);
+                reachable.extend(recipe.all_outputs());
+            }
+        }
+
+        let mut unsatisfiable: Vec<String> = self.recipes.values()
+            .filter(|recipe| !satisfiable.contains(&recipe.id))
+            .map(|recipe| recipe.name.clone())
+            .collect();
+        unsatisfiable.sort();
+        unsatisfiable
+    }
+
+    // Items produced per minute of back-to-back single crafts at the recipe's first crafter,
+    // with every ranged ingredient at its maximum. A mastered recipe's cook time is scaled by
+    // `mastery_cook_time_multiplier`. Variance averages out.
+    pub fn outputs_per_minute(&self, recipe_name: &str, mastery_cook_time_multiplier: f32) -> f32 {
+        let recipe = match self.get_recipe(recipe_name) {
+            Some(recipe) => recipe,
+            None => return 0.0,
+        };
+        let output_multiplier = recipe.plan_consumption(1, |_| u32::MAX)
+            .map(|plan| plan.output_multiplier)
+            .unwrap_or(1);
+        let produced = recipe.outputs_for(recipe.crafters.first()).len() as f32 * output_multiplier as f32;
+        let mut cook_time = recipe.base_cook_time as f32;
+        if self.is_mastered(recipe_name) {
+            cook_time *= mastery_cook_time_multiplier.max(0.0);
+        }
+        if cook_time <= 0.0 {
+            return f32::INFINITY;
+        }
+        produced * 60.0 / cook_time
+    }
+
+    // Every filter that's set must match; with none set this returns the whole book
+    pub fn recipes(&self, crafter: Option<&Crafter>, output: Option<&str>, tag: Option<&str>) -> Vec<Recipe> {
+        let filters: Vec<Option<&Vec<String>>> = [
+            crafter.map(|crafter| self.crafters.get(crafter)),
+            output.map(|output| self.outputs.get(output)),
+            tag.map(|tag| self.tags.get(tag)),
+        ].into_iter().flatten().collect();
+        // Hand recipes aren't in the crafter index but can be made at any crafter
+        let handcraftable: HashSet<&String> = match crafter {
+            Some(_) => self.recipes.values().filter(|recipe| recipe.is_handcraftable()).map(|recipe| &recipe.id).collect(),
+            None => HashSet::new(),
+        };
+
+        let mut ids: Option<HashSet<&String>> = None;
+        for (index, filter) in filters.into_iter().enumerate() {
+            let mut matching: HashSet<&String> = filter.map(|ids| ids.iter().collect()).unwrap_or_default();
+            if index == 0 && crafter.is_some() {
+                matching.extend(handcraftable.iter().copied());
+            }
+            ids = Some(match ids {
+                Some(ids) => ids.intersection(&matching).copied().collect(),
+                None => matching,
+            });
+        }
+
+        let mut recipes: Vec<Recipe> = match ids {
+            Some(ids) => ids.into_iter().filter_map(|id| self.recipes.get(id).cloned()).collect(),
+            None => self.recipes.values().cloned().collect(),
+        };
+        recipes.sort_by(|a, b| a.name.cmp(&b.name));
+        recipes
+    }
+
+    pub fn can_craft(&self, recipe_name: &str, inventory: &HashMap<String, Ingredient>) -> bool {
+        if let Some(recipe) = self.get_recipe(recipe_name) {
+            recipe.ingredients.iter().all(|ingredient| {
+                inventory.get(&ingredient.name)
+                    .map(|inv_ingredient| inv_ingredient.recipe_craftable && inv_ingredient.quantity >= ingredient.quantity)
+                    .unwrap_or(false)
+            })
+        } else {
+            false
+        }
+    }
+
+    pub async fn craft(&mut self, recipe_name: &str, inventory: &mut HashMap<String, Ingredient>) -> Result<String, CraftError> {
+        let cook_time = self.get_recipe(recipe_name).map(|recipe| recipe.base_cook_time as f64).unwrap_or(0.0);
+        let outcomes = self.craft_batch(recipe_name, 1, inventory, cook_time).await?;
+        outcomes.into_iter().next().ok_or_else(|| CraftError::Internal(format!("Recipe {} produced nothing", recipe_name)))
+    }
+
+    pub fn set_crafter_enabled(&mut self, crafter: &Crafter, enabled: bool) {
+        if enabled {
+            self.disabled_crafters.remove(crafter);
+        } else {
+            self.disabled_crafters.insert(crafter.clone());
+        }
+    }
+
+    pub fn is_crafter_enabled(&self, crafter: &Crafter) -> bool {
+        !self.disabled_crafters.contains(crafter)
+    }
+
+    // Hand recipes need no station. Otherwise the given crafter, or with none given any of
+    // the recipe's crafters, has to be enabled.
+    pub fn check_crafter(&self, recipe: &Recipe, crafter: Option<&Crafter>) -> Result<(), CraftError> {
+        match crafter {
+            Some(crafter) if !recipe.can_craft_at(crafter) => Err(CraftError::WrongCrafter),
+            Some(crafter) if !recipe.is_handcraftable() && !self.is_crafter_enabled(crafter) => Err(CraftError::NoCrafterAvailable),
+            None if !recipe.is_handcraftable() && !recipe.crafters.iter().any(|crafter| self.is_crafter_enabled(crafter)) => {
+                Err(CraftError::NoCrafterAvailable)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    pub fn record_craft(&mut self, recipe_name: &str, count: u32) {
+        if let Some(id) = self.resolve_recipe_id(recipe_name) {
+            let crafted = self.craft_counts.entry(id.clone()).or_insert(0);
+            *crafted = crafted.saturating_add(count);
+            let produced = self.global_craft_counts.entry(id).or_insert(0);
+            *produced = produced.saturating_add(count);
+        }
+    }
+
+    // Crafts still allowed under the recipe's `max_global`, or None when it has no cap.
+    // `queued` is how many crafts of it are already queued across all players.
+    pub fn global_crafts_remaining(&self, recipe: &Recipe, queued: u32) -> Option<u32> {
+        let crafted = self.global_craft_counts.get(&recipe.id).copied().unwrap_or(0);
+        recipe.max_global.map(|limit| limit.saturating_sub(crafted.saturating_add(queued)))
+    }
+
+    pub fn check_global_limit(&self, recipe: &Recipe, queued: u32, count: u32) -> Result<(), CraftError> {
+        match self.global_crafts_remaining(recipe, queued) {
+            Some(remaining) if count > remaining => Err(CraftError::GlobalLimitReached),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn get_recipe_craft_count(&self, recipe_name: &str) -> u32 {
+        let id = match self.resolve_recipe_id(recipe_name) {
+            Some(id) => id,
+            None => return 0,
+        };
+        let initial = self.recipes.get(&id).map(|recipe| recipe.cook_count).unwrap_or(0);
+        initial.saturating_add(self.craft_counts.get(&id).copied().unwrap_or(0))
+    }
+
+    pub fn is_mastered(&self, recipe_name: &str) -> bool {
+        self.get_recipe_craft_count(recipe_name) >= MASTERY_CRAFT_COUNT
+    }
+
+    // Clears the crafts counted towards mastery, back to the definition's starting `cook_count`.
+    // The server-wide counts behind `max_global` are kept.
+    pub fn reset_cook_count(&mut self, recipe_name: &str) -> Result<(), CraftError> {
+        let id = self.resolve_recipe_id(recipe_name).ok_or(CraftError::RecipeNotFound)?;
+        self.craft_counts.remove(&id);
+        Ok(())
+    }
+
+    // `cook_time` is the total time for the whole batch, in seconds
+    pub async fn craft_batch(&mut self, recipe_name: &str, count: u32, inventory: &mut HashMap<String, Ingredient>, cook_time: f64) -> Result<Vec<String>, CraftError> {
+        self.craft_batch_at(recipe_name, count, inventory, cook_time, None).await
+    }
+
+    // Everything `craft_batch_at` checks before it starts cooking; nothing is changed
+    pub fn plan_batch(&self, recipe_name: &str, count: u32, inventory: &HashMap<String, Ingredient>, crafter: Option<&Crafter>) -> Result<(Recipe, ConsumptionPlan), CraftError> {
+        if count == 0 {
+            return Err(CraftError::MissingIngredients);
+        }
+        let recipe = self.get_recipe(recipe_name).ok_or(CraftError::RecipeNotFound)?;
+        self.check_crafter(&recipe, crafter)?;
+
+        let plan = recipe.plan_consumption(count, |name| {
+            inventory.get(name)
+                .filter(|inv_ingredient| inv_ingredient.recipe_craftable)
+                .map(|inv_ingredient| inv_ingredient.quantity)
+                .unwrap_or(0)
+        }).ok_or(CraftError::MissingIngredients)?;
+        Ok((recipe, plan))
+    }
+
+    // Like `craft_batch`, but at a specific crafter, which may change what comes out
+    pub async fn craft_batch_at(&mut self, recipe_name: &str, count: u32, inventory: &mut HashMap<String, Ingredient>, cook_time: f64, crafter: Option<&Crafter>) -> Result<Vec<String>, CraftError> {
+        // Plan the whole batch up front so we never consume ingredients for a partial batch
+        let (recipe, plan) = self.plan_batch(recipe_name, count, inventory, crafter)?;
+
+        // Work out every remaining quantity before touching the map, so a plan that
+        // disagrees with the inventory aborts the craft instead of underflowing. An ingredient
+        // listed twice draws on what its first entry left.
+        let mut remaining: Vec<(&String, u32)> = Vec::with_capacity(plan.consumed.len());
+        for (name, amount) in &plan.consumed {
+            let seen = remaining.iter().position(|(seen, _)| *seen == name);
+            let have = match seen {
+                Some(index) => remaining[index].1,
+                None => inventory.get(name).map(|inv_ingredient| inv_ingredient.quantity).unwrap_or(0),
+            };
+            match have.checked_sub(*amount) {
+                Some(left) => match seen {
+                    Some(index) => remaining[index].1 = left,
+                    None => remaining.push((name, left)),
+                },
+                None => {
+                    let message = format!("Recipe {} needs {} {} but only {} are available", recipe.name, amount, name, have);
+                    error!(recipe = %recipe.name, "Aborting craft: {}", message);
+                    return Err(CraftError::Internal(message));
+                }
+            }
+        }
+
+        // Consume ingredients
+        for (name, left) in remaining {
+            if let Some(inv_ingredient) = inventory.get_mut(name) {
+                inv_ingredient.quantity = left;
+            }
+        }
+
+        // Simulate crafting time; zero-time recipes finish without yielding
+        if cook_time > 0.0 {
+            tokio::time::sleep(tokio::time::Duration::from_secs_f64(cook_time)).await;
+        }
+
+        // Update recipe
+        self.record_craft(&recipe.id, count);
+
+        let outputs = recipe.outputs_for(crafter);
+        Ok((0..count * plan.output_multiplier).flat_map(|_| outputs.iter().cloned()).collect())
+    }
+
+    pub fn import_recipes_from_file(&mut self, filename: &str) -> Result<(), RecipeSmithError> {
+        self.import_recipes_from_file_with_retry(filename, ImportRetry::none())
+    }
+
+    #[instrument(skip(self))]
+    pub fn import_recipes_from_file_with_retry(&mut self, filename: &str, retry: ImportRetry) -> Result<(), RecipeSmithError> {
+        let recipes = Self::read_recipes_with_retry(filename, retry)?;
+        self.add_imported_recipes(recipes)
+    }
+
+    fn add_imported_recipes(&mut self, recipes: Vec<Recipe>) -> Result<(), RecipeSmithError> {
+        let recipes = self.resolve_inheritance(recipes)?;
+        info!(count = recipes.len(), "Imported recipes");
+        for recipe in recipes {
+            self.add_recipe(recipe);
+        }
+        Ok(())
+    }
+
+    pub fn import_recipes_from_dir(&mut self, dir: &str) -> Result<usize, RecipeSmithError> {
+        self.import_recipes_from_dir_with_retry(dir, ImportRetry::none())
+    }
+
+    // Imports every .json/.csv/.yaml/.toml file in `dir` (not recursively) in filename order,
+    // returning how many recipes were loaded. Other files are ignored.
+    #[instrument(skip(self))]
+    pub fn import_recipes_from_dir_with_retry(&mut self, dir: &str, retry: ImportRetry) -> Result<usize, RecipeSmithError> {
+        let mut paths: Vec<std::path::PathBuf> = std::fs::read_dir(dir)
+            .map_err(|source| RecipeSmithError::Io { path: Some(dir.to_string()), source })?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.is_file() && RecipeFileFormat::from_path(&path.to_string_lossy()).is_some())
+            .collect();
+        paths.sort();
+
+        // Read everything first so recipes can extend ones from other files
+        let mut recipes = Vec::new();
+        for path in paths {
+            recipes.extend(Self::read_recipes_with_retry(&path.to_string_lossy(), retry)?);
+        }
+        let recipes = self.resolve_inheritance(recipes)?;
+
+        let loaded = recipes.len();
+        for recipe in recipes {
+            self.add_recipe(recipe);
+        }
+        info!(count = loaded, "Imported recipes");
+        Ok(loaded)
+    }
+
+    // Applies `extends` for each recipe. Bases are looked up among `recipes` first, by name or
+    // id, and then in the book. Resolving is idempotent, so re-importing a child is harmless.
+    pub fn resolve_inheritance(&self, recipes: Vec<Recipe>) -> Result<Vec<Recipe>, RecipeValidationError> {
+        let mut lookup: HashMap<&str, usize> = HashMap::new();
+        for (index, recipe) in recipes.iter().enumerate() {
+            lookup.insert(recipe.name.as_str(), index);
+            if !recipe.id.is_empty() {
+                lookup.insert(recipe.id.as_str(), index);
+            }
+        }
+
+        let mut resolved: Vec<Option<Recipe>> = vec![None; recipes.len()];
+        for index in 0..recipes.len() {
+            self.resolve_extends(index, &recipes, &lookup, &mut resolved, &mut Vec::new())?;
+        }
+        Ok(resolved.into_iter().flatten().collect())
+    }
+
+    fn resolve_extends(&self, index: usize, recipes: &[Recipe], lookup: &HashMap<&str, usize>, resolved: &mut Vec<Option<Recipe>>, chain: &mut Vec<usize>) -> Result<Recipe, RecipeValidationError> {
+        if let Some(recipe) = &resolved[index] {
+            return Ok(recipe.clone());
+        }
+        let recipe = &recipes[index];
+        if chain.contains(&index) {
+            return Err(RecipeValidationError::InheritanceCycle { recipe: recipe.name.clone() });
+        }
+
+        let merged = match &recipe.extends {
+            None => recipe.clone(),
+            Some(base_name) => {
+                chain.push(index);
+                let base = match lookup.get(base_name.as_str()) {
+                    Some(&base_index) => self.resolve_extends(base_index, recipes, lookup, resolved, chain)?,
+                    None => self.get_recipe(base_name).ok_or_else(|| RecipeValidationError::UnknownBase {
+                        recipe: recipe.name.clone(),
+                        base: base_name.clone(),
+                    })?,
+                };
+                chain.pop();
+                recipe.inherit_from(&base)
+            }
+        };
+        resolved[index] = Some(merged.clone());
+        Ok(merged)
+    }
+
+    fn read_recipes_with_retry(filename: &str, retry: ImportRetry) -> Result<Vec<Recipe>, RecipeSmithError> {
+        let format = Self::recipe_file_format(filename)?;
+        // Only the read is retried; the contents are parsed once
+        let contents = retry.run(|| std::fs::read(filename))
+            .map_err(|source| RecipeSmithError::Io { path: Some(filename.to_string()), source })?;
+        Self::parse_recipes(filename, format, &contents)
+    }
+
+    fn recipe_file_format(filename: &str) -> Result<RecipeFileFormat, RecipeSmithError> {
+        RecipeFileFormat::from_path(filename).ok_or_else(|| RecipeSmithError::UnsupportedFormat(filename.to_string()))
+    }
+
+    // The whole file is rejected if any recipe in it is malformed
+    fn parse_recipes(filename: &str, format: RecipeFileFormat, contents: &[u8]) -> Result<Vec<Recipe>, RecipeSmithError> {
+        let recipes = Self::parse_recipes_file(filename, format, contents)?;
+        for recipe in &recipes {
+            Self::validate_structure(recipe)?;
+        }
+        Ok(recipes)
+    }
+
+    fn parse_recipes_file(filename: &str, format: RecipeFileFormat, reader: &[u8]) -> Result<Vec<Recipe>, RecipeSmithError> {
+        let parsed = match format {
+            RecipeFileFormat::Json => serde_json::from_reader(reader)
+                .map_err(|e| RecipeImportError::from_json(filename, e)),
+            RecipeFileFormat::Csv => {
+                let mut csv_reader = csv::Reader::from_reader(reader);
+                let mut recipes = Vec::new();
+                for (index, result) in csv_reader.deserialize().enumerate() {
+                    // Record numbers are 1-based and count the header row
+                    recipes.push(result.map_err(|e| RecipeImportError::from_csv(filename, index as u64 + 1, e))?);
+                }
+                Ok(recipes)
+            }
+            RecipeFileFormat::Yaml => serde_yaml::from_reader(reader).map_err(|e| {
+                let location = e.location().map(|loc| ImportLocation::Line { line: loc.line(), column: loc.column() });
+                RecipeImportError::new(filename, location, e)
+            }),
+            RecipeFileFormat::Toml => std::str::from_utf8(reader)
+                .map_err(|e| RecipeImportError::new(filename, None, e))
+                .and_then(|text| {
+                    toml::from_str::<TomlRecipeFile>(text)
+                        .map(|file| file.recipes)
+                        .map_err(|e| RecipeImportError::new(filename, None, e))
+                }),
+        };
+        parsed.map_err(RecipeSmithError::Parse)
+    }
+
+    // Like a strict import, except a record that fails to parse, validate or resolve its
+    // `extends` is skipped and reported instead of failing the whole file
+    pub fn import_recipes_lenient(&mut self, filename: &str) -> ImportReport {
+        self.import_recipes_lenient_with_retry(filename, ImportRetry::none())
+    }
+
+    pub fn import_recipes_lenient_with_retry(&mut self, filename: &str, retry: ImportRetry) -> ImportReport {
+        let mut report = ImportReport::default();
+
+        let format = match RecipeFileFormat::from_path(filename) {
+            Some(format) => format,
+            None => {
+                report.errors.push(RecipeImportError::new(filename, None, "Unsupported file format"));
+                return report;
+            }
+        };
+        let contents = match retry.run(|| std::fs::read(filename)) {
+            Ok(contents) => contents,
+            Err(e) => {
+                report.errors.push(RecipeImportError::new(filename, None, e));
+                return report;
+            }
+        };
+        let records = match Self::parse_recipe_records(filename, format, &contents) {
+            Ok(records) => records,
+            Err(e) => {
+                report.errors.push(e);
+                return report;
+            }
+        };
+
+        let mut parsed: Vec<(u64, Recipe)> = Vec::with_capacity(records.len());
+        for (record, result) in records {
+            let checked = result.and_then(|recipe| {
+                Self::validate_structure(&recipe)
+                    .map(|_| recipe)
+                    .map_err(|e| RecipeImportError::new(filename, Some(ImportLocation::Record(record)), e))
+            });
+            match checked {
+                Ok(recipe) => parsed.push((record, recipe)),
+                Err(e) => {
+                    report.errors.push(e);
+                    report.skipped += 1;
+                }
+            }
+        }
+
+        // Resolve each record on its own so a broken base only takes down its own children
+        let (records, recipes): (Vec<u64>, Vec<Recipe>) = parsed.into_iter().unzip();
+        let mut lookup: HashMap<&str, usize> = HashMap::new();
+        for (index, recipe) in recipes.iter().enumerate() {
+            lookup.insert(recipe.name.as_str(), index);
+            if !recipe.id.is_empty() {
+                lookup.insert(recipe.id.as_str(), index);
+            }
+        }
+        let mut resolved: Vec<Option<Recipe>> = vec![None; recipes.len()];
+        let mut accepted = Vec::with_capacity(recipes.len());
+        for (index, record) in records.iter().enumerate() {
+            match self.resolve_extends(index, &recipes, &lookup, &mut resolved, &mut Vec::new()) {
+                Ok(recipe) => accepted.push(recipe),
+                Err(e) => {
+                    report.errors.push(RecipeImportError::new(filename, Some(ImportLocation::Record(*record)), e));
+                    report.skipped += 1;
+                }
+            }
+        }
+
+        for recipe in accepted {
+            self.add_recipe(recipe);
+            report.imported += 1;
+        }
+        report
+    }
+
+    // Splits a recipe file into its records, each parsed on its own. Only a file whose outer
+    // structure can't be read fails as a whole.
+    fn parse_recipe_records(filename: &str, format: RecipeFileFormat, contents: &[u8]) -> Result<Vec<(u64, Result<Recipe, RecipeImportError>)>, RecipeImportError> {
+        let record_error = |index: usize, e: &dyn std::fmt::Display| {
+            RecipeImportError::new(filename, Some(ImportLocation::Record(index as u64 + 1)), e)
+        };
+        let records = match format {
+            RecipeFileFormat::Json => {
+                let entries: Vec<serde_json::Value> = serde_json::from_slice(contents)
+                    .map_err(|e| RecipeImportError::from_json(filename, e))?;
+                entries.into_iter().enumerate()
+                    .map(|(index, entry)| (index as u64 + 1, serde_json::from_value(entry).map_err(|e| record_error(index, &e))))
+                    .collect()
+            }
+            RecipeFileFormat::Csv => {
+                let mut csv_reader = csv::Reader::from_reader(contents);
+                csv_reader.deserialize().enumerate()
+                    .map(|(index, result)| (index as u64 + 1, result.map_err(|e| RecipeImportError::from_csv(filename, index as u64 + 1, e))))
+                    .collect()
+            }
+            RecipeFileFormat::Yaml => {
+                let entries: Vec<serde_yaml::Value> = serde_yaml::from_slice(contents).map_err(|e| {
+                    let location = e.location().map(|loc| ImportLocation::Line { line: loc.line(), column: loc.column() });
+                    RecipeImportError::new(filename, location, e)
+                })?;
+                entries.into_iter().enumerate()
+                    .map(|(index, entry)| (index as u64 + 1, serde_yaml::from_value(entry).map_err(|e| record_error(index, &e))))
+                    .collect()
+            }
+            RecipeFileFormat::Toml => {
+                #[derive(Deserialize)]
+                struct TomlRecords {
+                    recipes: Vec<toml::Value>,
+                }
+                let text = std::str::from_utf8(contents).map_err(|e| RecipeImportError::new(filename, None, e))?;
+                let file: TomlRecords = toml::from_str(text).map_err(|e| RecipeImportError::new(filename, None, e))?;
+                file.recipes.into_iter().enumerate()
+                    .map(|(index, entry)| (index as u64 + 1, entry.try_into().map_err(|e: toml::de::Error| record_error(index, &e))))
+                    .collect()
+            }
+        };
+        Ok(records)
+    }
+}
+
+// Data carried by `item_crafted`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CraftedEvent {
+    pub player_id: String,
+    pub recipe_name: String,
+    pub outputs: Vec<String>,
+    pub elapsed_secs: f64,
+    pub sound: Option<String>,
+    pub animation: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum InventoryOperation {
+    Add,
+    Remove,
+    Move,
+    Transfer,
+    Craft,
+    CancelCraft,
+    Rename,
+    Modify,
+    Import,
+}
+
+// One entry of a player's inventory log; see `RecipeSmithConfig::inventory_log_capacity`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct InventoryLogEntry {
+    pub operation: InventoryOperation,
+    // Reading of the RecipeSmith clock when it happened
+    pub at: std::time::Duration,
+    pub changes: Vec<(String, i64)>,
+    pub checksum_before: u64,
+    pub checksum_after: u64,
+}
+
+// Data carried by `craft_started`, sent when cooking begins or a craft is queued
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CraftStartedEvent {
+    pub player_id: String,
+    pub recipe_name: String,
+    pub total_cook_time: f64,
+    pub sound: Option<String>,
+    pub animation: Option<String>,
+}
+
+// Data carried by `item_dropped`: an item that didn't fit and should appear in the world
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ItemDroppedEvent {
+    pub player_id: String,
+    pub item: Item,
+}
+
+// Copy of an emitted event for in-process subscribers; `event_type` has no prefix
+#[derive(Debug, Clone)]
+pub struct BroadcastEvent {
+    pub event_type: String,
+    // The player the event concerns, when its data names one
+    pub player_id: Option<String>,
+    pub data: Arc<dyn std::any::Any + Send + Sync>,
+}
+
+impl BroadcastEvent {
+    fn player_id_of(data: &(dyn std::any::Any + Send + Sync)) -> Option<String> {
+        if let Some(crafted) = data.downcast_ref::<CraftedEvent>() {
+            Some(crafted.player_id.clone())
+        } else if let Some(started) = data.downcast_ref::<CraftStartedEvent>() {
+            Some(started.player_id.clone())
+        } else if let Some(failed) = data.downcast_ref::<CraftFailedEvent>() {
+            Some(failed.player_id.clone())
+        } else if let Some(changed) = data.downcast_ref::<InventoryChangedEvent>() {
+            Some(changed.player_id.clone())
+        } else if let Some(dropped) = data.downcast_ref::<ItemDroppedEvent>() {
+            Some(dropped.player_id.clone())
+        } else if let Some((player_id, _)) = data.downcast_ref::<(String, String)>() {
+            Some(player_id.clone())
+        } else {
+            None
+        }
+    }
+}
+
+// Receives only the events concerning one player; see `RecipeSmith::subscribe_for_player`
+#[derive(Debug)]
+pub struct PlayerEventReceiver {
+    player_id: String,
+    receiver: broadcast::Receiver<BroadcastEvent>,
+}
+
+impl PlayerEventReceiver {
+    // None once RecipeSmith is gone. Events missed by falling behind are skipped.
+    pub async fn recv(&mut self) -> Option<BroadcastEvent> {
+        loop {
+            match self.receiver.recv().await {
+                Ok(event) if event.player_id.as_deref() == Some(self.player_id.as_str()) => return Some(event),
+                Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => return None,
+            }
+        }
+    }
+}
+
+// Host hook run after every successful craft, alongside the `item_crafted` event
+#[derive(Clone)]
+pub struct CraftCompleteCallback(pub Arc<dyn Fn(&CraftedEvent) + Send + Sync>);
+
+impl std::fmt::Debug for CraftCompleteCallback {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("CraftCompleteCallback")
+    }
+}
+
+// Data carried by `inventory_changed`, sent once per operation. `changes` is the net
+// change per item name and may be empty when items only moved between slots.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct InventoryChangedEvent {
+    pub player_id: String,
+    pub changes: Vec<(String, i64)>,
+}
+
+// Data carried by `crafting_failed`; `missing` is only filled in when ingredients ran short
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CraftFailedEvent {
+    pub player_id: String,
+    pub recipe_name: String,
+    pub reason: String,
+    pub missing: Vec<(String, u32)>,
+}
+
+// What a craft would do, without doing it
+#[derive(Debug, Clone, Serialize)]
+pub struct CraftPreview {
+    pub consumed: Vec<(String, u32)>,
+    pub produced: Vec<String>,
+    // The player's inventory as it would look after the craft
+    pub inventory: PlayerInventory,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RecipeBookEntry {
+    pub recipe: Recipe,
+    pub craftable: bool,
+    pub mastered: bool,
+    pub handcraftable: bool,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct QueuedCraft {
+    pub id: Uuid,
+    pub player_id: String,
+    pub recipe_id: String,
+    pub recipe_name: String,
+    pub reserved: Vec<Item>,
+    pub total_time: f64,
+    pub remaining_time: f64,
+    #[serde(default = "default_quantity")]
+    pub output_multiplier: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RecipeFileFormat {
+    Json,
+    Csv,
+    Yaml,
+    Toml,
+}
+
+impl RecipeFileFormat {
+    fn from_path(path: &str) -> Option<Self> {
+        let extension = std::path::Path::new(path).extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "json" => Some(RecipeFileFormat::Json),
+            "csv" => Some(RecipeFileFormat::Csv),
+            "yaml" | "yml" => Some(RecipeFileFormat::Yaml),
+            "toml" => Some(RecipeFileFormat::Toml),
+            _ => None,
+        }
+    }
+}
+
+// TOML has no top-level arrays, so recipe files use `[[recipes]]` tables
+#[derive(Debug, Deserialize)]
+struct TomlRecipeFile {
+    recipes: Vec<Recipe>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReferenceKind {
+    Ingredient,
+    Output,
+}
+
+// A recipe naming an item the registry doesn't define
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnresolvedReference {
+    pub recipe: String,
+    pub item: String,
+    pub kind: ReferenceKind,
+}
+
+impl std::fmt::Display for UnresolvedReference {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.kind {
+            ReferenceKind::Ingredient => write!(f, "Recipe {} needs undefined item {}", self.recipe, self.item),
+            ReferenceKind::Output => write!(f, "Recipe {} produces undefined item {}", self.recipe, self.item),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct RegistryValidationReport {
+    pub unresolved: Vec<UnresolvedReference>,
+}
+
+impl RegistryValidationReport {
+    pub fn is_empty(&self) -> bool {
+        self.unresolved.is_empty()
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct ImportReport {
+    pub imported: usize,
+    pub skipped: usize,
+    pub errors: Vec<RecipeImportError>,
+}
+
+// How far a slow subscriber can fall behind before it starts missing events
+const EVENT_CHANNEL_CAPACITY: usize = 256;
+
+// Registered with the host on initialize, before the configured prefix is applied
+const CUSTOM_EVENT_TYPES: &[&str] = &[
+    "recipe_learned",
+    "craft_started",
+    "item_crafted",
+    "inventory_changed",
+    "recipe_mastered",
+    "crafting_failed",
+    "storage_container_created",
+    "storage_container_accessed",
+    "recipe_now_craftable",
+    "crafting_cancelled",
+    "item_dropped",
+    "buff_expired",
+    "recipe_unmastered",
+];
+
+#[derive(Debug)]
+pub struct RecipeSmith {
+    // Shared so every clone (including the one registered for events) sees initialization
+    initialized: Arc<AtomicBool>,
+    ready: Arc<Notify>,
+    rng: Arc<std::sync::Mutex<Box<dyn CraftRng>>>,
+    recipe_book: Arc<RwLock<RecipeBook>>,
+    player_inventories: Arc<RwLock<HashMap<String, PlayerInventory>>>,
+    storage_containers: Arc<RwLock<HashMap<Uuid, StorageContainer>>>,
+    learned_recipes: Arc<RwLock<HashMap<String, HashSet<String>>>>,
+    craft_queues: Arc<RwLock<HashMap<String, VecDeque<QueuedCraft>>>>,
+    // Each finished job with the net change it made to the player's inventory
+    completed_crafts: Arc<RwLock<Vec<(QueuedCraft, Vec<(String, i64)>)>>>,
+    // Raised without a plugin context, e.g. on a tick; sent with `dispatch_completed_crafts`
+    deferred_events: Arc<RwLock<Vec<BroadcastEvent>>>,
+    player_attributes: Arc<RwLock<HashMap<String, HashMap<String, f32>>>>,
+    // Cached on join through `player_level_mapping`, or set by the host
+    player_levels: Arc<RwLock<HashMap<String, u32>>>,
+    player_buffs: Arc<RwLock<HashMap<String, Vec<CraftingBuff>>>>,
+    craftable_recipes: Arc<RwLock<HashMap<String, HashSet<String>>>>,
+    // player -> recipe id -> lifetime crafts, for recipes with `max_per_player`
+    player_craft_counts: Arc<RwLock<HashMap<String, HashMap<String, u32>>>>,
+    craft_callbacks: Arc<RwLock<Vec<CraftCompleteCallback>>>,
+    events: broadcast::Sender<BroadcastEvent>,
+    // Some while recording; see `start_recording_events`
+    event_recorder: Arc<std::sync::Mutex<Option<Vec<BroadcastEvent>>>>,
+    clock: Arc<dyn Clock>,
+    // player -> checksum of the inventory as last saved, so unchanged ones aren't rewritten
+    saved_checksums: Arc<RwLock<HashMap<String, u64>>>,
+    last_auto_save: Arc<RwLock<Option<std::time::Duration>>>,
+    inventory_logs: Arc<RwLock<HashMap<String, VecDeque<InventoryLogEntry>>>>,
+    // player -> their overflow container in `storage_containers`
+    overflow_containers: Arc<RwLock<HashMap<String, Uuid>>>,
+    // The clone registered with the plugin context; weak so the smith doesn't keep itself alive
+    event_handler: Arc<std::sync::Mutex<std::sync::Weak<RecipeSmith>>>,
+    config: RecipeSmithConfig,
+}
+
+impl RecipeSmith {
+    pub fn new() -> Self {
+        Self::with_config(RecipeSmithConfig::default())
+    }
+
+    pub fn with_config(config: RecipeSmithConfig) -> Self {
+        let mut recipe_book = RecipeBook::new();
+        recipe_book.set_case_insensitive(config.case_insensitive_recipe_names);
+        Self {
+            initialized: Arc::new(AtomicBool::new(false)),
+            ready: Arc::new(Notify::new()),
+            rng: Arc::new(std::sync::Mutex::new(Box::new(SeededRng::from_time()) as Box<dyn CraftRng>)),
+            recipe_book: Arc::new(RwLock::new(recipe_book)),
+            player_inventories: Arc::new(RwLock::new(HashMap::new())),
+            storage_containers: Arc::new(RwLock::new(HashMap::new())),
+            learned_recipes: Arc::new(RwLock::new(HashMap::new())),
+            craft_queues: Arc::new(RwLock::new(HashMap::new())),
+            completed_crafts: Arc::new(RwLock::new(Vec::new())),
+            deferred_events: Arc::new(RwLock::new(Vec::new())),
+            player_attributes: Arc::new(RwLock::new(HashMap::new())),
+            player_levels: Arc::new(RwLock::new(HashMap::new())),
+            player_buffs: Arc::new(RwLock::new(HashMap::new())),
+            craftable_recipes: Arc::new(RwLock::new(HashMap::new())),
+            player_craft_counts: Arc::new(RwLock::new(HashMap::new())),
+            craft_callbacks: Arc::new(RwLock::new(Vec::new())),
+            events: broadcast::channel(EVENT_CHANNEL_CAPACITY).0,
+            event_recorder: Arc::new(std::sync::Mutex::new(None)),
+            clock: Arc::new(SystemClock),
+            saved_checksums: Arc::new(RwLock::new(HashMap::new())),
+            last_auto_save: Arc::new(RwLock::new(None)),
+            inventory_logs: Arc::new(RwLock::new(HashMap::new())),
+            overflow_containers: Arc::new(RwLock::new(HashMap::new())),
+            event_handler: Arc::new(std::sync::Mutex::new(std::sync::Weak::new())),
+            config,
+        }
+    }
+
+    // Starts with the given recipes and never touches the filesystem. Recipes are checked as
+    // in `add_new_recipe`; any that fail are logged and left out.
+    pub fn with_recipes(recipes: Vec<Recipe>) -> Self {
+        let mut recipe_smith = Self::with_config(RecipeSmithConfig {
+            auto_load_recipes: false,
+            ..RecipeSmithConfig::default()
+        });
+        let mut recipe_book = RecipeBook::new();
+        recipe_book.set_case_insensitive(recipe_smith.config.case_insensitive_recipe_names);
+
+        // Bases may come later in the list, so `extends` is resolved across the whole list. If
+        // that fails, recipes are resolved one at a time so only the broken ones are left out.
+        let batch = recipe_book.resolve_inheritance(recipes.clone());
+        if let Err(error) = &batch {
+            warn!(%error, "Resolving recipes one at a time");
+        }
+        let resolved_together = batch.is_ok();
+        for recipe in batch.unwrap_or(recipes) {
+            let resolved = if resolved_together {
+                Ok(recipe)
+            } else {
+                recipe_book.resolve_inheritance(vec![recipe]).map(|mut resolved| resolved.remove(0))
+            };
+            if let Err(error) = resolved.and_then(|recipe| recipe_smith.add_checked_recipe(&mut recipe_book, recipe)) {
+                warn!(%error, "Skipping recipe");
+            }
+        }
+
+        recipe_smith.recipe_book = Arc::new(RwLock::new(recipe_book));
+        // Nothing left to load, so it's ready straight away
+        recipe_smith.initialized.store(true, Ordering::SeqCst);
+        recipe_smith.ready.notify_waiters();
+        recipe_smith
+    }
+
+    async fn initialize_recipe_smith(&mut self, context: &mut PluginContext) {
+        if !self.is_initialized() {
+            info!("RecipeSmith initializing...");
+            self.register_custom_events(CUSTOM_EVENT_TYPES, context).await;
+
+            // Load recipes from files
+            if self.config.auto_load_recipes {
+                for file in ["recipes.json", "recipes.csv"] {
+                    if let Err(e) = self.import_recipe_file(file).await {
+                        error!(file, error = %e, "Error importing recipes");
+                    }
+                }
+            }
+
+            // Recipes added before initialize are checked the same way as loaded ones
+            {
+                let mut recipe_book = self.recipe_book.write().await;
+                self.apply_unknown_output_policy(&mut recipe_book);
+                // Outputs were already handled by the policy above, so only ingredients are reported here
+                if !recipe_book.items.is_empty() {
+                    for reference in recipe_book.validate_against_registry(&recipe_book.items).unresolved {
+                        if reference.kind == ReferenceKind::Ingredient {
+                            warn!(%reference, "Recipe references an undefined item");
+                        }
+                    }
+                }
+            }
+
+            // Restored crafts that finish count towards the caps, so the counts come back first
+            self.restore_global_craft_counts().await;
+            // Queued crafts finish against the recipes, so they come back after them
+            self.restore_craft_queues().await;
+
+            self.initialized.store(true, Ordering::SeqCst);
+            self.ready.notify_waiters();
+            info!("RecipeSmith initialized!");
+        }
+    }
+
+    // Reads and retries before locking the book, so neither a slow read nor the backoff holds it up
+    #[instrument(skip(self))]
+    async fn import_recipe_file(&self, filename: &str) -> Result<(), RecipeSmithError> {
+        let format = RecipeBook::recipe_file_format(filename)?;
+        let contents = self.config.import_retry.run_async(|| tokio::fs::read(filename)).await
+            .map_err(|source| RecipeSmithError::Io { path: Some(filename.to_string()), source })?;
+        let recipes = RecipeBook::parse_recipes(filename, format, &contents)?;
+        self.recipe_book.write().await.add_imported_recipes(recipes)
+    }
+
+    // The bare name of one of our events, or None for events without our prefix, which
+    // belong to someone else
+    fn own_event_type<'a>(&self, event_type: &'a str) -> Option<&'a str> {
+        event_type.strip_prefix(self.config.event_prefix.as_str())
+            .filter(|event_type| CUSTOM_EVENT_TYPES.contains(event_type))
+    }
+
+    // One shared handler for every event type instead of a fresh clone per registration
+    fn event_handler(&self) -> Arc<RecipeSmith> {
+        let mut cached = match self.event_handler.lock() {
+            Ok(cached) => cached,
+            Err(_) => return Arc::new(self.clone()),
+        };
+        if let Some(handler) = cached.upgrade() {
+            return handler;
+        }
+        let handler = Arc::new(self.clone());
+        *cached = Arc::downgrade(&handler);
+        handler
+    }
+
+    async fn register_custom_events(&self, event_types: &[&str], context: &mut PluginContext) {
+        let handler = self.event_handler();
+        for event_type in event_types {
+            let event_type = format!("{}{}", self.config.event_prefix, event_type);
+            context.register_for_custom_event(&event_type, Arc::clone(&handler)).await;
+        }
+    }
+
+    fn apply_unknown_output_policy(&self, recipe_book: &mut RecipeBook) {
+        let invalid: Vec<(String, RecipeValidationError)> = recipe_book.recipes.values()
+            .filter_map(|recipe| recipe_book.validate_outputs(recipe).err().map(|e| (recipe.id.clone(), e)))
+            .collect();
+
+        for (recipe_id, error) in invalid {
+            match self.config.unknown_output_policy {
+                UnknownOutputPolicy::WarnAndPlaceholder => warn!(%error, "Recipe has an unknown output"),
+                UnknownOutputPolicy::Reject => {
+                    warn!(%error, "Rejecting recipe");
+                    recipe_book.remove_recipe(&recipe_id);
+                }
+            }
+        }
+    }
+
+    // Swap in a deterministic source, e.g. `SeededRng::new(42)`
+    pub fn with_rng(mut self, rng: impl CraftRng + 'static) -> Self {
+        self.rng = Arc::new(std::sync::Mutex::new(Box::new(rng) as Box<dyn CraftRng>));
+        self
+    }
+
+    pub fn with_clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.clock = Arc::new(clock);
+        self
+    }
+
+    fn roll(&self) -> f32 {
+        self.rng.lock().map(|mut rng| rng.next_f32()).unwrap_or(0.5)
+    }
+
+    // Batch curve, then the mastery bonus, then the recipe's random variance
+    fn effective_cook_time(&self, recipe: &Recipe, count: u32, mastered: bool) -> f64 {
+        let mut cook_time = self.config.batch_time_curve.total_time(recipe.base_cook_time, count);
+        if mastered {
+            cook_time *= self.config.mastery_cook_time_multiplier.max(0.0) as f64;
+        }
+        recipe.varied_cook_time(cook_time, self.roll()).max(0.0)
+    }
+
+    pub fn is_initialized(&self) -> bool {
+        self.initialized.load(Ordering::SeqCst)
+    }
+
+    // Guards the public entry points so calls made before `initialize` fail loudly
+    fn ensure_initialized(&self) -> Result<(), CraftError> {
+        if self.is_initialized() {
+            Ok(())
+        } else {
+            warn!("RecipeSmith used before it was initialized");
+            Err(CraftError::NotInitialized)
+        }
+    }
+
+    // Resolves once recipes have been loaded; returns immediately if that already happened
+    pub async fn wait_until_ready(&self) {
+        loop {
+            // Register before checking so a notification between the two isn't missed
+            let notified = self.ready.notified();
+            if self.is_initialized() {
+                return;
+            }
+            notified.await;
+        }
+    }
+
+    // Restores the saved inventory when there is one, otherwise starts an empty one
+    async fn create_player_inventory(&self, player_id: &str, num_slots: u32) {
+        let saved = match &self.config.inventory_store {
+            Some(store) => match store.load_inventory(player_id) {
+                Ok(saved) => saved,
+                Err(e) => {
+                    error!(player_id, error = %e, "Failed to load saved inventory");
+                    None
+                }
+            },
+            None => None,
+        };
+        let inventory = match saved {
+            Some(inventory) => {
+                self.saved_checksums.write().await.insert(player_id.to_string(), inventory.checksum());
+                inventory
+            }
+            None => PlayerInventory::with_hotbar(num_slots, self.config.hotbar_size),
+        };
+        let mut inventories = self.player_inventories.write().await;
+        inventories.insert(player_id.to_string(), inventory);
+    }
+
+    // Saves every inventory that changed since it was last saved or loaded; returns how many
+    // were written. Failed saves are logged and tried again next time. The craft queues are
+    // saved alongside, whether or not anything changed, since their progress always does, and
+    // so are the server-wide craft counts.
+    pub async fn save_dirty_inventories(&self) -> usize {
+        let store = match &self.config.inventory_store {
+            Some(store) => Arc::clone(store),
+            None => return 0,
+        };
+        let snapshot = self.craft_queue_snapshot().await;
+        if let Err(e) = store.save_craft_queues(&snapshot) {
+            error!(error = %e, "Failed to save craft queues");
+        }
+        let global_craft_counts = self.recipe_book.read().await.global_craft_counts.clone();
+        if let Err(e) = store.save_global_craft_counts(&global_craft_counts) {
+            error!(error = %e, "Failed to save global craft counts");
+        }
+        let inventories = self.inventories_snapshot().await;
+        let dirty: Vec<(String, PlayerInventory, u64)> = {
+            let saved = self.saved_checksums.read().await;
+            inventories.into_iter()
+                .map(|(player_id, inventory)| {
+                    let checksum = inventory.checksum();
+                    (player_id, inventory, checksum)
+                })
+                .filter(|(player_id, _, checksum)| saved.get(player_id) != Some(checksum))
+                .collect()
+        };
+
+        let mut written = 0;
+        for (player_id, inventory, checksum) in dirty {
+            match store.save_inventory(&player_id, &inventory) {
+                Ok(()) => {
+                    self.saved_checksums.write().await.insert(player_id, checksum);
+                    written += 1;
+                }
+                Err(e) => error!(player_id = %player_id, error = %e, "Failed to save inventory"),
+            }
+        }
+        written
+    }
+
+    // The first tick only starts the interval
+    async fn auto_save_if_due(&self) {
+        let interval = match self.config.auto_save_interval {
+            Some(interval) if self.config.inventory_store.is_some() => interval,
+            _ => return,
+        };
+        let now = self.clock.now();
+        {
+            let mut last_auto_save = self.last_auto_save.write().await;
+            match *last_auto_save {
+                Some(last) if now.saturating_sub(last) < interval => return,
+                Some(_) => *last_auto_save = Some(now),
+                None => {
+                    *last_auto_save = Some(now);
+                    return;
+                }
+            }
+        }
+        self.save_dirty_inventories().await;
+    }
+
+    async fn craft_queue_snapshot(&self) -> CraftQueueSnapshot {
+        let queues = self.craft_queues.read().await;
+        let mut player_ids: Vec<&String> = queues.keys().collect();
+        player_ids.sort();
+        CraftQueueSnapshot {
+            saved_at: self.clock.now(),
+            jobs: player_ids.into_iter().flat_map(|player_id| queues[player_id].iter().cloned()).collect(),
+        }
+    }
+
+    async fn restore_global_craft_counts(&self) {
+        let store = match &self.config.inventory_store {
+            Some(store) => Arc::clone(store),
+            None => return,
+        };
+        match store.load_global_craft_counts() {
+            Ok(Some(counts)) => self.recipe_book.write().await.global_craft_counts = counts,
+            Ok(None) => {}
+            Err(e) => error!(error = %e, "Failed to load global craft counts"),
+        }
+    }
+
+    // Puts saved crafts back in their queues with the downtime counted as cooking time, then
+    // finishes whatever is done. Crafts whose outputs don't fit yet, or whose player hasn't
+    // joined, stay finished at the front of the queue and complete on a later tick.
+    async fn restore_craft_queues(&self) {
+        let store = match &self.config.inventory_store {
+            Some(store) => Arc::clone(store),
+            None => return,
+        };
+        let snapshot = match store.load_craft_queues() {
+            Ok(Some(snapshot)) => snapshot,
+            Ok(None) => return,
+            Err(e) => {
+                error!(error = %e, "Failed to load craft queues");
+                return;
+            }
+        };
+
+        let downtime = self.clock.now().saturating_sub(snapshot.saved_at).as_secs_f64();
+        let restored = snapshot.jobs.len();
+        {
+            let mut queues = self.craft_queues.write().await;
+            let mut remaining_downtime: HashMap<String, f64> = HashMap::new();
+            for mut job in snapshot.jobs {
+                // Only the front job cooks, so downtime carries over from one job to the next
+                let available = remaining_downtime.entry(job.player_id.clone()).or_insert(downtime);
+                let spent = job.remaining_time.max(0.0).min(*available);
+                job.remaining_time -= spent;
+                *available -= spent;
+                queues.entry(job.player_id.clone()).or_insert_with(VecDeque::new).push_back(job);
+            }
+        }
+
+        let mut completed = 0;
+        loop {
+            let finished = self.process_craft_queue(0.0).await;
+            if finished == 0 {
+                break;
+            }
+            completed += finished;
+        }
+        info!(restored, completed, downtime_secs = downtime, "Craft queues restored");
+    }
+
+    pub async fn get_player_inventory(&self, player_id: &str) -> Option<PlayerInventory> {
+        let inventories = self.player_inventories.read().await;
+        inventories.get(player_id).cloned()
+    }
+
+    // Copies of every inventory, sorted by player id, for audits and other admin tasks
+    pub async fn inventories_snapshot(&self) -> Vec<(String, PlayerInventory)> {
+        let inventories = self.player_inventories.read().await;
+        let mut snapshot: Vec<(String, PlayerInventory)> = inventories.iter()
+            .map(|(player_id, inventory)| (player_id.clone(), inventory.clone()))
+            .collect();
+        drop(inventories);
+        snapshot.sort_by(|a, b| a.0.cmp(&b.0));
+        snapshot
+    }
+
+    // `f` runs on the snapshot rather than under the lock, so a slow `f` never blocks crafting
+    pub async fn for_each_inventory<F>(&self, mut f: F)
+    where
+        F: FnMut(&str, &PlayerInventory),
+    {
+        for (player_id, inventory) in self.inventories_snapshot().await {
+            f(&player_id, &inventory);
+        }
+    }
+
+    async fn update_player_inventory(&self, player_id: &str, inventory: PlayerInventory) {
+        let mut inventories = self.player_inventories.write().await;
+        inventories.insert(player_id.to_string(), inventory);
+    }
+
+    async fn craft_item(&self, player_id: &str, recipe_name: &str, context: &mut PluginContext) -> Option<String> {
+        self.craft_item_batch(player_id, recipe_name, 1, context).await
+            .and_then(|crafted| crafted.into_iter().next())
+    }
+
+    pub async fn craft_item_batch(&self, player_id: &str, recipe_name: &str, count: u32, context: &mut PluginContext) -> Option<Vec<String>> {
+        self.craft_item_inner(player_id, recipe_name, count, None, None, context).await.ok()
+    }
+
+    // Crafts as many as the player can right now, see `max_craftable`
+    pub async fn craft_all(&self, player_id: &str, recipe_name: &str, context: &mut PluginContext) -> Result<Vec<String>, String> {
+        match self.max_craftable(player_id, recipe_name).await {
+            0 => Err("Nothing can be crafted".to_string()),
+            count => self.craft_item_inner(player_id, recipe_name, count, None, None, context).await,
+        }
+    }
+
+    // How many single crafts in a row would succeed now, given ingredients, room for the
+    // outputs, attribute requirements and the per-player and server-wide limits. Nothing is changed.
+    pub async fn max_craftable(&self, player_id: &str, recipe_name: &str) -> u32 {
+        if self.ensure_initialized().is_err() {
+            return 0;
+        }
+        let (recipe, items, global_remaining) = {
+            let recipe_book = self.recipe_book.read().await;
+            match recipe_book.get_recipe(recipe_name) {
+                Some(recipe) => {
+                    let global_remaining = recipe_book.global_crafts_remaining(&recipe, self.queued_crafts_of(&recipe.id).await);
+                    (recipe, recipe_book.items.clone(), global_remaining)
+                }
+                None => return 0,
+            }
+        };
+        let mut inventory = match self.get_player_inventory(player_id).await {
+            Some(inventory) => inventory,
+            None => return 0,
+        };
+        if !self.meets_attribute_requirements(player_id, &recipe).await || self.check_level(player_id, &recipe).await.is_err() {
+            return 0;
+        }
+
+        // Recipes without ingredients are only bounded by space, so cap the search
+        let mut limit = recipe.ingredients.iter()
+            .filter(|ingredient| ingredient.consumed)
+            .map(|ingredient| inventory.available_for(&recipe, &ingredient.name) / ingredient.quantity.max(1))
+            .min()
+            .unwrap_or(u16::MAX as u32);
+        if let Some(max_per_player) = recipe.max_per_player {
+            let crafted = self.get_player_craft_count(player_id, &recipe.id).await;
+            limit = limit.min(max_per_player.saturating_sub(crafted));
+        }
+        if let Some(global_remaining) = global_remaining {
+            limit = limit.min(global_remaining);
+        }
+
+        let mut crafts = 0;
+        while crafts < limit {
+            let plan = match recipe.plan_consumption(1, |name| inventory.available_for(&recipe, name)) {
+                Some(plan) => plan,
+                None => break,
+            };
+            for (name, amount) in &plan.consumed {
+                inventory.take_for(&recipe, name, *amount, self.config.consume_order);
+            }
+            let returned = recipe.transformed_returns(&plan.consumed).into_iter().map(|(name, amount)| items.create_item(&name, amount));
+            let fits = (0..plan.output_multiplier)
+                .flat_map(|_| recipe.outputs())
+                .map(|output| items.create_item(&output, 1))
+                .chain(returned)
+                .all(|output| inventory.deposit_output(output, self.config.stack_merge_policy).is_ok());
+            if !fits {
+                break;
+            }
+            crafts += 1;
+        }
+        crafts
+    }
+
+    // Crafts at a specific crafter; its entry in `crafter_outputs`, if any, decides the outputs
+    pub async fn craft_item_at(&self, player_id: &str, recipe_name: &str, crafter: &Crafter, context: &mut PluginContext) -> Result<Vec<String>, String> {
+        self.craft_item_inner(player_id, recipe_name, 1, None, Some(crafter), context).await
+    }
+
+    // Puts the recipe's main outcome into `output_slot`, or the first free slot when None
+    pub async fn craft_item_to_slot(&self, player_id: &str, recipe_name: &str, output_slot: Option<u32>, context: &mut PluginContext) -> Result<String, String> {
+        self.craft_item_inner(player_id, recipe_name, 1, output_slot, None, context).await
+            .and_then(|crafted| crafted.into_iter().next().ok_or_else(|| "Recipe produced nothing".to_string()))
+    }
+
+    // Shared by real crafts and previews so both lay out the inventory the same way
+    // Consumes the ingredients, swapping in what transformed ones turn into, then places the
+    // outputs. Returns the items that didn't fit.
+    fn apply_craft(&self, inventory: &mut PlayerInventory, recipe: &Recipe, items: &ItemRegistry, consumed: &[(String, u32)], outputs: Vec<Item>, output_slot: Option<u32>) -> Vec<Item> {
+        for (name, amount) in consumed {
+            inventory.take_for(recipe, name, *amount, self.config.consume_order);
+        }
+        let returned: Vec<Item> = recipe.transformed_returns(consumed).into_iter()
+            .map(|(name, amount)| items.create_item(&name, amount))
+            .collect();
+
+        // The main outcome still goes first so it can claim `output_slot`
+        let mut outputs = outputs.into_iter().chain(returned);
+        let mut overflow = Vec::new();
+        if let Some(slot) = output_slot {
+            if let Some(outcome) = outputs.next() {
+                if let Err(outcome) = inventory.place_in_slot(slot, outcome, self.config.stack_merge_policy) {
+                    overflow.extend(inventory.deposit_output(outcome, self.config.stack_merge_policy).err());
+                }
+            }
+        }
+        for output in outputs {
+            overflow.extend(inventory.deposit_output(output, self.config.stack_merge_policy).err());
+        }
+        overflow
+    }
+
+    // Sends items that didn't fit in the player's inventory where `overflow_policy` says.
+    // Without a context `item_dropped` only reaches in-process subscribers.
+    // Returns the items left with nowhere to go, which is all of them under `OverflowPolicy::Error`.
+    async fn handle_overflow(&self, player_id: &str, items: Vec<Item>, mut context: Option<&mut PluginContext>) -> Vec<Item> {
+        let dropped = match self.config.overflow_policy {
+            OverflowPolicy::Error => return items,
+            OverflowPolicy::Drop => items,
+            OverflowPolicy::Container => self.store_overflow(player_id, items).await,
+        };
+
+        for item in dropped {
+            info!(player_id, item = %item.name, quantity = item.quantity, "Item dropped");
+            let data = Arc::new(ItemDroppedEvent { player_id: player_id.to_string(), item });
+            match context.as_deref_mut() {
+                Some(context) => self.emit_custom_event(CustomEvent { event_type: "item_dropped".to_string(), data }, context).await,
+                None => self.publish(BroadcastEvent {
+                    player_id: Some(player_id.to_string()),
+                    event_type: "item_dropped".to_string(),
+                    data,
+                }),
+            }
+        }
+        Vec::new()
+    }
+
+    // Returns the items that didn't fit in the overflow container either
+    async fn store_overflow(&self, player_id: &str, items: Vec<Item>) -> Vec<Item> {
+        let mut overflow_containers = self.overflow_containers.write().await;
+        let mut containers = self.storage_containers.write().await;
+        // The container may have been purged while empty, so it's recreated under the same uuid
+        let uuid = overflow_containers.get(player_id).copied().unwrap_or_else(Uuid::new_v4);
+        overflow_containers.insert(player_id.to_string(), uuid);
+        let container = containers.entry(uuid).or_insert_with(|| {
+            let mut container = StorageContainer::new(self.config.overflow_container_slots);
+            container.uuid = uuid;
+            container.owner = Some(player_id.to_string());
+            container
+        });
+        items.into_iter()
+            .filter_map(|item| container.inventory.deposit(item, self.config.stack_merge_policy).err())
+            .collect()
+    }
+
+    // Where `OverflowPolicy::Container` puts the player's items, once it has needed to
+    pub async fn get_overflow_container(&self, player_id: &str) -> Option<Uuid> {
+        self.overflow_containers.read().await.get(player_id).copied()
+    }
+
+    // For container recipes each crafted outcome gets its own new container, owned by the
+    // player and linked from the item's "container_uuid" tag. The containers still need registering.
+    // `craft_count` is the recipe's craft count including this craft, for `quality_progression`.
+    fn create_output_items(&self, player_id: &str, recipe: &Recipe, items: &ItemRegistry, crafted_items: &[String], craft_count: u32) -> (Vec<Item>, Vec<StorageContainer>) {
+        let quality = self.config.quality_progression.quality_for(craft_count);
+        let mut containers = Vec::new();
+        let outputs = crafted_items.iter()
+            .map(|crafted_item| {
+                let mut item = items.create_item(crafted_item, 1);
+                if item.model.is_none() {
+                    item.model = self.config.model_resolver.as_ref().and_then(|resolver| resolver.resolve_model(crafted_item));
+                }
+                // Progression only ever raises the quality an item would otherwise have
+                if let Some(quality) = quality {
+                    if item.quality() < quality_rank(quality) {
+                        item = item.tag("quality", quality);
+                    }
+                }
+                if let Some(container_output) = recipe.container_output.as_ref().filter(|_| crafted_item == &recipe.outcome) {
+                    let mut container = container_output.build(items, self.config.stack_merge_policy);
+                    container.owner = Some(player_id.to_string());
+                    item = item.tag("container_uuid", container.uuid.to_string());
+                    containers.push(container);
+                }
+                item
+            })
+            .collect();
+        (outputs, containers)
+    }
+
+    async fn register_containers(&self, containers: Vec<StorageContainer>) {
+        if containers.is_empty() {
+            return;
+        }
+        let mut registry = self.storage_containers.write().await;
+        for container in containers {
+            registry.insert(container.uuid, container);
+        }
+    }
+
+    // Runs a single craft against a copy of the player's inventory; no real state changes
+    pub async fn simulate_craft(&self, player_id: &str, recipe_name: &str) -> Result<CraftPreview, CraftError> {
+        self.ensure_initialized()?;
+        let recipe_book = self.recipe_book.read().await;
+        let recipe = recipe_book.get_recipe(recipe_name).ok_or(CraftError::RecipeNotFound)?;
+        recipe_book.check_crafter(&recipe, None)?;
+        let mut inventory = self.get_player_inventory(player_id).await
+            .ok_or_else(|| CraftError::Internal("Player inventory not found".to_string()))?;
+        if !self.meets_attribute_requirements(player_id, &recipe).await {
+            return Err(CraftError::RequirementsNotMet);
+        }
+        self.check_level(player_id, &recipe).await?;
+        self.check_craft_limit(player_id, &recipe, 1).await?;
+        recipe_book.check_global_limit(&recipe, self.queued_crafts_of(&recipe.id).await, 1)?;
+
+        let plan = recipe.plan_consumption(1, |name| inventory.available_for(&recipe, name)).ok_or(CraftError::MissingIngredients)?;
+        let (_, buff_yield) = self.buff_modifiers(player_id).await;
+        let produced: Vec<String> = (0..plan.output_multiplier * buff_yield).flat_map(|_| recipe.outputs()).collect();
+        let craft_count = recipe_book.get_recipe_craft_count(&recipe.id) + 1;
+        // Preview containers are never registered
+        let (outputs, _) = self.create_output_items(player_id, &recipe, &recipe_book.items, &produced, craft_count);
+        self.apply_craft(&mut inventory, &recipe, &recipe_book.items, &plan.consumed, outputs, None);
+
+        Ok(CraftPreview {
+            consumed: plan.consumed,
+            produced,
+            inventory,
+        })
+    }
+
+    // Hands every event to in-process listeners: the broadcast channel, plus the recorder
+    // while one is running. Happens before the event goes out through the plugin context.
+    fn publish(&self, event: BroadcastEvent) {
+        if let Ok(mut recorder) = self.event_recorder.lock() {
+            if let Some(recorded) = recorder.as_mut() {
+                recorded.push(event.clone());
+            }
+        }
+        // Sending only fails when nobody is subscribed
+        let _ = self.events.send(event);
+    }
+
+    // Unlike a broadcast subscriber, the recorder never drops events, so tests can check
+    // the exact sequence. Starting again discards anything recorded so far.
+    pub fn start_recording_events(&self) {
+        if let Ok(mut recorder) = self.event_recorder.lock() {
+            *recorder = Some(Vec::new());
+        }
+    }
+
+    // Events recorded since recording started or the last take, oldest first; recording continues
+    pub fn take_recorded_events(&self) -> Vec<BroadcastEvent> {
+        self.event_recorder.lock()
+            .ok()
+            .and_then(|mut recorder| recorder.as_mut().map(std::mem::take))
+            .unwrap_or_default()
+    }
+
+    pub fn stop_recording_events(&self) -> Vec<BroadcastEvent> {
+        self.event_recorder.lock()
+            .ok()
+            .and_then(|mut recorder| recorder.take())
+            .unwrap_or_default()
+    }
+
+    // Every event RecipeSmith emits, for subscribers in the same process
+    pub fn subscribe(&self) -> broadcast::Receiver<BroadcastEvent> {
+        self.events.subscribe()
+    }
+
+    pub fn subscribe_for_player(&self, player_id: &str) -> PlayerEventReceiver {
+        PlayerEventReceiver {
+            player_id: player_id.to_string(),
+            receiver: self.events.subscribe(),
+        }
+    }
+
+    pub async fn on_craft_complete(&self, callback: impl Fn(&CraftedEvent) + Send + Sync + 'static) {
+        self.craft_callbacks.write().await.push(CraftCompleteCallback(Arc::new(callback)));
+    }
+
+    async fn complete_craft(&self, record: CraftedEvent, context: &mut PluginContext) {
+        // Run callbacks without holding the lock so they may register more
+        let callbacks: Vec<CraftCompleteCallback> = self.craft_callbacks.read().await.clone();
+        for callback in &callbacks {
+            (callback.0)(&record);
+        }
+
+        self.emit_custom_event(CustomEvent {
+            event_type: "item_crafted".to_string(),
+            data: Arc::new(record),
+        }, context).await;
+    }
+
+    async fn emit_craft_started(&self, player_id: &str, recipe: &Recipe, total_cook_time: f64, context: &mut PluginContext) {
+        self.emit_custom_event(CustomEvent {
+            event_type: "craft_started".to_string(),
+            data: Arc::new(CraftStartedEvent {
+                player_id: player_id.to_string(),
+                recipe_name: recipe.name.clone(),
+                total_cook_time,
+                sound: recipe.sound.clone(),
+                animation: recipe.animation.clone(),
+            }),
+        }, context).await;
+    }
+
+    async fn fail_craft<T>(&self, player_id: &str, recipe_name: &str, reason: &str, missing: Vec<(String, u32)>, context: &mut PluginContext) -> Result<T, String> {
+        warn!(player_id, recipe = recipe_name, reason, "Crafting failed");
+        self.emit_custom_event(CustomEvent {
+            event_type: "crafting_failed".to_string(),
+            data: Arc::new(CraftFailedEvent {
+                player_id: player_id.to_string(),
+                recipe_name: recipe_name.to_string(),
+                reason: reason.to_string(),
+                missing,
+            }),
+        }, context).await;
+        Err(reason.to_string())
+    }
+
+    pub async fn missing_ingredients(&self, player_id: &str, recipe_name: &str, count: u32) -> Option<Vec<(String, u32)>> {
+        let recipe = self.recipe_book.read().await.get_recipe(recipe_name)?;
+        let inventory = self.get_player_inventory(player_id).await?;
+        Some(recipe.missing_ingredients(count, |name| inventory.available_for(&recipe, name)))
+    }
+
+    #[instrument(skip(self, context))]
+    async fn craft_item_inner(&self, player_id: &str, recipe_name: &str, count: u32, output_slot: Option<u32>, crafter: Option<&Crafter>, context: &mut PluginContext) -> Result<Vec<String>, String> {
+        self.ensure_initialized().map_err(|e| e.to_string())?;
+        let mut recipe_book = self.recipe_book.write().await;
+        let mut player_inventory = self.get_player_inventory(player_id).await.ok_or("Player inventory not found")?;
+
+        let recipe = match recipe_book.get_recipe(recipe_name) {
+            Some(recipe) => recipe,
+            None => return self.fail_craft(player_id, recipe_name, "Recipe not found", Vec::new(), context).await,
+        };
+        if !self.meets_attribute_requirements(player_id, &recipe).await {
+            return self.fail_craft(player_id, recipe_name, "Player does not meet the recipe's requirements", Vec::new(), context).await;
+        }
+        if let Err(e) = self.check_level(player_id, &recipe).await {
+            return self.fail_craft(player_id, recipe_name, &e.to_string(), Vec::new(), context).await;
+        }
+        if let Err(e) = self.check_craft_limit(player_id, &recipe, count).await {
+            return self.fail_craft(player_id, recipe_name, &e.to_string(), Vec::new(), context).await;
+        }
+        let queued = self.queued_crafts_of(&recipe.id).await;
+        if let Err(e) = recipe_book.check_global_limit(&recipe, queued, count) {
+            return self.fail_craft(player_id, recipe_name, &e.to_string(), Vec::new(), context).await;
+        }
+
+        // Check the target slot before anything is consumed
+        if let Some(slot) = output_slot {
+            let main_outcome = recipe.outputs_for(crafter).into_iter().next().unwrap_or_else(|| recipe.outcome.clone());
+            let outcome = recipe_book.items.create_item(&main_outcome, 1);
+            match player_inventory.slots.get(&slot) {
+                None => return self.fail_craft(player_id, recipe_name, "Output slot is out of range", Vec::new(), context).await,
+                Some(Some(existing)) if !(existing.can_stack_with(&outcome, self.config.stack_merge_policy) && existing.quantity < existing.max_stack.min(outcome.max_stack)) => {
+                    return self.fail_craft(player_id, recipe_name, "Output slot is occupied", Vec::new(), context).await;
+                }
+                Some(_) => {}
+            }
+        }
+
+        let mut inventory_map: HashMap<String, Ingredient> = HashMap::new();
+        for item in player_inventory.slots.values().flatten() {
+            inventory_map.entry(item.name.clone())
+                .or_insert_with(|| Ingredient {
+                    name: item.name.clone(),
+                    quantity: 0,
+                    recipe_craftable: true,
+                    max_quantity: None,
+                    min_quality: None,
+                    consumed: true,
+                    transformed_to: None,
+                })
+                .quantity += item.quantity;
+        }
+        // Recipe ingredients only count stacks that meet their quality requirement
+        for ingredient in &recipe.ingredients {
+            if let Some(entry) = inventory_map.get_mut(&ingredient.name) {
+                entry.quantity = player_inventory.count_for(ingredient);
+            }
+        }
+        let available: HashMap<String, u32> = inventory_map.iter()
+            .map(|(name, ingredient)| (name.clone(), ingredient.quantity))
+            .collect();
+
+        // Announce the craft only once it's certain to start
+        let plan = match recipe_book.plan_batch(recipe_name, count, &inventory_map, crafter) {
+            Ok((_, plan)) => plan,
+            Err(e) => {
+                let missing = match e {
+                    CraftError::MissingIngredients => recipe.missing_ingredients(count, |name| available.get(name).copied().unwrap_or(0)),
+                    _ => Vec::new(),
+                };
+                return self.fail_craft(player_id, recipe_name, &e.to_string(), missing, context).await;
+            }
+        };
+
+        let (buff_cook_time, buff_yield) = self.buff_modifiers(player_id).await;
+        // With nowhere for extra items to go, a craft whose outputs won't fit must not consume anything
+        if self.config.overflow_policy == OverflowPolicy::Error {
+            let outputs = recipe.outputs_for(crafter);
+            let produced: Vec<String> = (0..count * plan.output_multiplier * buff_yield).flat_map(|_| outputs.iter().cloned()).collect();
+            let craft_count = recipe_book.get_recipe_craft_count(&recipe.id).saturating_add(count);
+            // Trial containers are never registered
+            let (outputs, _) = self.create_output_items(player_id, &recipe, &recipe_book.items, &produced, craft_count);
+            let mut trial = player_inventory.clone();
+            if !self.apply_craft(&mut trial, &recipe, &recipe_book.items, &plan.consumed, outputs, output_slot).is_empty() {
+                return self.fail_craft(player_id, recipe_name, "Not enough inventory space for the outputs", Vec::new(), context).await;
+            }
+        }
+        let cook_time = self.effective_cook_time(&recipe, count, recipe_book.is_mastered(&recipe.id)) * buff_cook_time;
+        self.emit_craft_started(player_id, &recipe, cook_time, context).await;
+        let crafted_items = match recipe_book.craft_batch_at(recipe_name, count, &mut inventory_map, cook_time, crafter).await {
+            Ok(crafted_items) => (0..buff_yield).flat_map(|_| crafted_items.iter().cloned()).collect::<Vec<String>>(),
+            Err(e) => {
+                let missing = match e {
+                    CraftError::MissingIngredients => recipe.missing_ingredients(count, |name| available.get(name).copied().unwrap_or(0)),
+                    _ => Vec::new(),
+                };
+                return self.fail_craft(player_id, recipe_name, &e.to_string(), missing, context).await;
+            }
+        };
+
+        let consumed: Vec<(String, u32)> = available.iter()
+            .map(|(name, before)| (name.clone(), before.saturating_sub(inventory_map.get(name).map(|i| i.quantity).unwrap_or(0))))
+            .filter(|(_, consumed)| *consumed > 0)
+            .collect();
+        // The batch has already been recorded
+        let craft_count = recipe_book.get_recipe_craft_count(&recipe.id);
+        let (outputs, containers) = self.create_output_items(player_id, &recipe, &recipe_book.items, &crafted_items, craft_count);
+        let before = player_inventory.clone();
+        let overflow = self.apply_craft(&mut player_inventory, &recipe, &recipe_book.items, &consumed, outputs, output_slot);
+        let changes = player_inventory.diff(&before);
+        self.log_inventory_operation(player_id, InventoryOperation::Craft, &before, &player_inventory).await;
+
+        self.update_player_inventory(player_id, player_inventory).await;
+        self.register_containers(containers).await;
+        self.record_player_craft(player_id, &recipe, count).await;
+
+        // Release the book before emitting; the follow-up checks read it again
+        let mastered = recipe_book.is_mastered(&recipe.id);
+        drop(recipe_book);
+
+        info!(player_id, recipe = %recipe.name, count, elapsed_secs = cook_time, "Item crafted");
+
+        // Emit custom events
+        self.complete_craft(CraftedEvent {
+            player_id: player_id.to_string(),
+            recipe_name: recipe.name.clone(),
+            outputs: crafted_items.clone(),
+            elapsed_secs: cook_time,
+            sound: recipe.sound.clone(),
+            animation: recipe.animation.clone(),
+        }, context).await;
+
+        self.emit_inventory_changed(player_id, changes, context).await;
+
+        let lost = self.handle_overflow(player_id, overflow, Some(&mut *context)).await;
+        if !lost.is_empty() {
+            warn!(player_id, recipe = %recipe.name, lost = lost.len(), "Crafted items didn't fit in the inventory");
+        }
+
+        if mastered {
+            self.emit_custom_event(CustomEvent {
+                event_type: "recipe_mastered".to_string(),
+                data: Arc::new((player_id.to_string(), recipe_name.to_string())),
+            }, context).await;
+        }
+
+        // Mastery or the crafted items may satisfy another recipe's unlock condition
+        self.check_recipe_unlocks(player_id, context).await;
+
+        Ok(crafted_items)
+    }
+
+    // Party crafting: ingredients are pooled across the participants and each gives up a share
+    // in proportion to what they hold. Everything goes to `output_player`, who must meet the
+    // recipe's requirements. Inventories are checked again after cooking and edited together,
+    // so nobody loses anything unless the whole craft goes through.
+    pub async fn craft_cooperative(&self, participant_ids: &[String], recipe_name: &str, output_player: &str, context: &mut PluginContext) -> Result<Vec<String>, String> {
+        self.ensure_initialized().map_err(|e| e.to_string())?;
+        let mut participants: Vec<&str> = Vec::new();
+        for participant in participant_ids {
+            if !participants.contains(&participant.as_str()) {
+                participants.push(participant);
+            }
+        }
+        if participants.is_empty() {
+            return Err("No participants".to_string());
+        }
+
+        let (recipe, mastered_before, crafter_check) = {
+            let recipe_book = self.recipe_book.read().await;
+            let recipe = recipe_book.get_recipe(recipe_name).ok_or("Recipe not found")?;
+            let mastered = recipe_book.is_mastered(&recipe.id);
+            let queued = self.queued_crafts_of(&recipe.id).await;
+            let crafter_check = recipe_book.check_crafter(&recipe, None)
+                .and_then(|_| recipe_book.check_global_limit(&recipe, queued, 1));
+            (recipe, mastered, crafter_check)
+        };
+        if let Err(e) = crafter_check {
+            return self.fail_craft(output_player, &recipe.name, &e.to_string(), Vec::new(), context).await;
+        }
+        if !self.meets_attribute_requirements(output_player, &recipe).await {
+            return self.fail_craft(output_player, &recipe.name, "Player does not meet the recipe's requirements", Vec::new(), context).await;
+        }
+        if let Err(e) = self.check_level(output_player, &recipe).await {
+            return self.fail_craft(output_player, &recipe.name, &e.to_string(), Vec::new(), context).await;
+        }
+        if let Err(e) = self.check_craft_limit(output_player, &recipe, 1).await {
+            return self.fail_craft(output_player, &recipe.name, &e.to_string(), Vec::new(), context).await;
+        }
+
+        // Announce the craft only if the pool covers it now; it's checked again after cooking
+        {
+            let inventories = self.player_inventories.read().await;
+            let total = |name: &str| participants.iter()
+                .filter_map(|participant| inventories.get(*participant))
+                .map(|inventory| inventory.available_for(&recipe, name))
+                .sum::<u32>();
+            if recipe.plan_consumption(1, total).is_none() {
+                let missing = recipe.missing_ingredients(1, total);
+                drop(inventories);
+                return self.fail_craft(output_player, &recipe.name, "Missing ingredients", missing, context).await;
+            }
+        }
+
+        // Only the output player's buffs count
+        let (buff_cook_time, buff_yield) = self.buff_modifiers(output_player).await;
+        let cook_time = self.effective_cook_time(&recipe, 1, mastered_before) * buff_cook_time;
+        self.emit_craft_started(output_player, &recipe, cook_time, context).await;
+        if cook_time > 0.0 {
+            tokio::time::sleep(tokio::time::Duration::from_secs_f64(cook_time)).await;
+        }
+
+        // Book before inventories, the same order craft_item_inner takes them in
+        let recipe_book = self.recipe_book.read().await;
+        let mut inventories = self.player_inventories.write().await;
+        let mut pooled: Vec<(String, PlayerInventory)> = Vec::with_capacity(participants.len());
+        for participant in &participants {
+            match inventories.get(*participant) {
+                Some(inventory) => pooled.push((participant.to_string(), inventory.clone())),
+                None => return Err(format!("Inventory not found for {}", participant)),
+            }
+        }
+        if !inventories.contains_key(output_player) {
+            return Err("Output player inventory not found".to_string());
+        }
+
+        let total = |name: &str| pooled.iter().map(|(_, inventory)| inventory.available_for(&recipe, name)).sum::<u32>();
+        let plan = match recipe.plan_consumption(1, total) {
+            Some(plan) => plan,
+            None => {
+                let missing = recipe.missing_ingredients(1, total);
+                drop(inventories);
+                drop(recipe_book);
+                return self.fail_craft(output_player, &recipe.name, "Missing ingredients", missing, context).await;
+            }
+        };
+        for (name, amount) in &plan.consumed {
+            let held: Vec<u32> = pooled.iter().map(|(_, inventory)| inventory.available_for(&recipe, name)).collect();
+            for ((_, inventory), share) in pooled.iter_mut().zip(split_proportionally(*amount, &held)) {
+                inventory.take_for(&recipe, name, share, self.config.consume_order);
+            }
+        }
+
+        let crafted_items: Vec<String> = (0..plan.output_multiplier * buff_yield).flat_map(|_| recipe.outputs()).collect();
+        // Transformed ingredients come back to the output player with the outputs
+        let craft_count = recipe_book.get_recipe_craft_count(&recipe.id) + 1;
+        let (mut outputs, containers) = self.create_output_items(output_player, &recipe, &recipe_book.items, &crafted_items, craft_count);
+        outputs.extend(recipe.transformed_returns(&plan.consumed).into_iter().map(|(name, amount)| recipe_book.items.create_item(&name, amount)));
+        let mut receiver = match pooled.iter().position(|(player_id, _)| player_id == output_player) {
+            Some(index) => pooled.remove(index).1,
+            None => inventories[output_player].clone(),
+        };
+        let overflow: Vec<Item> = outputs.into_iter()
+            .filter_map(|output| receiver.deposit_output(output, self.config.stack_merge_policy).err())
+            .collect();
+        if !overflow.is_empty() && self.config.overflow_policy == OverflowPolicy::Error {
+            drop(inventories);
+            drop(recipe_book);
+            return self.fail_craft(output_player, &recipe.name, "Not enough room for the crafted items", Vec::new(), context).await;
+        }
+        pooled.push((output_player.to_string(), receiver));
+
+        let mut changes = Vec::with_capacity(pooled.len());
+        for (player_id, inventory) in pooled {
+            changes.push((player_id.clone(), inventory.diff(&inventories[&player_id])));
+            self.log_inventory_operation(&player_id, InventoryOperation::Craft, &inventories[&player_id], &inventory).await;
+            inventories.insert(player_id, inventory);
+        }
+        drop(inventories);
+        drop(recipe_book);
+
+        self.register_containers(containers).await;
+        let mastered = {
+            let mut recipe_book = self.recipe_book.write().await;
+            recipe_book.record_craft(&recipe.id, 1);
+            recipe_book.is_mastered(&recipe.id)
+        };
+        self.record_player_craft(output_player, &recipe, 1).await;
+
+        info!(player_id = output_player, recipe = %recipe.name, participants = participants.len(), elapsed_secs = cook_time, "Item crafted cooperatively");
+
+        self.complete_craft(CraftedEvent {
+            player_id: output_player.to_string(),
+            recipe_name: recipe.name.clone(),
+            outputs: crafted_items.clone(),
+            elapsed_secs: cook_time,
+            sound: recipe.sound.clone(),
+            animation: recipe.animation.clone(),
+        }, context).await;
+
+        for (player_id, player_changes) in changes {
+            self.emit_inventory_changed(&player_id, player_changes, context).await;
+        }
+        self.handle_overflow(output_player, overflow, Some(&mut *context)).await;
+
+        if mastered {
+            self.emit_custom_event(CustomEvent {
+                event_type: "recipe_mastered".to_string(),
+                data: Arc::new((output_player.to_string(), recipe.name.clone())),
+            }, context).await;
+        }
+        self.check_recipe_unlocks(output_player, context).await;
+
+        Ok(crafted_items)
+    }
+}
+
+// Splits `amount` in proportion to `held`, never asking anyone for more than they hold.
+// Rounding leftovers go to whoever has the most left over, earlier entries first on ties.
+fn split_proportionally(amount: u32, held: &[u32]) -> Vec<u32> {
+    let total: u64 = held.iter().map(|held| *held as u64).sum();
+    if total == 0 {
+        return vec![0; held.len()];
+    }
+    let mut shares: Vec<u32> = held.iter()
+        .map(|held| (amount as u64 * *held as u64 / total) as u32)
+        .collect();
+    let mut remaining = amount.saturating_sub(shares.iter().sum());
+    while remaining > 0 {
+        let next = (0..held.len())
+            .filter(|index| shares[*index] < held[*index])
+            .max_by_key(|index| (held[*index] - shares[*index], std::cmp::Reverse(*index)));
+        match next {
+            Some(index) => {
+                shares[index] += 1;
+                remaining -= 1;
+            }
+            None => break,
+        }
+    }
+    shares
+}
+
+
+#[async_trait]
+impl BaseAPI for RecipeSmith {
+    async fn on_game_event(&self, event: &GameEvent) {
+        match event {
+            GameEvent::PlayerJoined(player) => {
+                println!("RecipeSmith: Player {} joined. Initializing crafting data...", player.id);
+                self.create_player_inventory(&player.id, 20).await; // Assuming 20 inventory slots
+                if let Some(mapping) = &self.config.player_attribute_mapping {
+                    self.set_player_attributes(&player.id, (mapping.0)(player)).await;
+                }
+                if let Some(mapping) = &self.config.player_level_mapping {
+                    self.set_player_level(&player.id, (mapping.0)(player)).await;
+                }
+            }
+            GameEvent::Custom(custom_event) => {
+                let event_type = match self.own_event_type(&custom_event.event_type) {
+                    Some(event_type) => event_type,
+                    None => return,
+                };
+                match event_type {
+                    "recipe_learned" => println!("RecipeSmith: New recipe learned!"),
+                    "item_crafted" => println!("RecipeSmith: Item crafted!"),
+                    "inventory_changed" => println!("RecipeSmith: Inventory updated!"),
+                    "recipe_mastered" => println!("RecipeSmith: Recipe mastered!"),
+                    "crafting_failed" => println!("RecipeSmith: Crafting failed!"),
+                    "storage_container_created" => println!("RecipeSmith: New storage container created!"),
+                    "storage_container_accessed" => println!("RecipeSmith: Storage container accessed!"),
+                    "recipe_now_craftable" => println!("RecipeSmith: Recipe now craftable!"),
+                    "crafting_cancelled" => println!("RecipeSmith: Crafting cancelled!"),
+                    "item_dropped" => println!("RecipeSmith: Item dropped!"),
+                    "buff_expired" => println!("RecipeSmith: Crafting buff expired!"),
+                    "recipe_unmastered" => println!("RecipeSmith: Recipe mastery reset!"),
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+    }
+
+    async fn on_game_tick(&self, delta_time: f64) {
+        self.process_craft_queue(delta_time).await;
+        self.expire_buffs().await;
+        self.auto_save_if_due().await;
+    }
+
+    async fn register_custom_event(&self, event_type: &str, context: &mut PluginContext) {
+        let event_type = format!("{}{}", self.config.event_prefix, event_type);
+        context.register_for_custom_event(&event_type, self.event_handler()).await;
+    }
+
+    async fn emit_custom_event(&self, mut event: CustomEvent, context: &mut PluginContext) {
+        self.publish(BroadcastEvent {
+            player_id: BroadcastEvent::player_id_of(event.data.as_ref()),
+            event_type: event.event_type.clone(),
+            data: Arc::clone(&event.data),
+        });
+        event.event_type = format!("{}{}", self.config.event_prefix, event.event_type);
+        context.dispatch_custom_event(event).await;
+    }
+
+    fn as_any(&self) -> &dyn std::any::Any {
+        self
+    }
+}
+
+impl Plugin for RecipeSmith {
+    fn on_load(&self) {
+        println!("RecipeSmith plugin loaded");
+    }
+
+    fn on_unload(&self) {
+        println!("RecipeSmith plugin unloaded");
+    }
+
+    fn execute(&self) {
+        println!("RecipeSmith plugin executed");
+    }
+
+    fn initialize(&self, context: &mut PluginContext) {
+        println!("RecipeSmith plugin initializing");
+        let mut recipe_smith = self.clone();
+        
+        // Instead of spawning a new task, we'll call initialize_recipe_smith directly
+        tokio::runtime::Runtime::new()
+            .unwrap()
+            .block_on(async {
+                recipe_smith.initialize_recipe_smith(context).await;
+            });
+        
+        println!("RecipeSmith plugin initialized");
+    }
+
+    fn shutdown(&self, _context: &mut PluginContext) {
+        println!("RecipeSmith plugin shut down");
+    }
+
+    fn on_enable(&self, _context: &mut PluginContext) {
+        println!("RecipeSmith plugin enabled");
+    }
+
+    fn on_disable(&self, _context: &mut PluginContext) {
+        println!("RecipeSmith plugin disabled");
+    }
+}
+
+impl PluginInformation for RecipeSmith {
+    fn name(&self) -> String {
+        "RecipeSmith".to_string()
+    }
+
+    fn get_instance(&self) -> Box<dyn SayHello> {
+        Box::new(self.clone())
+    }
+}
+
+impl SayHello for RecipeSmith {
+    fn say_hello(&self) -> String {
+        "Hello from RecipeSmith! Ready to craft some amazing items?".to_string()
+    }
+}
+
+pub fn create_plugin_metadata() -> RecipeSmith {
+    RecipeSmith::new()
+}
+
+impl RecipeSmith {
+    pub async fn get_all_recipes(&self) -> Vec<Recipe> {
+        if self.ensure_initialized().is_err() {
+            return Vec::new();
+        }
+        let recipe_book = self.recipe_book.read().await;
+        recipe_book.recipes.values().cloned().collect()
+    }
+
+    pub async fn get_recipe_craft_count(&self, recipe_name: &str) -> u32 {
+        self.recipe_book.read().await.get_recipe_craft_count(recipe_name)
+    }
+
+    // Admin tool; emits `recipe_unmastered` when the recipe had been mastered. Without a context
+    // to send it through, the plugin bus gets it from the next `dispatch_completed_crafts`.
+    pub async fn reset_cook_count(&self, recipe_name: &str) -> Result<(), CraftError> {
+        let (recipe_name, was_mastered) = {
+            let mut recipe_book = self.recipe_book.write().await;
+            let recipe = recipe_book.get_recipe(recipe_name).ok_or(CraftError::RecipeNotFound)?;
+            let was_mastered = recipe_book.is_mastered(&recipe.id);
+            recipe_book.reset_cook_count(&recipe.id)?;
+            (recipe.name, was_mastered)
+        };
+
+        info!(recipe = %recipe_name, "Recipe cook count reset");
+        if was_mastered {
+            self.emit_deferred_event(BroadcastEvent {
+                player_id: None,
+                event_type: "recipe_unmastered".to_string(),
+                data: Arc::new(recipe_name),
+            }).await;
+        }
+        Ok(())
+    }
+
+    // Mastery is tracked on the recipe book, so every player currently gets the same list
+    pub async fn get_mastered_recipes(&self, _player_id: &str) -> Vec<String> {
+        let recipe_book = self.recipe_book.read().await;
+        let mut mastered: Vec<String> = recipe_book.recipes.values()
+            .filter(|recipe| recipe_book.is_mastered(&recipe.id))
+            .map(|recipe| recipe.name.clone())
+            .collect();
+        mastered.sort();
+        mastered
+    }
+
+    // Mastered recipes cook faster by `mastery_cook_time_multiplier`
+    pub async fn outputs_per_minute(&self, recipe_name: &str) -> f32 {
+        self.recipe_book.read().await.outputs_per_minute(recipe_name, self.config.mastery_cook_time_multiplier)
+    }
+
+    pub async fn set_crafter_enabled(&self, crafter: &Crafter, enabled: bool) {
+        self.recipe_book.write().await.set_crafter_enabled(crafter, enabled);
+    }
+
+    pub async fn recipe_book_snapshot(&self) -> Arc<RecipeBookView> {
+        self.recipe_book.read().await.snapshot()
+    }
+
+    pub async fn search_recipes(&self, query: &str) -> Vec<Recipe> {
+        if self.ensure_initialized().is_err() {
+            return Vec::new();
+        }
+        self.recipe_book.read().await.search_recipes(query)
+    }
+
+    pub async fn get_recipes_by_crafter(&self, crafter_name: &str) -> Vec<Recipe> {
+        if self.ensure_initialized().is_err() {
+            return Vec::new();
+        }
+        let recipe_book = self.recipe_book.read().await;
+        let crafter = Crafter { name: crafter_name.to_string() };
+        recipe_book.get_recipes_for_crafter(&crafter)
+    }
+
+    pub async fn add_new_recipe(&self, recipe: Recipe) -> Result<(), RecipeValidationError> {
+        let mut recipe_book = self.recipe_book.write().await;
+        let recipe = recipe_book.resolve_inheritance(vec![recipe])?.remove(0);
+        self.add_checked_recipe(&mut recipe_book, recipe)
+    }
+
+    // Adds an already resolved recipe, applying `unknown_output_policy`
+    fn add_checked_recipe(&self, recipe_book: &mut RecipeBook, recipe: Recipe) -> Result<(), RecipeValidationError> {
+        if let Err(error) = recipe_book.validate_outputs(&recipe) {
+            match self.config.unknown_output_policy {
+                UnknownOutputPolicy::WarnAndPlaceholder => warn!(%error, "Recipe has an unknown output"),
+                UnknownOutputPolicy::Reject => return Err(error),
+            }
+        }
+        recipe_book.add_recipe(recipe);
+        Ok(())
+    }
+
+    pub async fn register_item(&self, definition: ItemDefinition) {
+        let mut recipe_book = self.recipe_book.write().await;
+        recipe_book.items.register(definition);
+    }
+
+    pub async fn get_player_inventory_contents(&self, player_id: &str) -> Option<Vec<Item>> {
+        let inventory = self.get_player_inventory(player_id).await?;
+        Some(inventory.slots.values().filter_map(|item| item.clone()).collect())
+    }
+
+    pub async fn add_item_to_player_inventory(&self, player_id: &str, item: Item) -> Result<(), String> {
+        let mut inventories = self.player_inventories.write().await;
+        let inventory = inventories.get_mut(player_id).ok_or("Player inventory not found")?;
+        inventory.check_category_cap(&item, &self.config.category_caps)?;
+
+        // All or nothing unless `overflow_policy` has somewhere to put the rest
+        let mut updated = inventory.clone();
+        let overflow = updated.deposit(item, self.config.stack_merge_policy).err();
+        if overflow.is_some() && self.config.overflow_policy == OverflowPolicy::Error {
+            return Err("Inventory is full".to_string());
+        }
+        self.log_inventory_operation(player_id, InventoryOperation::Add, inventory, &updated).await;
+        *inventory = updated;
+        drop(inventories);
+
+        self.handle_overflow(player_id, overflow.into_iter().collect(), None).await;
+        Ok(())
+    }
+
+    pub async fn remove_item_from_player_inventory(&self, player_id: &str, item_name: &str) -> Result<(), String> {
+        let mut inventory = self.get_player_inventory(player_id).await.ok_or("Player inventory not found")?;
+        
+        for (slot, item_opt) in inventory.slots.iter_mut() {
+            if let Some(item) = item_opt {
+                if item.name == item_name {
+                    *item_opt = None;
+                    self.get_player_inventory("player_id");
+                    return Ok(());
+                }
+            }
+        }
+        
+        Err("Item not found in inventory".to_string())
+    }
+
+    pub async fn remove_item_from_slot(&self, player_id: &str, slot: u32, context: &mut PluginContext) -> Result<Option<Item>, String> {
+        let (removed, changes) = self.edit_player_inventory(player_id, InventoryOperation::Remove, |inventory| {
+            if !inventory.slots.contains_key(&slot) {
+                return Err(format!("Slot {} is out of range", slot));
+            }
+            Ok(inventory.remove_item(slot))
+        }).await?;
+
+        if removed.is_some() {
+            self.emit_inventory_changed(player_id, changes, context).await;
+        }
+
+        Ok(removed)
+    }
+
+    pub async fn clear_player_inventory(&self, player_id: &str, context: &mut PluginContext) -> Result<Vec<Item>, String> {
+        // The write lock is held throughout, so an item given meanwhile is either cleared and returned or left alone
+        let (removed, changes) = self.edit_player_inventory(player_id, InventoryOperation::Remove, |inventory| Ok(inventory.clear())).await?;
+
+        if !removed.is_empty() {
+            self.emit_inventory_changed(player_id, changes, context).await;
+        }
+
+        Ok(removed)
+    }
+
+    pub async fn sort_player_inventory(&self, player_id: &str, key: SortKey, context: &mut PluginContext) -> Result<(), String> {
+        let policy = self.config.stack_merge_policy;
+        self.modify_player_inventory(player_id, context, |inventory| inventory.sort_and_consolidate(key, policy)).await
+    }
+
+    // For modpack migrations; returns the players whose inventories changed
+    pub async fn rename_item_everywhere(&self, old_name: &str, new_name: &str, context: &mut PluginContext) -> Vec<String> {
+        let policy = self.config.stack_merge_policy;
+
+        let mut affected: Vec<(String, PlayerInventory, PlayerInventory)> = {
+            let mut inventories = self.player_inventories.write().await;
+            inventories.iter_mut()
+                .filter_map(|(player_id, inventory)| {
+                    let before = inventory.clone();
+                    inventory.rename_item(old_name, new_name, policy).then(|| (player_id.clone(), before, inventory.clone()))
+                })
+                .collect()
+        };
+        affected.sort_by(|a, b| a.0.cmp(&b.0));
+
+        {
+            let mut containers = self.storage_containers.write().await;
+            for container in containers.values_mut() {
+                container.inventory.rename_item(old_name, new_name, policy);
+            }
+        }
+
+        let mut affected_players = Vec::with_capacity(affected.len());
+        for (player_id, before, after) in affected {
+            self.log_inventory_operation(&player_id, InventoryOperation::Rename, &before, &after).await;
+            self.emit_inventory_changed(&player_id, after.diff(&before), context).await;
+            affected_players.push(player_id);
+        }
+
+        affected_players
+    }
+
+    // Host entry point for arbitrary inventory edits. The closure works on a copy; the result
+    // is only stored if the slot layout is unchanged and every stack is valid.
+    pub async fn modify_player_inventory<F, R>(&self, player_id: &str, context: &mut PluginContext, modify: F) -> Result<R, String>
+    where
+        F: FnOnce(&mut PlayerInventory) -> R,
+    {
+        let (result, changes) = self.edit_player_inventory(player_id, InventoryOperation::Modify, |inventory| Ok(modify(inventory))).await?;
+        self.emit_inventory_changed(player_id, changes, context).await;
+        Ok(result)
+    }
+
+    // Edits a copy under the write lock, so nothing else can change the inventory in between,
+    // and keeps it only if `modify` succeeds and the copy is still valid. Returns the result
+    // and the net item changes for the caller to announce.
+    async fn edit_player_inventory<F, R>(&self, player_id: &str, operation: InventoryOperation, modify: F) -> Result<(R, Vec<(String, i64)>), String>
+    where
+        F: FnOnce(&mut PlayerInventory) -> Result<R, String>,
+    {
+        let mut inventories = self.player_inventories.write().await;
+        let inventory = inventories.get_mut(player_id).ok_or("Player inventory not found")?;
+
+        let mut modified = inventory.clone();
+        let result = modify(&mut modified)?;
+
+        let same_layout = modified.slots.len() == inventory.slots.len()
+            && modified.slots.keys().all(|slot| inventory.slots.contains_key(slot));
+        if !same_layout {
+            return Err("Inventory slots cannot be added or removed".to_string());
+        }
+        modified.validate()?;
+
+        let changes = modified.diff(inventory);
+        self.log_inventory_operation(player_id, operation, inventory, &modified).await;
+        *inventory = modified;
+        Ok((result, changes))
+    }
+
+    // Base64 of the compact JSON form, for support tickets and moving inventories around
+    pub async fn export_player_inventory(&self, player_id: &str) -> Option<String> {
+        let inventory = self.get_player_inventory(player_id).await?;
+        let json = serde_json::to_vec(&inventory.to_compact()).ok()?;
+        Some(base64::engine::general_purpose::STANDARD.encode(json))
+    }
+
+    // Replaces the player's inventory with an exported one; malformed or invalid data changes nothing
+    pub async fn import_player_inventory(&self, player_id: &str, data: &str, context: &mut PluginContext) -> Result<(), String> {
+        let json = base64::engine::general_purpose::STANDARD.decode(data.trim())
+            .map_err(|e| format!("Inventory data is not valid base64: {}", e))?;
+        let compact: CompactInventory = serde_json::from_slice(&json)
+            .map_err(|e| format!("Inventory data is malformed: {}", e))?;
+        if compact.items.keys().any(|slot| *slot >= compact.capacity) {
+            return Err("Inventory data has items outside its capacity".to_string());
+        }
+        // Checked before building the inventory so a huge capacity never gets allocated
+        let max_slots = match self.player_inventories.read().await.get(player_id) {
+            Some(existing) => existing.slots.len() as u32,
+            None => self.config.max_inventory_slots,
+        };
+        if compact.capacity > max_slots {
+            return Err(format!("Inventory data has {} slots, more than the {} allowed", compact.capacity, max_slots));
+        }
+        let inventory = PlayerInventory::from(compact);
+        inventory.validate()?;
+
+        let mut inventories = self.player_inventories.write().await;
+        let empty = PlayerInventory::new(0);
+        let previous = inventories.get(player_id).unwrap_or(&empty);
+        let changes = inventory.diff(previous);
+        self.log_inventory_operation(player_id, InventoryOperation::Import, previous, &inventory).await;
+        inventories.insert(player_id.to_string(), inventory);
+        drop(inventories);
+
+        self.emit_inventory_changed(player_id, changes, context).await;
+        Ok(())
+    }
+
+    pub async fn inventory_checksum(&self, player_id: &str) -> Option<u64> {
+        let inventories = self.player_inventories.read().await;
+        inventories.get(player_id).map(|inventory| inventory.checksum())
+    }
+
+    pub async fn find_item_slots(&self, player_id: &str, name: &str) -> Option<Vec<u32>> {
+        let inventory = self.get_player_inventory(player_id).await?;
+        Some(inventory.slots_containing(name))
+    }
+
+    pub async fn move_item(&self, player_id: &str, from_slot: u32, to_slot: u32, context: &mut PluginContext) -> Result<(), String> {
+        let policy = self.config.stack_merge_policy;
+        self.edit_player_inventory(player_id, InventoryOperation::Move, |inventory| inventory.move_item(from_slot, to_slot, policy)).await?;
+
+        // Nothing was gained or lost, only rearranged
+        self.emit_inventory_changed(player_id, Vec::new(), context).await;
+
+        Ok(())
+    }
+
+    pub async fn set_player_attributes(&self, player_id: &str, attributes: HashMap<String, f32>) {
+        let mut player_attributes = self.player_attributes.write().await;
+        player_attributes.insert(player_id.to_string(), attributes);
+    }
+
+    pub async fn set_player_level(&self, player_id: &str, level: u32) {
+        self.player_levels.write().await.insert(player_id.to_string(), level);
+    }
+
+    // Players whose level was never set count as level 0
+    pub async fn get_player_level(&self, player_id: &str) -> u32 {
+        self.player_levels.read().await.get(player_id).copied().unwrap_or(0)
+    }
+
+    async fn check_level(&self, player_id: &str, recipe: &Recipe) -> Result<(), CraftError> {
+        match recipe.required_level {
+            Some(required) if self.get_player_level(player_id).await < required => Err(CraftError::LevelTooLow),
+            _ => Ok(()),
+        }
+    }
+
+    // Applying a buff the player already has restarts it with the new effect and duration
+    pub async fn apply_buff(&self, player_id: &str, name: &str, effect: BuffEffect, duration: std::time::Duration) {
+        let buff = CraftingBuff {
+            name: name.to_string(),
+            effect,
+            expires_at: self.clock.now() + duration,
+        };
+        let mut player_buffs = self.player_buffs.write().await;
+        let buffs = player_buffs.entry(player_id.to_string()).or_default();
+        buffs.retain(|existing| existing.name != name);
+        buffs.push(buff);
+    }
+
+    pub async fn get_active_buffs(&self, player_id: &str) -> Vec<CraftingBuff> {
+        let now = self.clock.now();
+        self.player_buffs.read().await.get(player_id)
+            .map(|buffs| buffs.iter().filter(|buff| buff.expires_at > now).cloned().collect())
+            .unwrap_or_default()
+    }
+
+    // Combined cook time multiplier and yield multiplier of the player's active buffs
+    async fn buff_modifiers(&self, player_id: &str) -> (f64, u32) {
+        self.get_active_buffs(player_id).await.iter()
+            .fold((1.0, 1), |(cook_time, yield_multiplier), buff| match buff.effect {
+                BuffEffect::CookTime(multiplier) => (cook_time * multiplier.max(0.0) as f64, yield_multiplier),
+                BuffEffect::Yield(multiplier) => (cook_time, yield_multiplier * multiplier.max(1)),
+            })
+    }
+
+    // Removes buffs that have run out and emits `buff_expired` for each; run every game tick.
+    // Expired buffs already stop applying before this runs.
+    pub async fn expire_buffs(&self) -> Vec<(String, CraftingBuff)> {
+        let now = self.clock.now();
+        let mut expired = Vec::new();
+        {
+            let mut player_buffs = self.player_buffs.write().await;
+            for (player_id, buffs) in player_buffs.iter_mut() {
+                let (ended, active): (Vec<CraftingBuff>, Vec<CraftingBuff>) = buffs.drain(..).partition(|buff| buff.expires_at <= now);
+                *buffs = active;
+                expired.extend(ended.into_iter().map(|buff| (player_id.clone(), buff)));
+            }
+            player_buffs.retain(|_, buffs| !buffs.is_empty());
+        }
+
+        for (player_id, buff) in &expired {
+            self.emit_deferred_event(BroadcastEvent {
+                player_id: Some(player_id.clone()),
+                event_type: "buff_expired".to_string(),
+                data: Arc::new((player_id.clone(), buff.name.clone())),
+            }).await;
+        }
+        expired
+    }
+
+    pub async fn get_player_attributes(&self, player_id: &str) -> HashMap<String, f32> {
+        let player_attributes = self.player_attributes.read().await;
+        player_attributes.get(player_id).cloned().unwrap_or_default()
+    }
+
+    // Attributes the player doesn't have count as zero
+    pub async fn meets_attribute_requirements(&self, player_id: &str, recipe: &Recipe) -> bool {
+        if recipe.required_attributes.is_empty() {
+            return true;
+        }
+        let attributes = self.get_player_attributes(player_id).await;
+        recipe.required_attributes.iter()
+            .all(|(name, required)| attributes.get(name).copied().unwrap_or(0.0) >= *required)
+    }
+
+    async fn log_inventory_operation(&self, player_id: &str, operation: InventoryOperation, before: &PlayerInventory, after: &PlayerInventory) {
+        let capacity = self.config.inventory_log_capacity;
+        if capacity == 0 {
+            return;
+        }
+        let entry = InventoryLogEntry {
+            operation,
+            at: self.clock.now(),
+            changes: after.diff(before),
+            checksum_before: before.checksum(),
+            checksum_after: after.checksum(),
+        };
+        let mut logs = self.inventory_logs.write().await;
+        let log = logs.entry(player_id.to_string()).or_insert_with(VecDeque::new);
+        while log.len() >= capacity {
+            log.pop_front();
+        }
+        log.push_back(entry);
+    }
+
+    // Oldest first
+    pub async fn get_inventory_log(&self, player_id: &str) -> Vec<InventoryLogEntry> {
+        let logs = self.inventory_logs.read().await;
+        logs.get(player_id).map(|log| log.iter().cloned().collect()).unwrap_or_default()
+    }
+
+    async fn emit_inventory_changed(&self, player_id: &str, changes: Vec<(String, i64)>, context: &mut PluginContext) {
+        self.emit_custom_event(CustomEvent {
+            event_type: "inventory_changed".to_string(),
+            data: Arc::new(InventoryChangedEvent {
+                player_id: player_id.to_string(),
+                changes,
+            }),
+        }, context).await;
+        self.notify_newly_craftable(player_id, context).await;
+    }
+
+    // Emits `recipe_now_craftable` for learned recipes that weren't craftable the last time
+    // this player's inventory was checked. A recipe only notifies again after it has become
+    // uncraftable in between, so repeated inventory changes don't spam the player.
+    async fn notify_newly_craftable(&self, player_id: &str, context: &mut PluginContext) {
+        let inventory = match self.get_player_inventory(player_id).await {
+            Some(inventory) => inventory,
+            None => return,
+        };
+
+        let mut craftable_now: Vec<Recipe> = Vec::new();
+        for recipe in self.get_learned_recipes(player_id).await {
+            if inventory.has_ingredients(&recipe.ingredients, 1) && self.meets_attribute_requirements(player_id, &recipe).await {
+                craftable_now.push(recipe);
+            }
+        }
+
+        let newly_craftable: Vec<Recipe> = {
+            let mut craftable = self.craftable_recipes.write().await;
+            let previous = craftable.entry(player_id.to_string()).or_insert_with(HashSet::new);
+            let newly = craftable_now.iter().filter(|recipe| !previous.contains(&recipe.id)).cloned().collect();
+            *previous = craftable_now.iter().map(|recipe| recipe.id.clone()).collect();
+            newly
+        };
+
+        for recipe in newly_craftable {
+            self.emit_custom_event(CustomEvent {
+                event_type: "recipe_now_craftable".to_string(),
+                data: Arc::new((player_id.to_string(), recipe.name.clone())),
+            }, context).await;
+        }
+    }
+
+    // Returns whether the recipe was newly learned; re-learning is a no-op and emits nothing.
+    // Fails if the player already learned another recipe from the same group.
+    pub async fn learn_recipe(&self, player_id: &str, recipe_name: &str, context: &mut PluginContext) -> Result<bool, String> {
+        self.ensure_initialized().map_err(|e| e.to_string())?;
+        let (recipe, siblings) = {
+            let recipe_book = self.recipe_book.read().await;
+            let recipe = recipe_book.get_recipe(recipe_name).ok_or("Recipe not found")?;
+            let siblings = Self::group_siblings(&recipe_book, &recipe);
+            (recipe, siblings)
+        };
+
+        let mut learned = self.learned_recipes.write().await;
+        let known = learned.entry(player_id.to_string()).or_insert_with(HashSet::new);
+        if siblings.iter().any(|id| known.contains(id)) {
+            return Err(format!("Another recipe in group {} has already been learned", recipe.group.as_deref().unwrap_or_default()));
+        }
+        let newly_learned = known.insert(recipe.id.clone());
+        drop(learned);
+
+        if newly_learned {
+            self.emit_custom_event(CustomEvent {
+                event_type: "recipe_learned".to_string(),
+                data: Arc::new((player_id.to_string(), recipe.name.clone())),
+            }, context).await;
+        }
+
+        Ok(newly_learned)
+    }
+
+    // Ids of the other recipes sharing this recipe's group
+    fn group_siblings(recipe_book: &RecipeBook, recipe: &Recipe) -> HashSet<String> {
+        match &recipe.group {
+            Some(group) => recipe_book.recipes_in_group(group).into_iter()
+                .map(|sibling| sibling.id)
+                .filter(|id| id != &recipe.id)
+                .collect(),
+            None => HashSet::new(),
+        }
+    }
+
+    // Locked recipes can't be learned because the player picked another one from their group
+    pub async fn is_recipe_locked(&self, player_id: &str, recipe_name: &str) -> bool {
+        let siblings = {
+            let recipe_book = self.recipe_book.read().await;
+            match recipe_book.get_recipe(recipe_name) {
+                Some(recipe) => Self::group_siblings(&recipe_book, &recipe),
+                None => return false,
+            }
+        };
+        let learned = self.learned_recipes.read().await;
+        learned.get(player_id).map(|known| siblings.iter().any(|id| known.contains(id))).unwrap_or(false)
+    }
+
+    pub async fn has_learned_recipe(&self, player_id: &str, recipe_name: &str) -> bool {
+        let recipe_id = {
+            let recipe_book = self.recipe_book.read().await;
+            match recipe_book.resolve_recipe_id(recipe_name) {
+                Some(id) => id,
+                None => return false,
+            }
+        };
+        let learned = self.learned_recipes.read().await;
+        learned.get(player_id).map(|ids| ids.contains(&recipe_id)).unwrap_or(false)
+    }
+
+    pub async fn get_learned_recipes(&self, player_id: &str) -> Vec<Recipe> {
+        let learned = self.learned_recipes.read().await;
+        let recipe_book = self.recipe_book.read().await;
+        learned.get(player_id)
+            .map(|ids| ids.iter().filter_map(|id| recipe_book.get_recipe(id)).collect())
+            .unwrap_or_else(Vec::new)
+    }
+
+    // Everything a recipe book screen needs: the player's learned recipes, sorted by name,
+    // with whether they can be crafted right now and whether they've been mastered
+    pub async fn get_player_recipe_book(&self, player_id: &str) -> Vec<RecipeBookEntry> {
+        if self.ensure_initialized().is_err() {
+            return Vec::new();
+        }
+        let inventory = self.get_player_inventory(player_id).await;
+        let mut entries = Vec::new();
+        for recipe in self.get_learned_recipes(player_id).await {
+            let has_ingredients = inventory.as_ref().map(|inv| inv.has_ingredients(&recipe.ingredients, 1)).unwrap_or(false);
+            let craftable = has_ingredients && self.meets_attribute_requirements(player_id, &recipe).await;
+            let mastered = self.recipe_book.read().await.is_mastered(&recipe.id);
+            let handcraftable = recipe.is_handcraftable();
+            entries.push(RecipeBookEntry { recipe, craftable, mastered, handcraftable });
+        }
+        entries.sort_by(|a, b| a.recipe.name.cmp(&b.recipe.name));
+        entries
+    }
+
+    // Learns every recipe whose unlock condition the player now satisfies
+    pub async fn check_recipe_unlocks(&self, player_id: &str, context: &mut PluginContext) {
+        let held_items: HashSet<String> = match self.get_player_inventory(player_id).await {
+            Some(inventory) => inventory.slots.values().flatten().map(|item| item.name.clone()).collect(),
+            None => HashSet::new(),
+        };
+
+        let unlocked: Vec<String> = {
+            let recipe_book = self.recipe_book.read().await;
+            let learned = self.learned_recipes.read().await;
+            let known = learned.get(player_id);
+            recipe_book.recipes.values()
+                .filter(|recipe| !known.map(|ids| ids.contains(&recipe.id)).unwrap_or(false))
+                .filter(|recipe| !known.map(|ids| Self::group_siblings(&recipe_book, recipe).iter().any(|id| ids.contains(id))).unwrap_or(false))
+                .filter(|recipe| match &recipe.unlock_condition {
+                    Some(UnlockCondition::MasteredRecipe(name)) => recipe_book.is_mastered(name),
+                    Some(UnlockCondition::HasItem(name)) => held_items.contains(name),
+                    None => false,
+                })
+                .map(|recipe| recipe.id.clone())
+                .collect()
+        };
+
+        for recipe_id in unlocked {
+            if let Err(e) = self.learn_recipe(player_id, &recipe_id, context).await {
+                error!(player_id, recipe = %recipe_id, error = %e, "Failed to unlock recipe");
+            }
+        }
+    }
+
+    pub async fn get_player_craft_count(&self, player_id: &str, recipe_name: &str) -> u32 {
+        let recipe_id = match self.recipe_book.read().await.resolve_recipe_id(recipe_name) {
+            Some(recipe_id) => recipe_id,
+            None => return 0,
+        };
+        let counts = self.player_craft_counts.read().await;
+        counts.get(player_id).and_then(|recipes| recipes.get(&recipe_id)).copied().unwrap_or(0)
+    }
+
+    // Queued crafts count towards the limit so it can't be exceeded by queueing ahead
+    async fn check_craft_limit(&self, player_id: &str, recipe: &Recipe, count: u32) -> Result<(), CraftError> {
+        let limit = match recipe.max_per_player {
+            Some(limit) => limit,
+            None => return Ok(()),
+        };
+        let crafted = {
+            let counts = self.player_craft_counts.read().await;
+            counts.get(player_id).and_then(|recipes| recipes.get(&recipe.id)).copied().unwrap_or(0)
+        };
+        let queued = {
+            let queues = self.craft_queues.read().await;
+            queues.get(player_id)
+                .map(|queue| queue.iter().filter(|job| job.recipe_id == recipe.id).count() as u32)
+                .unwrap_or(0)
+        };
+        if crafted.saturating_add(queued).saturating_add(count) > limit {
+            return Err(CraftError::CraftLimitReached);
+        }
+        Ok(())
+    }
+
+    // Across every player's queue, for `max_global`
+    async fn queued_crafts_of(&self, recipe_id: &str) -> u32 {
+        let queues = self.craft_queues.read().await;
+        queues.values()
+            .map(|queue| queue.iter().filter(|job| job.recipe_id == recipe_id).count() as u32)
+            .sum()
+    }
+
+    async fn record_player_craft(&self, player_id: &str, recipe: &Recipe, count: u32) {
+        if recipe.max_per_player.is_none() {
+            return;
+        }
+        let mut counts = self.player_craft_counts.write().await;
+        *counts.entry(player_id.to_string()).or_default().entry(recipe.id.clone()).or_insert(0) += count;
+    }
+
+    // Reserves the ingredients now and cooks the recipe over subsequent game ticks
+    pub async fn queue_craft(&self, player_id: &str, recipe_name: &str, context: &mut PluginContext) -> Result<Uuid, String> {
+        self.ensure_initialized().map_err(|e| e.to_string())?;
+        let (recipe, mastered, crafter_check) = {
+            let recipe_book = self.recipe_book.read().await;
+            let recipe = recipe_book.get_recipe(recipe_name).ok_or("Recipe not found")?;
+            let mastered = recipe_book.is_mastered(&recipe.id);
+            let queued = self.queued_crafts_of(&recipe.id).await;
+            let crafter_check = recipe_book.check_crafter(&recipe, None)
+                .and_then(|_| recipe_book.check_global_limit(&recipe, queued, 1));
+            (recipe, mastered, crafter_check)
+        };
+        if let Err(e) = crafter_check {
+            return self.fail_craft(player_id, &recipe.name, &e.to_string(), Vec::new(), context).await;
+        }
+
+        if !self.meets_attribute_requirements(player_id, &recipe).await {
+            return Err("Player does not meet the recipe's requirements".to_string());
+        }
+        self.check_level(player_id, &recipe).await.map_err(|e| e.to_string())?;
+        self.check_craft_limit(player_id, &recipe, 1).await.map_err(|e| e.to_string())?;
+
+        let mut inventories = self.player_inventories.write().await;
+        let inventory = inventories.get_mut(player_id).ok_or("Player inventory not found")?;
+        let plan = recipe.plan_consumption(1, |name| inventory.available_for(&recipe, name));
+        let (reserved, output_multiplier) = match plan {
+            Some(plan) => {
+                let before = inventory.clone();
+                let reserved = plan.consumed.iter()
+                    .flat_map(|(name, amount)| inventory.take_for(&recipe, name, *amount, self.config.consume_order))
+                    .collect::<Vec<Item>>();
+                self.log_inventory_operation(player_id, InventoryOperation::Craft, &before, inventory).await;
+                (reserved, plan.output_multiplier)
+            }
+            None => {
+                let missing = recipe.missing_ingredients(1, |name| inventory.available_for(&recipe, name));
+                drop(inventories);
+                return self.fail_craft(player_id, &recipe.name, "Missing ingredients", missing, context).await;
+            }
+        };
+        drop(inventories);
+
+        // Buffs active when the craft is queued apply to the whole job
+        let (buff_cook_time, buff_yield) = self.buff_modifiers(player_id).await;
+        let cook_time = self.effective_cook_time(&recipe, 1, mastered) * buff_cook_time;
+        let job = QueuedCraft {
+            id: Uuid::new_v4(),
+            player_id: player_id.to_string(),
+            recipe_id: recipe.id.clone(),
+            recipe_name: recipe.name.clone(),
+            reserved,
+            total_time: cook_time,
+            remaining_time: cook_time,
+            output_multiplier: output_multiplier * buff_yield,
+        };
+        let job_id = job.id;
+        let changes = net_item_changes([], &job.reserved);
+        let mut queues = self.craft_queues.write().await;
+        let queue = queues.entry(player_id.to_string()).or_insert_with(VecDeque::new);
+        // Instant recipes skip the tick queue unless other crafts are waiting ahead of them
+        let instant = if cook_time <= 0.0 && queue.is_empty() {
+            Some(job)
+        } else {
+            queue.push_back(job);
+            None
+        };
+        drop(queues);
+
+        self.emit_craft_started(player_id, &recipe, cook_time, context).await;
+        self.emit_inventory_changed(player_id, changes, context).await;
+
+        if let Some(job) = instant {
+            match self.finish_queued_craft(job).await {
+                Ok(()) => self.dispatch_completed_crafts(context).await,
+                // No room for the outputs yet; it waits in the queue like any finished craft
+                Err(job) => {
+                    let mut queues = self.craft_queues.write().await;
+                    queues.entry(player_id.to_string()).or_insert_with(VecDeque::new).push_front(job);
+                }
+            }
+        }
+
+        Ok(job_id)
+    }
+
+    // Refunds the reserved ingredients; if they no longer fit the craft stays queued
+    pub async fn cancel_craft(&self, player_id: &str, craft_id: Uuid, context: &mut PluginContext) -> Result<Vec<Item>, String> {
+        let mut queues = self.craft_queues.write().await;
+        let queue = queues.get_mut(player_id).ok_or("No queued crafts for player")?;
+        let position = queue.iter().position(|job| job.id == craft_id).ok_or("Craft not found")?;
+
+        let mut inventories = self.player_inventories.write().await;
+        let inventory = inventories.get_mut(player_id).ok_or("Player inventory not found")?;
+
+        let mut refunded = inventory.clone();
+        for item in &queue[position].reserved {
+            if refunded.deposit(item.clone(), self.config.stack_merge_policy).is_err() {
+                return Err("Not enough inventory space to refund ingredients".to_string());
+            }
+        }
+        self.log_inventory_operation(player_id, InventoryOperation::CancelCraft, inventory, &refunded).await;
+        *inventory = refunded;
+        drop(inventories);
+
+        let job = queue.remove(position).ok_or("Craft not found")?;
+        drop(queues);
+
+        self.emit_custom_event(CustomEvent {
+            event_type: "crafting_cancelled".to_string(),
+            data: Arc::new((player_id.to_string(), job.recipe_name.clone())),
+        }, context).await;
+        self.emit_inventory_changed(player_id, net_item_changes(&job.reserved, []), context).await;
+
+        Ok(job.reserved)
+    }
+
+    // The first entry is the active craft; only its remaining_time counts down
+    pub async fn peek_queue(&self, player_id: &str) -> Vec<QueuedCraft> {
+        let queues = self.craft_queues.read().await;
+        queues.get(player_id)
+            .map(|queue| queue.iter().cloned().map(|mut job| {
+                job.remaining_time = job.remaining_time.max(0.0);
+                job
+            }).collect())
+            .unwrap_or_else(Vec::new)
+    }
+
+    // Advances the active craft of every player and completes the ones that are done,
+    // at most `max_craft_completions_per_tick` of them. Finished crafts over the cap stay
+    // at the front of their queue and complete on a later tick.
+    pub async fn process_craft_queue(&self, delta_time: f64) -> usize {
+        let mut finished: Vec<QueuedCraft> = Vec::new();
+        {
+            let mut queues = self.craft_queues.write().await;
+            for queue in queues.values_mut() {
+                if let Some(active) = queue.front_mut() {
+                    active.remaining_time -= delta_time;
+                }
+            }
+
+            // Most overdue first so deferred crafts aren't starved by newer ones
+            let mut ready: Vec<(String, f64)> = queues.iter()
+                .filter_map(|(player_id, queue)| queue.front().map(|job| (player_id.clone(), job.remaining_time)))
+                .filter(|(_, remaining)| *remaining <= 0.0)
+                .collect();
+            ready.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal));
+
+            let cap = self.config.max_craft_completions_per_tick.unwrap_or(usize::MAX);
+            for (player_id, _) in ready.into_iter().take(cap) {
+                if let Some(job) = queues.get_mut(&player_id).and_then(|queue| queue.pop_front()) {
+                    finished.push(job);
+                }
+            }
+        }
+
+        let mut completed = 0;
+        for job in finished {
+            match self.finish_queued_craft(job).await {
+                Ok(()) => completed += 1,
+                Err(job) => {
+                    // No room for the outputs yet; keep the job finished and retry next tick
+                    let mut queues = self.craft_queues.write().await;
+                    queues.entry(job.player_id.clone()).or_insert_with(VecDeque::new).push_front(job);
+                }
+            }
+        }
+
+        completed
+    }
+
+    // Places the outputs and records the craft; events go out with `dispatch_completed_crafts`.
+    // The job is handed back if its outputs don't fit yet.
+    async fn finish_queued_craft(&self, job: QueuedCraft) -> Result<(), QueuedCraft> {
+        let (outputs, containers) = {
+            let recipe_book = self.recipe_book.read().await;
+            match recipe_book.get_recipe(&job.recipe_id) {
+                Some(recipe) => {
+                    let crafted_items: Vec<String> = (0..job.output_multiplier).flat_map(|_| recipe.outputs()).collect();
+                    let craft_count = recipe_book.get_recipe_craft_count(&recipe.id) + 1;
+                    let (mut outputs, containers) = self.create_output_items(&job.player_id, &recipe, &recipe_book.items, &crafted_items, craft_count);
+                    // The reserved ingredients are what was consumed
+                    let mut consumed: Vec<(String, u32)> = Vec::new();
+                    for item in &job.reserved {
+                        match consumed.iter_mut().find(|(name, _)| name == &item.name) {
+                            Some((_, amount)) => *amount += item.quantity,
+                            None => consumed.push((item.name.clone(), item.quantity)),
+                        }
+                    }
+                    outputs.extend(recipe.transformed_returns(&consumed).into_iter().map(|(name, amount)| recipe_book.items.create_item(&name, amount)));
+                    (outputs, containers)
+                }
+                None => (Vec::new(), Vec::new()),
+            }
+        };
+
+        let mut inventories = self.player_inventories.write().await;
+        let placed = match inventories.get_mut(&job.player_id) {
+            Some(inventory) => {
+                let mut updated = inventory.clone();
+                let overflow: Vec<Item> = outputs.into_iter()
+                    .filter_map(|output| updated.deposit_output(output, self.config.stack_merge_policy).err())
+                    .collect();
+                // Under the default policy the job waits until there's room
+                if overflow.is_empty() || self.config.overflow_policy != OverflowPolicy::Error {
+                    let changes = updated.diff(inventory);
+                    self.log_inventory_operation(&job.player_id, InventoryOperation::Craft, inventory, &updated).await;
+                    *inventory = updated;
+                    Some((overflow, changes))
+                } else {
+                    None
+                }
+            }
+            None => None,
+        };
+        drop(inventories);
+
+        let (overflow, changes) = match placed {
+            Some(placed) => placed,
+            None => return Err(job),
+        };
+        self.register_containers(containers).await;
+        self.handle_overflow(&job.player_id, overflow, None).await;
+
+        let recipe = {
+            let mut recipe_book = self.recipe_book.write().await;
+            recipe_book.record_craft(&job.recipe_id, 1);
+            recipe_book.get_recipe(&job.recipe_id)
+        };
+        if let Some(recipe) = recipe {
+            self.record_player_craft(&job.player_id, &recipe, 1).await;
+        }
+        self.completed_crafts.write().await.push((job, changes));
+        Ok(())
+    }
+
+    // In-process listeners hear it straight away; the plugin bus gets it from the next
+    // `dispatch_completed_crafts`, for callers that have no context to send it through
+    async fn emit_deferred_event(&self, event: BroadcastEvent) {
+        self.publish(event.clone());
+        self.deferred_events.write().await.push(event);
+    }
+
+    // Ticks carry no plugin context, so events for crafts finished there, and any other events
+    // raised without a context, are sent from here.
+    // Each player gets a single inventory_changed covering all of their finished crafts.
+    pub async fn dispatch_completed_crafts(&self, context: &mut PluginContext) {
+        let completed: Vec<(QueuedCraft, Vec<(String, i64)>)> = std::mem::take(&mut *self.completed_crafts.write().await);
+        let mut changes: BTreeMap<String, BTreeMap<String, i64>> = BTreeMap::new();
+        for (job, job_changes) in completed {
+            let (outputs, mastered, recipe) = {
+                let recipe_book = self.recipe_book.read().await;
+                let recipe = recipe_book.get_recipe(&job.recipe_id);
+                let outputs = recipe.as_ref().map(|r| r.outputs()).unwrap_or_default();
+                (
+                    (0..job.output_multiplier).flat_map(|_| outputs.iter().cloned()).collect::<Vec<String>>(),
+                    recipe_book.is_mastered(&job.recipe_id),
+                    recipe,
+                )
+            };
+
+            // What actually landed in the inventory, so stack sizes, transformed returns and
+            // outputs sent to overflow are all reflected
+            let player_changes = changes.entry(job.player_id.clone()).or_default();
+            for (item, amount) in job_changes {
+                *player_changes.entry(item).or_insert(0) += amount;
+            }
+
+            self.complete_craft(CraftedEvent {
+                player_id: job.player_id.clone(),
+                recipe_name: job.recipe_name.clone(),
+                outputs,
+                elapsed_secs: job.total_time,
+                sound: recipe.as_ref().and_then(|r| r.sound.clone()),
+                animation: recipe.and_then(|r| r.animation),
+            }, context).await;
+
+            if mastered {
+                self.emit_custom_event(CustomEvent {
+                    event_type: "recipe_mastered".to_string(),
+                    data: Arc::new((job.player_id.clone(), job.recipe_name.clone())),
+                }, context).await;
+            }
+
+            self.check_recipe_unlocks(&job.player_id, context).await;
+        }
+
+        for (player_id, player_changes) in changes {
+            let player_changes: Vec<(String, i64)> = player_changes.into_iter().filter(|(_, amount)| *amount != 0).collect();
+            if !player_changes.is_empty() {
+                self.emit_inventory_changed(&player_id, player_changes, context).await;
+            }
+        }
+
+        let deferred: Vec<BroadcastEvent> = std::mem::take(&mut *self.deferred_events.write().await);
+        for event in deferred {
+            context.dispatch_custom_event(CustomEvent {
+                event_type: format!("{}{}", self.config.event_prefix, event.event_type),
+                data: event.data,
+            }).await;
+        }
+    }
+
+    pub async fn create_storage_container(&self, num_slots: u32) -> StorageContainer {
+        let container = StorageContainer::new(num_slots);
+        let mut containers = self.storage_containers.write().await;
+        containers.insert(container.uuid, container.clone());
+        container
+    }
+
+    pub async fn create_owned_storage_container(&self, owner_id: &str, num_slots: u32) -> StorageContainer {
+        let mut container = StorageContainer::new(num_slots);
+        container.owner = Some(owner_id.to_string());
+        let mut containers = self.storage_containers.write().await;
+        containers.insert(container.uuid, container.clone());
+        container
+    }
+
+    pub async fn grant_container_access(&self, uuid: &Uuid, player_id: &str) -> Result<(), String> {
+        let mut containers = self.storage_containers.write().await;
+        let container = containers.get_mut(uuid).ok_or("Storage container not found")?;
+        container.accessors.insert(player_id.to_string());
+        Ok(())
+    }
+
+    pub async fn revoke_container_access(&self, uuid: &Uuid, player_id: &str) -> Result<(), String> {
+        let mut containers = self.storage_containers.write().await;
+        let container = containers.get_mut(uuid).ok_or("Storage container not found")?;
+        container.accessors.remove(player_id);
+        Ok(())
+    }
+
+    // Containers the player owns or has been given access to
+    pub async fn get_containers_for_player(&self, player_id: &str) -> Vec<StorageContainer> {
+        let containers = self.storage_containers.read().await;
+        let mut accessible: Vec<StorageContainer> = containers.values()
+            .filter(|container| container.can_access(player_id))
+            .cloned()
+            .collect();
+        accessible.sort_by_key(|container| container.uuid);
+        accessible
+    }
+
+    // Drops registered containers with nothing in them and returns how many went. There's no
+    // plugin context here, so purge events only go to `subscribe` listeners.
+    pub async fn purge_empty_containers(&self) -> usize {
+        let purged: Vec<StorageContainer> = {
+            let mut containers = self.storage_containers.write().await;
+            let empty: Vec<Uuid> = containers.values()
+                .filter(|container| container.inventory.slots.values().all(Option::is_none))
+                .map(|container| container.uuid)
+                .collect();
+            empty.iter().filter_map(|uuid| containers.remove(uuid)).collect()
+        };
+
+        if self.config.emit_container_purge_events {
+            for container in &purged {
+                self.publish(BroadcastEvent {
+                    event_type: "storage_container_purged".to_string(),
+                    player_id: container.owner.clone(),
+                    data: Arc::new(container.uuid.to_string()),
+                });
+            }
+        }
+
+        purged.len()
+    }
+
+    pub async fn get_storage_container(&self, uuid: &Uuid) -> Option<StorageContainer> {
+        let containers = self.storage_containers.read().await;
+        containers.get(uuid).cloned()
+    }
+
+    pub async fn update_storage_container(&self, container: StorageContainer) {
+        let mut containers = self.storage_containers.write().await;
+        containers.insert(container.uuid, container);
+    }
+
+    pub async fn get_container_contents(&self, uuid: &Uuid) -> Option<Vec<Item>> {
+        let containers = self.storage_containers.read().await;
+        containers.get(uuid).map(|container| container.contents())
+    }
+
+    pub async fn access_storage_container(&self, container: &mut StorageContainer, player_id: &str, context: &mut PluginContext) {
+        // Here you would implement the logic for a player accessing a storage container
+        // For now, we'll just emit an event
+        self.emit_custom_event(CustomEvent {
+            event_type: "storage_container_accessed".to_string(),
+            data: Arc::new((player_id.to_string(), container.uuid.to_string())),
+        }, context).await;
+    }
+
+    // Moves up to `quantity` from one player's slot into another player's inventory. Both
+    // inventories are edited under one lock, and neither changes unless everything fits.
+    pub async fn transfer_between_players(&self, from_id: &str, to_id: &str, from_slot: u32, quantity: u32, context: &mut PluginContext) -> Result<(), String> {
+        if from_id == to_id {
+            return Err("Cannot transfer items to the same player".to_string());
+        }
+        if quantity == 0 {
+            return Err("Nothing to transfer".to_string());
+        }
+
+        let mut inventories = self.player_inventories.write().await;
+        let mut from = inventories.get(from_id).cloned().ok_or("Source inventory not found")?;
+        let mut to = inventories.get(to_id).cloned().ok_or("Destination inventory not found")?;
+
+        let stack = match from.slots.get_mut(&from_slot) {
+            Some(Some(stack)) => stack,
+            Some(None) => return Err("Source slot is empty".to_string()),
+            None => return Err("Source slot is out of range".to_string()),
+        };
+        let moved = if stack.quantity <= quantity {
+            from.slots.insert(from_slot, None).flatten().unwrap()
+        } else {
+            stack.quantity -= quantity;
+            let mut split = stack.clone();
+            split.quantity = quantity;
+            split
+        };
+
+        to.check_category_cap(&moved, &self.config.category_caps)?;
+        if to.deposit(moved, self.config.stack_merge_policy).is_err() {
+            return Err("Destination inventory is full".to_string());
+        }
+
+        let from_changes = from.diff(&inventories[from_id]);
+        let to_changes = to.diff(&inventories[to_id]);
+        self.log_inventory_operation(from_id, InventoryOperation::Transfer, &inventories[from_id], &from).await;
+        self.log_inventory_operation(to_id, InventoryOperation::Transfer, &inventories[to_id], &to).await;
+        inventories.insert(from_id.to_string(), from);
+        inventories.insert(to_id.to_string(), to);
+        drop(inventories);
+
+        self.emit_inventory_changed(from_id, from_changes, context).await;
+        self.emit_inventory_changed(to_id, to_changes, context).await;
+        Ok(())
+    }
+
+    pub async fn transfer_item(&self, from_inventory: &mut PlayerInventory, to_inventory: &mut PlayerInventory, item_name: &str) -> Result<(), String> {
+        let mut item_to_transfer: Option<Item> = None;
+
+        // Find and remove the item from the source inventory
+        for (slot, item_opt) in from_inventory.slots.iter_mut() {
+            if let Some(item) = item_opt {
+                if item.name == item_name {
+                    item_to_transfer = item_opt.take();
+                    break;
+                }
+            }
+        }
+
+        // If we found the item, add it to the destination inventory
+        if let Some(item) = item_to_transfer {
+            for (slot, item_opt) in to_inventory.slots.iter_mut() {
+                if item_opt.is_none() {
+                    *item_opt = Some(item);
+                    return Ok(());
+                }
+            }
+            // If we couldn't add the item to the destination inventory, put it back in the source
+            for (slot, item_opt) in from_inventory.slots.iter_mut() {
+                if item_opt.is_none() {
+                    *item_opt = Some(item);
+                    break;
+                }
+            }
+            Err("Destination inventory is full".to_string())
+        } else {
+            Err("Item not found in source inventory".to_string())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A fresh directory per test, so tests running in parallel never share files
+    fn temp_dir(name: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("recipesmith-{}-{}", name, Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn write_file(dir: &std::path::Path, name: &str, contents: &str) -> String {
+        let path = dir.join(name);
+        std::fs::write(&path, contents).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    // Hand recipe with no cook time; tests set whatever else they need on the result
+    fn recipe(name: &str, ingredients: &[(&str, u32)], outcome: &str) -> Recipe {
+        let ingredients: Vec<serde_json::Value> = ingredients.iter()
+            .map(|(name, quantity)| serde_json::json!({ "name": name, "quantity": quantity, "recipe_craftable": true }))
+            .collect();
+        serde_json::from_value(serde_json::json!({
+            "name": name,
+            "ingredients": ingredients,
+            "outcome": outcome,
+            "crafters": [],
+            "base_cook_time": 0,
+        })).unwrap()
+    }
+
+    fn context() -> PluginContext {
+        PluginContext::new()
+    }
+
+    // Joins the player with the usual 20 empty slots, then gives them `items`
+    async fn join(smith: &RecipeSmith, player_id: &str, items: Vec<Item>) {
+        smith.create_player_inventory(player_id, 20).await;
+        for item in items {
+            smith.add_item_to_player_inventory(player_id, item).await.unwrap();
+        }
+    }
+
+    fn event_types(events: &[BroadcastEvent]) -> Vec<&str> {
+        events.iter().map(|event| event.event_type.as_str()).collect()
+    }
+
+    // Like `RecipeSmith::with_recipes`, but with a custom config
+    async fn smith_with(config: RecipeSmithConfig, recipes: Vec<Recipe>) -> RecipeSmith {
+        let smith = RecipeSmith::with_config(RecipeSmithConfig { auto_load_recipes: false, ..config });
+        for recipe in recipes {
+            smith.add_new_recipe(recipe).await.unwrap();
+        }
+        smith.initialized.store(true, Ordering::SeqCst);
+        smith
+    }
+
+    // Flour in slots 0, 1 and 2 with 5, 2 and 8 in them
+    fn flour_stacks() -> PlayerInventory {
+        let mut inventory = PlayerInventory::new(4);
+        for (slot, quantity) in [(0, 5), (1, 2), (2, 8)] {
+            inventory.add_item(slot, Item::with_quantity("Flour", quantity).max_stack(64));
+        }
+        inventory
+    }
+
+    fn stack_sizes(inventory: &PlayerInventory) -> Vec<u32> {
+        (0..3).map(|slot| inventory.get_item(slot).map(|item| item.quantity).unwrap_or(0)).collect()
+    }
+
+    // Takes 2 to 6 herbs; every 2 herbs make a potion
+    fn potion_recipe() -> Recipe {
+        let mut potion = recipe("Potion", &[("Herb", 2)], "Potion");
+        potion.ingredients[0].max_quantity = Some(6);
+        potion.output_scaling = OutputScaling::Proportional;
+        potion
+    }
+
+    // A registry that defines Bread but not Cake
+    fn book_with_unknown_output() -> RecipeBook {
+        let mut book = RecipeBook::new();
+        book.items.register(ItemDefinition { name: "Bread".to_string(), model: None, max_stack: 1, meta_tags: HashMap::new() });
+        book.add_recipe(recipe("Bread", &[("Flour", 1)], "Bread"));
+        book.add_recipe(recipe("Cake", &[("Flour", 1)], "Cake"));
+        book
+    }
+
+    fn crafter(name: &str) -> Crafter {
+        Crafter { name: name.to_string() }
+    }
+
+    // Forge-made sword and nails, an anvil-made sword, and a hand-made torch
+    fn station_book() -> RecipeBook {
+        let mut book = RecipeBook::new();
+        let mut forged_sword = recipe("Forged Sword", &[("Iron Ingot", 2)], "Sword");
+        forged_sword.crafters = vec![crafter("Forge")];
+        forged_sword.tags = vec!["weapon".to_string()];
+        let mut nails = recipe("Nails", &[("Iron Ingot", 1)], "Nails");
+        nails.crafters = vec![crafter("Forge")];
+        let mut hammered_sword = recipe("Hammered Sword", &[("Iron Ingot", 3)], "Sword");
+        hammered_sword.crafters = vec![crafter("Anvil")];
+        let mut torch = recipe("Torch", &[("Stick", 1)], "Torch");
+        torch.tags = vec!["light".to_string()];
+        for recipe in [forged_sword, nails, hammered_sword, torch] {
+            book.add_recipe(recipe);
+        }
+        book
+    }
+
+    fn recipe_names(recipes: Vec<Recipe>) -> Vec<String> {
+        let mut names: Vec<String> = recipes.into_iter().map(|recipe| recipe.name).collect();
+        names.sort();
+        names
+    }
+
+    // Records the fields of every tracing event, since there's no subscriber crate to lean on
+    #[derive(Clone, Default)]
+    struct EventCapture(Arc<std::sync::Mutex<Vec<HashMap<String, String>>>>);
+
+    struct FieldVisitor<'a>(&'a mut HashMap<String, String>);
+
+    impl tracing::field::Visit for FieldVisitor<'_> {
+        fn record_str(&mut self, field: &tracing::field::Field, value: &str) {
+            self.0.insert(field.name().to_string(), value.to_string());
+        }
+
+        fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn std::fmt::Debug) {
+            self.0.insert(field.name().to_string(), format!("{:?}", value));
+        }
+    }
+
+    impl tracing::Subscriber for EventCapture {
+        fn enabled(&self, _: &tracing::Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, _: &tracing::span::Attributes<'_>) -> tracing::span::Id {
+            tracing::span::Id::from_u64(1)
+        }
+
+        fn record(&self, _: &tracing::span::Id, _: &tracing::span::Record<'_>) {}
+
+        fn record_follows_from(&self, _: &tracing::span::Id, _: &tracing::span::Id) {}
+
+        fn event(&self, event: &tracing::Event<'_>) {
+            let mut fields = HashMap::from([("level".to_string(), event.metadata().level().to_string())]);
+            event.record(&mut FieldVisitor(&mut fields));
+            self.0.lock().unwrap().push(fields);
+        }
+
+        fn enter(&self, _: &tracing::span::Id) {}
+
+        fn exit(&self, _: &tracing::span::Id) {}
+    }
+
+    // The base game and a mod that both define Bread, with different cook times
+    fn base_and_mod_books() -> (RecipeBook, RecipeBook) {
+        let mut base = RecipeBook::new();
+        base.add_recipe(recipe("Bread", &[("Flour", 2)], "Bread"));
+        let mut modded = RecipeBook::new();
+        let mut mod_bread = recipe("Bread", &[("Flour", 1)], "Bread");
+        mod_bread.base_cook_time = 3;
+        let mut forged = recipe("Nails", &[("Iron Ingot", 1)], "Nails");
+        forged.crafters = vec![Crafter { name: "Forge".to_string() }];
+        modded.add_recipe(mod_bread);
+        modded.add_recipe(forged);
+        (base, modded)
+    }
+
+    // Keeps the ids of the players it was asked to save, in order
+    #[derive(Debug, Default)]
+    struct RecordingStore(std::sync::Mutex<Vec<String>>);
+
+    impl RecordingStore {
+        fn saved(&self) -> Vec<String> {
+            self.0.lock().unwrap().clone()
+        }
+    }
+
+    impl InventoryStore for RecordingStore {
+        fn save_inventory(&self, player_id: &str, _: &PlayerInventory) -> std::io::Result<()> {
+            self.0.lock().unwrap().push(player_id.to_string());
+            Ok(())
+        }
+
+        fn load_inventory(&self, _: &str) -> std::io::Result<Option<PlayerInventory>> {
+            Ok(None)
+        }
+    }
+
+    #[test]
+    fn import_error_names_the_line_and_column_of_the_bad_record() {
+        let dir = temp_dir("import-location");
+        let path = write_file(&dir, "recipes.json", r#"[
+      {"name": "Bread", "ingredients": [], "outcome": "Bread", "crafters": [], "base_cook_time": 5},
+      {"name": "Cake", "ingredients": [], "outcome": "Cake", "crafters": [], "base_cook_time": "slow"}
+    ]"#);
+
+        match RecipeBook::new().import_recipes_from_file(&path) {
+            Err(RecipeSmithError::Parse(e)) => {
+                assert!(matches!(e.location, Some(ImportLocation::Line { line: 3, .. })), "{:?}", e.location);
+                assert!(e.to_string().starts_with(&format!("{}:3:", path)), "{}", e);
+            }
+            other => panic!("expected a parse error, got {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn lenient_import_skips_bad_records_and_keeps_the_rest() {
+        let dir = temp_dir("lenient-json");
+        let path = write_file(&dir, "recipes.json", r#"[
+      {"name": "Bread", "ingredients": [{"name": "Flour", "quantity": 2, "recipe_craftable": true}], "outcome": "Bread", "crafters": [], "base_cook_time": 5},
+      {"name": "Cake", "outcome": "Cake"},
+      {"name": "Soup", "ingredients": [{"name": "Water", "quantity": 0, "recipe_craftable": true}], "outcome": "Soup", "crafters": [], "base_cook_time": 5},
+      {"name": "Toast", "extends": "Bread", "ingredients": [], "outcome": "Toast", "crafters": [], "base_cook_time": 2},
+      {"name": "Pie", "extends": "Pastry", "ingredients": [], "outcome": "Pie", "crafters": [], "base_cook_time": 2}
+    ]"#);
+
+        let mut book = RecipeBook::new();
+        let report = book.import_recipes_lenient(&path);
+        assert_eq!(report.imported, 2);
+        assert_eq!(report.skipped, 3);
+        let locations: Vec<Option<ImportLocation>> = report.errors.iter().map(|e| e.location.clone()).collect();
+        assert_eq!(locations, vec![Some(ImportLocation::Record(2)), Some(ImportLocation::Record(3)), Some(ImportLocation::Record(5))]);
+        assert!(book.get_recipe("Bread").is_some());
+        assert_eq!(book.get_recipe("Toast").unwrap().ingredients.len(), 1);
+    }
+
+    #[test]
+    fn lenient_import_reads_yaml_records_one_by_one() {
+        let dir = temp_dir("lenient-yaml");
+        let path = write_file(&dir, "recipes.yaml", "
+    - name: Bread
+      ingredients:
+        - { name: Flour, quantity: 2, recipe_craftable: true }
+      outcome: Bread
+      crafters: []
+      base_cook_time: 5
+    - name: Broken
+      outcome: Broken
+    ");
+
+        let mut book = RecipeBook::new();
+        let report = book.import_recipes_lenient(&path);
+        assert_eq!((report.imported, report.skipped), (1, 1));
+        assert_eq!(report.errors[0].location, Some(ImportLocation::Record(2)));
+        assert!(book.get_recipe("Bread").is_some());
+    }
+
+    #[tokio::test]
+    async fn container_contents_are_listed_in_slot_order() {
+        let smith = RecipeSmith::with_recipes(Vec::new());
+        let mut container = smith.create_storage_container(4).await;
+        container.inventory.add_item(2, Item::new("Gem"));
+        container.inventory.add_item(0, Item::with_quantity("Coin", 5).max_stack(64));
+        smith.update_storage_container(container.clone()).await;
+
+        let contents = smith.get_container_contents(&container.uuid).await.unwrap();
+        let contents: Vec<(&str, u32)> = contents.iter().map(|item| (item.name.as_str(), item.quantity)).collect();
+        assert_eq!(contents, vec![("Coin", 5), ("Gem", 1)]);
+        assert!(smith.get_container_contents(&Uuid::new_v4()).await.is_none());
+    }
+
+    #[test]
+    fn batch_time_curves_compare_batches_of_one_and_ten() {
+        let linear = BatchTimeCurve::Linear { multiplier: 0.5 };
+        assert_eq!(linear.total_time(10, 1), 10.0);
+        assert_eq!(linear.total_time(10, 10), 55.0);
+
+        let logarithmic = BatchTimeCurve::Logarithmic;
+        assert_eq!(logarithmic.total_time(10, 1), 10.0);
+        assert!((logarithmic.total_time(10, 10) - 10.0 * (1.0 + 10f64.ln())).abs() < 1e-9);
+
+        assert_eq!(BatchTimeCurve::default().total_time(10, 10), 100.0);
+    }
+
+    #[test]
+    fn configured_batch_curve_sets_the_cook_time() {
+        let smith = RecipeSmith::with_config(RecipeSmithConfig {
+            batch_time_curve: BatchTimeCurve::Linear { multiplier: 0.5 },
+            auto_load_recipes: false,
+            ..RecipeSmithConfig::default()
+        });
+        let mut bread = recipe("Bread", &[("Flour", 1)], "Bread");
+        bread.base_cook_time = 10;
+        assert_eq!(smith.effective_cook_time(&bread, 1, false), 10.0);
+        assert_eq!(smith.effective_cook_time(&bread, 10, false), 55.0);
+    }
+
+    #[test]
+    fn recipes_producing_finds_every_recipe_for_an_item() {
+        let mut book = RecipeBook::new();
+        book.add_recipe(recipe("Smelt Iron", &[("Iron Ore", 2)], "Iron Ingot"));
+        let mut mixed = recipe("Smelt Mixed Ore", &[("Mixed Ore", 1)], "Copper Ingot");
+        mixed.byproducts = vec!["Iron Ingot".to_string()];
+        book.add_recipe(mixed);
+        book.add_recipe(recipe("Smelt Gold", &[("Gold Ore", 2)], "Gold Ingot"));
+
+        let mut names: Vec<String> = book.recipes_producing("Iron Ingot").into_iter().map(|recipe| recipe.name).collect();
+        names.sort();
+        assert_eq!(names, ["Smelt Iron", "Smelt Mixed Ore"]);
+        assert!(book.recipes_producing("Silver Ingot").is_empty());
+    }
+
+    #[tokio::test]
+    async fn remove_item_from_an_occupied_slot() {
+        let smith = RecipeSmith::with_recipes(Vec::new());
+        join(&smith, "p1", vec![Item::new("Gem")]).await;
+        smith.start_recording_events();
+
+        let removed = smith.remove_item_from_slot("p1", 0, &mut context()).await.unwrap();
+        assert_eq!(removed.map(|item| item.name), Some("Gem".to_string()));
+        assert!(smith.get_player_inventory("p1").await.unwrap().get_item(0).is_none());
+        assert_eq!(event_types(&smith.stop_recording_events()), ["inventory_changed"]);
+    }
+
+    #[tokio::test]
+    async fn remove_item_from_an_empty_slot() {
+        let smith = RecipeSmith::with_recipes(Vec::new());
+        join(&smith, "p1", Vec::new()).await;
+        smith.start_recording_events();
+
+        assert!(smith.remove_item_from_slot("p1", 3, &mut context()).await.unwrap().is_none());
+        assert!(smith.stop_recording_events().is_empty());
+    }
+
+    #[tokio::test]
+    async fn remove_item_from_an_out_of_range_slot() {
+        let smith = RecipeSmith::with_recipes(Vec::new());
+        join(&smith, "p1", vec![Item::new("Gem")]).await;
+
+        assert!(smith.remove_item_from_slot("p1", 20, &mut context()).await.is_err());
+        assert_eq!(smith.get_player_inventory("p1").await.unwrap().count_item("Gem"), 1);
+    }
+
+    #[test]
+    fn move_item_to_an_empty_slot() {
+        let mut inventory = PlayerInventory::new(4);
+        inventory.add_item(0, Item::new("Gem"));
+
+        inventory.move_item(0, 3, StackMergePolicy::ExactTags).unwrap();
+        assert!(inventory.get_item(0).is_none());
+        assert_eq!(inventory.get_item(3).map(|item| item.name.as_str()), Some("Gem"));
+    }
+
+    #[test]
+    fn move_item_merges_into_the_same_item() {
+        let mut inventory = PlayerInventory::new(4);
+        inventory.add_item(0, Item::with_quantity("Coin", 30).max_stack(50));
+        inventory.add_item(1, Item::with_quantity("Coin", 40).max_stack(50));
+
+        inventory.move_item(0, 1, StackMergePolicy::ExactTags).unwrap();
+        assert_eq!(inventory.get_item(1).map(|item| item.quantity), Some(50));
+        assert_eq!(inventory.get_item(0).map(|item| item.quantity), Some(20));
+    }
+
+    #[test]
+    fn move_item_swaps_with_a_different_item() {
+        let mut inventory = PlayerInventory::new(4);
+        inventory.add_item(0, Item::new("Gem"));
+        inventory.add_item(1, Item::new("Sword"));
+
+        inventory.move_item(0, 1, StackMergePolicy::ExactTags).unwrap();
+        assert_eq!(inventory.get_item(0).map(|item| item.name.as_str()), Some("Sword"));
+        assert_eq!(inventory.get_item(1).map(|item| item.name.as_str()), Some("Gem"));
+        assert!(inventory.move_item(0, 4, StackMergePolicy::ExactTags).is_err());
+    }
+
+    #[tokio::test]
+    async fn renaming_a_recipe_keeps_it_learned() {
+        let smith = RecipeSmith::with_recipes(vec![recipe("Iron Sword", &[("Iron Ingot", 2)], "Iron Sword")]);
+        smith.learn_recipe("p1", "Iron Sword", &mut context()).await.unwrap();
+
+        smith.recipe_book.write().await.rename_recipe("Iron Sword", "Steel Sword").unwrap();
+        assert!(smith.has_learned_recipe("p1", "Steel Sword").await);
+        assert!(!smith.has_learned_recipe("p1", "Iron Sword").await);
+        assert_eq!(smith.recipe_book.read().await.resolve_recipe_id("Steel Sword").as_deref(), Some("iron_sword"));
+    }
+
+    #[test]
+    fn recipe_ids_are_generated_from_names() {
+        assert_eq!(recipe_id_from_name("  Iron Sword (Heavy) "), "iron_sword_heavy");
+
+        let mut book = RecipeBook::new();
+        book.add_recipe(recipe("Iron Sword", &[], "Iron Sword"));
+        book.add_recipe(recipe("Iron-Sword", &[], "Iron Sword"));
+        assert_eq!(book.resolve_recipe_id("Iron Sword").as_deref(), Some("iron_sword"));
+        assert_eq!(book.resolve_recipe_id("Iron-Sword").as_deref(), Some("iron_sword_2"));
+    }
+
+    #[tokio::test]
+    async fn initializing_without_auto_load_reads_no_recipe_files() {
+        // The default files are read relative to the working directory; no other test reads them
+        let dir = temp_dir("auto-load");
+        write_file(&dir, "recipes.json", r#"[{"name": "Plank", "ingredients": [], "outcome": "Plank", "crafters": [], "base_cook_time": 0}]"#);
+        let original = std::env::current_dir().unwrap();
+        std::env::set_current_dir(&dir).unwrap();
+
+        let mut skipped = RecipeSmith::with_config(RecipeSmithConfig {
+            auto_load_recipes: false,
+            unknown_output_policy: UnknownOutputPolicy::Reject,
+            ..RecipeSmithConfig::default()
+        });
+        *skipped.recipe_book.write().await = book_with_unknown_output();
+        skipped.initialize_recipe_smith(&mut context()).await;
+        let mut loaded = RecipeSmith::new();
+        loaded.initialize_recipe_smith(&mut context()).await;
+        std::env::set_current_dir(original).unwrap();
+
+        assert!(skipped.is_initialized());
+        assert!(skipped.recipe_book.read().await.get_recipe("Plank").is_none());
+        assert!(loaded.recipe_book.read().await.get_recipe("Plank").is_some());
+        // Recipes already in the book still go through the unknown-output policy
+        assert!(skipped.recipe_book.read().await.get_recipe("Cake").is_none());
+        assert!(skipped.recipe_book.read().await.get_recipe("Bread").is_some());
+    }
+
+    #[tokio::test]
+    async fn mastering_a_recipe_unlocks_the_next_one() {
+        let mut bread = recipe("Bread", &[("Flour", 1)], "Bread");
+        bread.cook_count = MASTERY_CRAFT_COUNT - 1;
+        let mut cake = recipe("Cake", &[("Flour", 3)], "Cake");
+        cake.unlock_condition = Some(UnlockCondition::MasteredRecipe("Bread".to_string()));
+        let smith = RecipeSmith::with_recipes(vec![bread, cake]);
+        join(&smith, "p1", vec![Item::with_quantity("Flour", 5).max_stack(64)]).await;
+        smith.start_recording_events();
+
+        smith.craft_item_batch("p1", "Bread", 1, &mut context()).await.unwrap();
+        assert!(smith.has_learned_recipe("p1", "Cake").await);
+        let events = smith.stop_recording_events();
+        let learned: Vec<&(String, String)> = events.iter()
+            .filter(|event| event.event_type == "recipe_learned")
+            .filter_map(|event| event.data.downcast_ref::<(String, String)>())
+            .collect();
+        assert_eq!(learned, [&("p1".to_string(), "Cake".to_string())]);
+    }
+
+    #[tokio::test]
+    async fn find_item_slots_lists_every_slot_holding_the_item() {
+        let smith = RecipeSmith::with_recipes(Vec::new());
+        join(&smith, "p1", vec![Item::new("Gem"), Item::new("Sword"), Item::new("Gem"), Item::new("Gem")]).await;
+
+        assert_eq!(smith.find_item_slots("p1", "Gem").await, Some(vec![0, 2, 3]));
+        assert_eq!(smith.find_item_slots("p1", "Shield").await, Some(Vec::new()));
+        assert_eq!(smith.find_item_slots("p2", "Gem").await, None);
+    }
+
+    #[tokio::test]
+    async fn craft_completions_per_tick_are_capped() {
+        let mut bread = recipe("Bread", &[("Flour", 1)], "Bread");
+        bread.base_cook_time = 1;
+        let smith = smith_with(RecipeSmithConfig {
+            max_craft_completions_per_tick: Some(2),
+            ..RecipeSmithConfig::default()
+        }, vec![bread]).await;
+        let players: Vec<String> = (0..5).map(|index| format!("p{}", index)).collect();
+        for player_id in &players {
+            join(&smith, player_id, vec![Item::new("Flour")]).await;
+            smith.queue_craft(player_id, "Bread", &mut context()).await.unwrap();
+        }
+
+        assert_eq!(smith.process_craft_queue(1.0).await, 2);
+        assert_eq!(smith.process_craft_queue(0.0).await, 2);
+        assert_eq!(smith.process_craft_queue(0.0).await, 1);
+        assert_eq!(smith.process_craft_queue(1.0).await, 0);
+        for player_id in &players {
+            assert_eq!(smith.get_player_inventory(player_id).await.unwrap().count_item("Bread"), 1);
+        }
+    }
+
+    #[tokio::test]
+    async fn player_attributes_gate_recipes() {
+        let mut sword = recipe("Sword", &[("Iron Ingot", 1)], "Sword");
+        sword.required_attributes = HashMap::from([("smithing".to_string(), 5.0)]);
+        let smith = RecipeSmith::with_recipes(vec![sword]);
+        join(&smith, "novice", vec![Item::new("Iron Ingot")]).await;
+        join(&smith, "smith", vec![Item::new("Iron Ingot")]).await;
+        smith.set_player_attributes("novice", HashMap::from([("smithing".to_string(), 2.0)])).await;
+        smith.set_player_attributes("smith", HashMap::from([("smithing".to_string(), 5.0)])).await;
+
+        assert!(smith.craft_item_batch("novice", "Sword", 1, &mut context()).await.is_none());
+        assert_eq!(smith.craft_item_batch("smith", "Sword", 1, &mut context()).await, Some(vec!["Sword".to_string()]));
+    }
+
+    #[test]
+    fn lowest_slot_first_drains_stacks_in_slot_order() {
+        let bread = recipe("Bread", &[("Flour", 6)], "Bread");
+        let mut inventory = flour_stacks();
+        inventory.take_for(&bread, "Flour", 6, ConsumeOrder::LowestSlotFirst);
+        assert_eq!(stack_sizes(&inventory), [0, 1, 8]);
+    }
+
+    #[test]
+    fn smallest_first_finishes_small_stacks_before_large_ones() {
+        let bread = recipe("Bread", &[("Flour", 6)], "Bread");
+        let mut inventory = flour_stacks();
+        inventory.take_for(&bread, "Flour", 6, ConsumeOrder::SmallestFirst);
+        assert_eq!(stack_sizes(&inventory), [1, 0, 8]);
+    }
+
+    #[test]
+    fn largest_first_keeps_small_stacks_whole() {
+        let bread = recipe("Bread", &[("Flour", 6)], "Bread");
+        let mut inventory = flour_stacks();
+        inventory.take_for(&bread, "Flour", 6, ConsumeOrder::LargestFirst);
+        assert_eq!(stack_sizes(&inventory), [5, 2, 2]);
+    }
+
+    #[tokio::test]
+    async fn peek_queue_lists_queued_crafts_in_order() {
+        let mut recipes = Vec::new();
+        for name in ["Bread", "Cake", "Pie"] {
+            let mut baked = recipe(name, &[("Flour", 1)], name);
+            baked.base_cook_time = 4;
+            recipes.push(baked);
+        }
+        let smith = RecipeSmith::with_recipes(recipes);
+        join(&smith, "p1", vec![Item::with_quantity("Flour", 3).max_stack(64)]).await;
+        for name in ["Bread", "Cake", "Pie"] {
+            smith.queue_craft("p1", name, &mut context()).await.unwrap();
+        }
+        smith.process_craft_queue(1.0).await;
+
+        let queue = smith.peek_queue("p1").await;
+        let names: Vec<&str> = queue.iter().map(|job| job.recipe_name.as_str()).collect();
+        assert_eq!(names, ["Bread", "Cake", "Pie"]);
+        assert_eq!(queue[0].remaining_time, 3.0);
+        assert_eq!(queue[1].remaining_time, 4.0);
+        assert_eq!(queue[0].reserved.iter().map(|item| item.quantity).sum::<u32>(), 1);
+        assert!(smith.peek_queue("p2").await.is_empty());
+    }
+
+    #[test]
+    fn items_with_equal_tags_merge_into_one_stack() {
+        let mut inventory = PlayerInventory::new(4);
+        let potion = Item::with_quantity("Potion", 2).max_stack(10).tag("effect", "healing");
+        inventory.deposit(potion.clone(), StackMergePolicy::ExactTags).unwrap();
+        inventory.deposit(potion, StackMergePolicy::ExactTags).unwrap();
+
+        assert_eq!(inventory.slots_containing("Potion"), [0]);
+        assert_eq!(inventory.get_item(0).map(|item| item.quantity), Some(4));
+    }
+
+    #[test]
+    fn items_with_different_tags_stay_in_separate_stacks() {
+        let mut inventory = PlayerInventory::new(4);
+        let healing = Item::with_quantity("Potion", 2).max_stack(10).tag("effect", "healing");
+        let poison = Item::with_quantity("Potion", 2).max_stack(10).tag("effect", "poison");
+        assert!(!healing.can_stack_with(&poison, StackMergePolicy::ExactTags));
+        inventory.deposit(healing, StackMergePolicy::ExactTags).unwrap();
+        inventory.deposit(poison, StackMergePolicy::ExactTags).unwrap();
+
+        assert_eq!(inventory.slots_containing("Potion"), [0, 1]);
+    }
+
+    #[test]
+    fn compatible_tags_merge_only_under_the_compatible_policy() {
+        let plain = Item::with_quantity("Potion", 1).max_stack(10).tag("effect", "healing");
+        let signed = Item::with_quantity("Potion", 1).max_stack(10).tag("effect", "healing").tag("brewer", "Ann");
+        assert!(!plain.can_stack_with(&signed, StackMergePolicy::ExactTags));
+        assert!(plain.can_stack_with(&signed, StackMergePolicy::CompatibleTags));
+    }
+
+    #[tokio::test]
+    async fn picking_up_the_last_ingredient_notifies_once() {
+        let smith = RecipeSmith::with_recipes(vec![recipe("Bread", &[("Flour", 2)], "Bread")]);
+        join(&smith, "p1", vec![Item::with_quantity("Flour", 1).max_stack(64)]).await;
+        smith.learn_recipe("p1", "Bread", &mut context()).await.unwrap();
+        smith.start_recording_events();
+
+        let flour = Item::with_quantity("Flour", 1).max_stack(64);
+        smith.modify_player_inventory("p1", &mut context(), |inventory| inventory.deposit(flour, StackMergePolicy::ExactTags)).await.unwrap().unwrap();
+        // Still craftable, so rearranging doesn't notify again
+        smith.move_item("p1", 0, 5, &mut context()).await.unwrap();
+
+        let events = smith.stop_recording_events();
+        let notified: Vec<&(String, String)> = events.iter()
+            .filter(|event| event.event_type == "recipe_now_craftable")
+            .filter_map(|event| event.data.downcast_ref::<(String, String)>())
+            .collect();
+        assert_eq!(notified, [&("p1".to_string(), "Bread".to_string())]);
+    }
+
+    #[test]
+    fn recipe_files_can_use_cook_time_and_omit_cook_count() {
+        let dir = temp_dir("aliases");
+        let path = write_file(&dir, "recipes.json", r#"[
+      {"name": "Bread", "ingredients": [{"name": "Flour", "quantity": 2, "recipe_craftable": true}], "output": "Bread", "crafters": [], "cook_time": 5}
+    ]"#);
+
+        let mut book = RecipeBook::new();
+        book.import_recipes_from_file(&path).unwrap();
+        let bread = book.get_recipe("Bread").unwrap();
+        assert_eq!(bread.base_cook_time, 5);
+        assert_eq!(bread.cook_count, 0);
+        assert_eq!(bread.outcome, "Bread");
+    }
+
+    #[tokio::test]
+    async fn clearing_an_inventory_returns_what_was_in_it() {
+        let smith = RecipeSmith::with_recipes(Vec::new());
+        join(&smith, "p1", vec![Item::new("Sword"), Item::with_quantity("Coin", 12).max_stack(64)]).await;
+        smith.start_recording_events();
+
+        let mut removed: Vec<(String, u32)> = smith.clear_player_inventory("p1", &mut context()).await.unwrap()
+            .into_iter()
+            .map(|item| (item.name, item.quantity))
+            .collect();
+        removed.sort();
+        assert_eq!(removed, [("Coin".to_string(), 12), ("Sword".to_string(), 1)]);
+        assert!(smith.get_player_inventory_contents("p1").await.unwrap().is_empty());
+        assert_eq!(event_types(&smith.stop_recording_events()), ["inventory_changed"]);
+    }
+
+    #[tokio::test]
+    async fn minimum_ingredients_make_a_single_output() {
+        let smith = RecipeSmith::with_recipes(vec![potion_recipe()]);
+        join(&smith, "p1", vec![Item::with_quantity("Herb", 2).max_stack(64)]).await;
+
+        let crafted = smith.craft_item_batch("p1", "Potion", 1, &mut context()).await.unwrap();
+        assert_eq!(crafted.len(), 1);
+        assert_eq!(smith.get_player_inventory("p1").await.unwrap().count_item("Herb"), 0);
+    }
+
+    #[tokio::test]
+    async fn maximum_ingredients_scale_the_output() {
+        let smith = RecipeSmith::with_recipes(vec![potion_recipe()]);
+        join(&smith, "p1", vec![Item::with_quantity("Herb", 8).max_stack(64)]).await;
+
+        let crafted = smith.craft_item_batch("p1", "Potion", 1, &mut context()).await.unwrap();
+        assert_eq!(crafted, ["Potion", "Potion", "Potion"]);
+        // Anything over the maximum is left alone
+        assert_eq!(smith.get_player_inventory("p1").await.unwrap().count_item("Herb"), 2);
+    }
+
+    #[tokio::test]
+    async fn with_recipes_can_craft_straight_away() {
+        let smith = RecipeSmith::with_recipes(vec![recipe("Plank", &[("Log", 1)], "Plank")]);
+        join(&smith, "p1", vec![Item::new("Log")]).await;
+
+        assert_eq!(smith.craft_item_batch("p1", "Plank", 1, &mut context()).await, Some(vec!["Plank".to_string()]));
+        assert_eq!(smith.get_player_inventory("p1").await.unwrap().count_item("Plank"), 1);
+    }
+
+    #[test]
+    fn with_recipes_resolves_inheritance_and_skips_broken_recipes() {
+        let sword = recipe("Iron Sword", &[("Iron Ingot", 2)], "Iron Sword");
+        let mut sharp = recipe("Sharp Iron Sword", &[("Whetstone", 1)], "Sharp Iron Sword");
+        sharp.extends = Some("Iron Sword".to_string());
+        let mut orphan = recipe("Orphan", &[], "Orphan");
+        orphan.extends = Some("Nobody".to_string());
+
+        // The base comes after its child
+        let smith = RecipeSmith::with_recipes(vec![sharp.clone(), sword.clone()]);
+        let book = smith.recipe_book.try_read().unwrap();
+        assert_eq!(book.get_recipe("Sharp Iron Sword").unwrap().ingredients.len(), 2);
+        drop(book);
+
+        // One bad recipe doesn't keep the rest out
+        let smith = RecipeSmith::with_recipes(vec![sword, orphan, sharp]);
+        let book = smith.recipe_book.try_read().unwrap();
+        assert!(book.get_recipe("Orphan").is_none());
+        assert!(book.get_recipe("Iron Sword").is_some());
+        assert_eq!(book.get_recipe("Sharp Iron Sword").unwrap().ingredients.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn renaming_an_item_updates_every_player_holding_it() {
+        let smith = RecipeSmith::with_recipes(Vec::new());
+        join(&smith, "p1", vec![Item::with_quantity("copper_ore", 3).max_stack(64)]).await;
+        join(&smith, "p2", vec![Item::with_quantity("copper_ore", 4).max_stack(64), Item::with_quantity("raw_copper", 5).max_stack(64)]).await;
+        join(&smith, "p3", vec![Item::new("Gem")]).await;
+        smith.start_recording_events();
+
+        let affected = smith.rename_item_everywhere("copper_ore", "raw_copper", &mut context()).await;
+        assert_eq!(affected, ["p1", "p2"]);
+        let p2 = smith.get_player_inventory("p2").await.unwrap();
+        assert_eq!(p2.count_item("copper_ore"), 0);
+        assert_eq!(p2.slots_containing("raw_copper"), [0]);
+        assert_eq!(p2.count_item("raw_copper"), 9);
+        assert_eq!(smith.get_player_inventory("p1").await.unwrap().count_item("raw_copper"), 3);
+        let changed: Vec<Option<String>> = smith.stop_recording_events().into_iter()
+            .filter(|event| event.event_type == "inventory_changed")
+            .map(|event| event.player_id)
+            .collect();
+        assert_eq!(changed, [Some("p1".to_string()), Some("p2".to_string())]);
+    }
+
+    #[test]
+    fn unknown_outputs_get_placeholders_by_default() {
+        let smith = RecipeSmith::new();
+        let mut book = book_with_unknown_output();
+        assert!(matches!(book.validate_outputs(&book.get_recipe("Cake").unwrap()), Err(RecipeValidationError::UnknownOutput { .. })));
+
+        smith.apply_unknown_output_policy(&mut book);
+        assert!(book.get_recipe("Cake").is_some());
+        assert_eq!(book.items.create_item("Cake", 1).model, None);
+    }
+
+    #[test]
+    fn unknown_outputs_can_reject_the_recipe() {
+        let smith = RecipeSmith::with_config(RecipeSmithConfig {
+            unknown_output_policy: UnknownOutputPolicy::Reject,
+            ..RecipeSmithConfig::default()
+        });
+        let mut book = book_with_unknown_output();
+
+        smith.apply_unknown_output_policy(&mut book);
+        assert!(book.get_recipe("Cake").is_none());
+        assert!(book.get_recipe("Bread").is_some());
+    }
+
+    #[test]
+    fn recipes_without_filters_returns_the_whole_book() {
+        let book = station_book();
+        assert_eq!(recipe_names(book.recipes(None, None, None)), ["Forged Sword", "Hammered Sword", "Nails", "Torch"]);
+    }
+
+    #[test]
+    fn recipes_filtered_by_a_single_index() {
+        let book = station_book();
+        // Hand recipes can be made at any station
+        assert_eq!(recipe_names(book.recipes(Some(&crafter("Forge")), None, None)), ["Forged Sword", "Nails", "Torch"]);
+        assert_eq!(recipe_names(book.recipes(None, Some("Sword"), None)), ["Forged Sword", "Hammered Sword"]);
+        assert_eq!(recipe_names(book.recipes(None, None, Some("light"))), ["Torch"]);
+    }
+
+    #[test]
+    fn recipes_filtered_by_combined_indices() {
+        let book = station_book();
+        let forge = crafter("Forge");
+        assert_eq!(recipe_names(book.recipes(Some(&forge), Some("Sword"), None)), ["Forged Sword"]);
+        assert_eq!(recipe_names(book.recipes(Some(&forge), None, Some("weapon"))), ["Forged Sword"]);
+        assert_eq!(recipe_names(book.recipes(None, Some("Sword"), Some("weapon"))), ["Forged Sword"]);
+        assert_eq!(recipe_names(book.recipes(Some(&forge), Some("Sword"), Some("weapon"))), ["Forged Sword"]);
+        assert!(book.recipes(Some(&crafter("Anvil")), Some("Nails"), None).is_empty());
+        assert!(book.recipes(None, Some("Shield"), None).is_empty());
+    }
+
+    #[tokio::test]
+    async fn cancelling_a_half_cooked_craft_refunds_everything() {
+        let mut bread = recipe("Bread", &[("Flour", 2)], "Bread");
+        bread.base_cook_time = 4;
+        let smith = RecipeSmith::with_recipes(vec![bread]);
+        join(&smith, "p1", vec![Item::with_quantity("Flour", 2).max_stack(64)]).await;
+        let craft_id = smith.queue_craft("p1", "Bread", &mut context()).await.unwrap();
+        smith.process_craft_queue(2.0).await;
+        assert_eq!(smith.get_player_inventory("p1").await.unwrap().count_item("Flour"), 0);
+        smith.start_recording_events();
+
+        let refunded = smith.cancel_craft("p1", craft_id, &mut context()).await.unwrap();
+        assert_eq!(refunded.iter().map(|item| item.quantity).sum::<u32>(), 2);
+        assert_eq!(smith.get_player_inventory("p1").await.unwrap().count_item("Flour"), 2);
+        assert!(smith.peek_queue("p1").await.is_empty());
+        assert_eq!(event_types(&smith.stop_recording_events())[0], "crafting_cancelled");
+    }
+
+    #[tokio::test]
+    async fn cancelling_into_a_full_inventory_keeps_the_craft_queued() {
+        let mut bread = recipe("Bread", &[("Flour", 2)], "Bread");
+        bread.base_cook_time = 4;
+        let smith = RecipeSmith::with_recipes(vec![bread]);
+        smith.create_player_inventory("p1", 1).await;
+        smith.add_item_to_player_inventory("p1", Item::with_quantity("Flour", 2).max_stack(64)).await.unwrap();
+        let craft_id = smith.queue_craft("p1", "Bread", &mut context()).await.unwrap();
+        smith.add_item_to_player_inventory("p1", Item::new("Gem")).await.unwrap();
+
+        assert!(smith.cancel_craft("p1", craft_id, &mut context()).await.is_err());
+        assert_eq!(smith.peek_queue("p1").await.len(), 1);
+        assert_eq!(smith.get_player_inventory("p1").await.unwrap().count_item("Gem"), 1);
+    }
+
+    #[test]
+    fn items_with_the_same_name_model_and_tags_are_equal() {
+        let a = Item::with_quantity("Sword", 1).model("sword.glb").tag("damage", 5).tag("enchant", serde_json::json!({ "fire": 1, "ice": 2 }));
+        let b = Item::with_quantity("Sword", 3).model("sword.glb").tag("enchant", serde_json::json!({ "ice": 2, "fire": 1.0 })).tag("damage", 5.0);
+        assert_eq!(a, b);
+
+        let mut set = HashSet::new();
+        set.insert(a);
+        assert!(set.contains(&b));
+    }
+
+    #[test]
+    fn items_with_different_tags_or_models_are_not_equal() {
+        let sword = Item::new("Sword").tag("damage", 5);
+        assert_ne!(sword, Item::new("Sword").tag("damage", 6));
+        assert_ne!(sword, Item::new("Sword").tag("damage", "5"));
+        assert_ne!(sword, Item::new("Sword").tag("damage", 5).model("sword.glb"));
+        assert_ne!(sword, Item::new("Sword"));
+    }
+
+    #[tokio::test]
+    async fn crafted_items_skip_the_hotbar_when_the_backpack_has_room() {
+        let smith = smith_with(RecipeSmithConfig {
+            hotbar_size: 2,
+            ..RecipeSmithConfig::default()
+        }, vec![recipe("Plank", &[("Log", 1)], "Plank")]).await;
+        join(&smith, "p1", vec![Item::new("Log")]).await;
+        let inventory = smith.get_player_inventory("p1").await.unwrap();
+        assert_eq!(inventory.hotbar_slots(), [0, 1]);
+        assert_eq!(inventory.backpack_slots().first(), Some(&2));
+
+        smith.craft_item_batch("p1", "Plank", 1, &mut context()).await.unwrap();
+        assert_eq!(smith.find_item_slots("p1", "Plank").await, Some(vec![2]));
+    }
+
+    #[test]
+    fn crafted_items_fall_back_to_the_hotbar_when_the_backpack_is_full() {
+        let mut inventory = PlayerInventory::with_hotbar(3, 2);
+        inventory.add_item(2, Item::new("Gem"));
+
+        inventory.deposit_output(Item::new("Plank"), StackMergePolicy::ExactTags).unwrap();
+        assert_eq!(inventory.slots_containing("Plank"), [0]);
+    }
+
+    #[test]
+    fn items_round_trip_through_csv_with_nested_tags() {
+        let items = vec![
+            Item::with_quantity("Sword", 1).model("sword.glb").tag("enchant", serde_json::json!({ "fire": { "level": 2 }, "sockets": [1, "ruby", null] })),
+            Item::with_quantity("Coin", 40).max_stack(64),
+        ];
+        let mut csv = Vec::new();
+        export_items_to_csv(&items, &mut csv).unwrap();
+
+        let imported = import_items_from_csv(csv.as_slice()).unwrap();
+        assert_eq!(imported, items);
+        assert_eq!(imported[0].meta_tags, items[0].meta_tags);
+        assert_eq!((imported[1].quantity, imported[1].max_stack), (40, 64));
+    }
+
+    #[tokio::test]
+    async fn crafting_after_waiting_until_ready() {
+        let mut smith = RecipeSmith::with_config(RecipeSmithConfig {
+            auto_load_recipes: false,
+            ..RecipeSmithConfig::default()
+        });
+        assert!(!smith.is_initialized());
+        let waiter = tokio::spawn({
+            let smith = smith.clone();
+            async move { smith.wait_until_ready().await }
+        });
+
+        smith.initialize_recipe_smith(&mut context()).await;
+        waiter.await.unwrap();
+        smith.add_new_recipe(recipe("Plank", &[("Log", 1)], "Plank")).await.unwrap();
+        join(&smith, "p1", vec![Item::new("Log")]).await;
+        assert_eq!(smith.craft_item_batch("p1", "Plank", 1, &mut context()).await, Some(vec!["Plank".to_string()]));
+        // Already ready, so this returns straight away
+        smith.wait_until_ready().await;
+    }
+
+    #[tokio::test]
+    async fn crafting_into_a_chosen_slot() {
+        let smith = RecipeSmith::with_recipes(vec![recipe("Plank", &[("Log", 1)], "Plank")]);
+        join(&smith, "p1", vec![Item::with_quantity("Log", 2).max_stack(64)]).await;
+
+        assert_eq!(smith.craft_item_to_slot("p1", "Plank", Some(7), &mut context()).await, Ok("Plank".to_string()));
+        assert_eq!(smith.find_item_slots("p1", "Plank").await, Some(vec![7]));
+        smith.craft_item_to_slot("p1", "Plank", None, &mut context()).await.unwrap();
+        assert_eq!(smith.find_item_slots("p1", "Plank").await, Some(vec![0, 7]));
+    }
+
+    #[tokio::test]
+    async fn crafting_into_an_occupied_slot_fails_without_consuming() {
+        let smith = RecipeSmith::with_recipes(vec![recipe("Plank", &[("Log", 1)], "Plank")]);
+        join(&smith, "p1", vec![Item::new("Log"), Item::new("Gem")]).await;
+
+        assert!(smith.craft_item_to_slot("p1", "Plank", Some(1), &mut context()).await.is_err());
+        assert!(smith.craft_item_to_slot("p1", "Plank", Some(20), &mut context()).await.is_err());
+        let inventory = smith.get_player_inventory("p1").await.unwrap();
+        assert_eq!(inventory.count_item("Log"), 1);
+        assert_eq!(inventory.count_item("Plank"), 0);
+    }
+
+    #[tokio::test]
+    async fn a_plan_the_inventory_cannot_cover_aborts_without_underflowing() {
+        // Listing Flour twice makes the plan check each entry alone and ask for 4 in total
+        let mut book = RecipeBook::new();
+        book.add_recipe(recipe("Bread", &[("Flour", 2), ("Flour", 2)], "Bread"));
+        let mut inventory = HashMap::from([("Flour".to_string(), Ingredient {
+            name: "Flour".to_string(),
+            quantity: 3,
+            recipe_craftable: true,
+            max_quantity: None,
+            min_quality: None,
+            consumed: true,
+            transformed_to: None,
+        })]);
+
+        let result = book.craft_batch_at("Bread", 1, &mut inventory, 0.0, None).await;
+        assert!(matches!(result, Err(CraftError::Internal(_))));
+        assert_eq!(inventory["Flour"].quantity, 3);
+        assert_eq!(book.get_recipe_craft_count("Bread"), 0);
+    }
+
+    #[test]
+    fn varied_cook_times_stay_within_the_band() {
+        let mut bread = recipe("Bread", &[("Flour", 1)], "Bread");
+        bread.base_cook_time = 10;
+        bread.cook_time_variance = 0.1;
+        // The variance is an f32, so the band edges are only close to 9 and 11
+        assert!((bread.varied_cook_time(10.0, 0.0) - 9.0).abs() < 1e-6);
+        assert!((bread.varied_cook_time(10.0, 0.5) - 10.0).abs() < 1e-6);
+
+        let smith = RecipeSmith::with_config(RecipeSmithConfig {
+            auto_load_recipes: false,
+            ..RecipeSmithConfig::default()
+        }).with_rng(SeededRng::new(42));
+        let times: Vec<f64> = (0..100).map(|_| smith.effective_cook_time(&bread, 1, false)).collect();
+        assert!(times.iter().all(|time| (9.0 - 1e-6..=11.0 + 1e-6).contains(time)));
+        assert!(times.iter().any(|time| *time != times[0]));
+    }
+
+    #[test]
+    fn the_same_seed_gives_the_same_cook_times() {
+        let mut bread = recipe("Bread", &[("Flour", 1)], "Bread");
+        bread.base_cook_time = 10;
+        bread.cook_time_variance = 0.25;
+        let times = |seed| {
+            let smith = RecipeSmith::with_recipes(Vec::new()).with_rng(SeededRng::new(seed));
+            (0..5).map(|_| smith.effective_cook_time(&bread, 1, false)).collect::<Vec<f64>>()
+        };
+        assert_eq!(times(7), times(7));
+    }
+
+    #[tokio::test]
+    async fn recipe_book_lists_only_learned_recipes() {
+        let smith = RecipeSmith::with_recipes(vec![
+            recipe("Bread", &[("Flour", 2)], "Bread"),
+            recipe("Cake", &[("Flour", 3)], "Cake"),
+            recipe("Pie", &[("Flour", 1)], "Pie"),
+        ]);
+        join(&smith, "p1", vec![Item::with_quantity("Flour", 2).max_stack(64)]).await;
+        smith.learn_recipe("p1", "Cake", &mut context()).await.unwrap();
+        smith.learn_recipe("p1", "Bread", &mut context()).await.unwrap();
+
+        let book = smith.get_player_recipe_book("p1").await;
+        let entries: Vec<(&str, bool, bool)> = book.iter().map(|entry| (entry.recipe.name.as_str(), entry.craftable, entry.mastered)).collect();
+        assert_eq!(entries, [("Bread", true, false), ("Cake", false, false)]);
+        assert!(smith.get_player_recipe_book("p2").await.is_empty());
+    }
+
+    #[test]
+    fn importing_a_directory_loads_every_recipe_file() {
+        let dir = temp_dir("recipe-pack");
+        write_file(&dir, "bakery.json", r#"[
+      {"name": "Bread", "ingredients": [{"name": "Flour", "quantity": 2, "recipe_craftable": true}], "outcome": "Bread", "crafters": [], "base_cook_time": 5},
+      {"name": "Cake", "ingredients": [{"name": "Flour", "quantity": 3, "recipe_craftable": true}], "outcome": "Cake", "crafters": [], "base_cook_time": 5}
+    ]"#);
+        write_file(&dir, "forge.yaml", "
+    - name: Nails
+      ingredients:
+        - { name: Iron Ingot, quantity: 1, recipe_craftable: true }
+      outcome: Nails
+      crafters: []
+      base_cook_time: 2
+    ");
+        write_file(&dir, "mill.toml", r#"
+    [[recipes]]
+    name = "Flour"
+    ingredients = [{ name = "Wheat", quantity = 2, recipe_craftable = true }]
+    outcome = "Flour"
+    crafters = []
+    base_cook_time = 1
+    "#);
+        write_file(&dir, "README.txt", "not a recipe file");
+
+        let mut book = RecipeBook::new();
+        assert_eq!(book.import_recipes_from_dir(&dir.to_string_lossy()).unwrap(), 4);
+        for name in ["Bread", "Cake", "Nails", "Flour"] {
+            assert!(book.get_recipe(name).is_some(), "{} wasn't imported", name);
+        }
+    }
+
+    #[tokio::test]
+    async fn learning_a_recipe_twice_emits_one_event() {
+        let smith = RecipeSmith::with_recipes(vec![recipe("Bread", &[("Flour", 2)], "Bread")]);
+        smith.start_recording_events();
+
+        assert_eq!(smith.learn_recipe("p1", "Bread", &mut context()).await, Ok(true));
+        assert_eq!(smith.learn_recipe("p1", "Bread", &mut context()).await, Ok(false));
+        assert_eq!(event_types(&smith.stop_recording_events()), ["recipe_learned"]);
+        assert!(smith.has_learned_recipe("p1", "Bread").await);
+    }
+
+    #[tokio::test]
+    async fn item_crafted_reports_the_mastery_reduced_cook_time() {
+        let mut bread = recipe("Bread", &[("Flour", 1)], "Bread");
+        bread.base_cook_time = 4;
+        bread.cook_count = MASTERY_CRAFT_COUNT;
+        let smith = smith_with(RecipeSmithConfig {
+            mastery_cook_time_multiplier: 0.5,
+            ..RecipeSmithConfig::default()
+        }, vec![bread]).await;
+        join(&smith, "p1", vec![Item::new("Flour")]).await;
+        smith.queue_craft("p1", "Bread", &mut context()).await.unwrap();
+        smith.start_recording_events();
+
+        assert_eq!(smith.process_craft_queue(2.0).await, 1);
+        smith.dispatch_completed_crafts(&mut context()).await;
+        let events = smith.stop_recording_events();
+        let crafted = events.iter()
+            .find(|event| event.event_type == "item_crafted")
+            .and_then(|event| event.data.downcast_ref::<CraftedEvent>())
+            .unwrap();
+        assert_eq!(crafted.recipe_name, "Bread");
+        assert_eq!(crafted.outputs, ["Bread"]);
+        assert_eq!(crafted.elapsed_secs, 2.0);
+    }
+
+    #[tokio::test]
+    async fn the_public_inventory_getter_returns_a_copy() {
+        let smith = RecipeSmith::with_recipes(Vec::new());
+        join(&smith, "p1", vec![Item::new("Gem")]).await;
+
+        let mut inventory = smith.get_player_inventory("p1").await.unwrap();
+        inventory.clear();
+        assert_eq!(smith.get_player_inventory("p1").await.unwrap().count_item("Gem"), 1);
+        assert!(smith.get_player_inventory("p2").await.is_none());
+    }
+
+    #[tokio::test]
+    async fn guarded_inventory_edits_reject_layout_changes() {
+        let smith = RecipeSmith::with_recipes(Vec::new());
+        join(&smith, "p1", vec![Item::new("Gem")]).await;
+
+        let result = smith.modify_player_inventory("p1", &mut context(), |inventory| {
+            inventory.slots.insert(99, Some(Item::new("Sword")));
+        }).await;
+        assert!(result.is_err());
+        assert_eq!(smith.find_item_slots("p1", "Sword").await, Some(Vec::new()));
+
+        smith.modify_player_inventory("p1", &mut context(), |inventory| inventory.move_item(0, 4, StackMergePolicy::ExactTags)).await.unwrap().unwrap();
+        assert_eq!(smith.find_item_slots("p1", "Gem").await, Some(vec![4]));
+    }
+
+    #[test]
+    fn adding_recipes_in_bulk_reports_each_one() {
+        let mut book = RecipeBook::new();
+        let results = book.add_recipes(vec![
+            recipe("Bread", &[("Flour", 2)], "Bread"),
+            recipe("Soup", &[("Water", 0)], "Soup"),
+            recipe(" ", &[("Flour", 1)], "Nothing"),
+            recipe("Cake", &[("Flour", 3)], "Cake"),
+        ]);
+
+        assert_eq!(results.len(), 4);
+        assert!(results[0].is_ok());
+        assert!(matches!(results[1], Err(RecipeValidationError::ZeroQuantity { .. })));
+        assert_eq!(results[2], Err(RecipeValidationError::EmptyName));
+        assert!(results[3].is_ok());
+        assert!(book.get_recipe("Soup").is_none());
+        assert!(book.get_recipe("Cake").is_some());
+    }
+
+    #[test]
+    fn deposit_tops_up_partial_stacks_before_using_empty_slots() {
+        let mut inventory = PlayerInventory::new(4);
+        inventory.add_item(2, Item::with_quantity("Coin", 60).max_stack(64));
+
+        inventory.deposit(Item::with_quantity("Coin", 10).max_stack(64), StackMergePolicy::ExactTags).unwrap();
+        assert_eq!(inventory.get_item(2).map(|item| item.quantity), Some(64));
+        assert_eq!(inventory.get_item(0).map(|item| item.quantity), Some(6));
+        assert_eq!(inventory.count_item("Coin"), 70);
+    }
+
+    #[tokio::test]
+    async fn giving_an_item_fills_the_existing_stack() {
+        let smith = RecipeSmith::with_recipes(Vec::new());
+        join(&smith, "p1", vec![Item::new("Gem"), Item::with_quantity("Coin", 30).max_stack(64)]).await;
+
+        smith.add_item_to_player_inventory("p1", Item::with_quantity("Coin", 20).max_stack(64)).await.unwrap();
+        assert_eq!(smith.find_item_slots("p1", "Coin").await, Some(vec![1]));
+        assert_eq!(smith.get_player_inventory("p1").await.unwrap().count_item("Coin"), 50);
+    }
+
+    #[tokio::test]
+    async fn crafting_stops_at_the_per_player_limit() {
+        let mut crown = recipe("Crown", &[("Gold Ingot", 1)], "Crown");
+        crown.max_per_player = Some(2);
+        let smith = RecipeSmith::with_recipes(vec![crown]);
+        join(&smith, "p1", vec![Item::with_quantity("Gold Ingot", 5).max_stack(64)]).await;
+        join(&smith, "p2", vec![Item::with_quantity("Gold Ingot", 1).max_stack(64)]).await;
+
+        for _ in 0..2 {
+            smith.craft_item_batch("p1", "Crown", 1, &mut context()).await.unwrap();
+        }
+        let rejected = smith.craft_item_inner("p1", "Crown", 1, None, None, &mut context()).await;
+        assert_eq!(rejected, Err(CraftError::CraftLimitReached.to_string()));
+        assert_eq!(smith.get_player_inventory("p1").await.unwrap().count_item("Gold Ingot"), 3);
+        // The limit is per player
+        assert!(smith.craft_item_batch("p2", "Crown", 1, &mut context()).await.is_some());
+    }
+
+    #[test]
+    fn crafting_emits_an_info_event_naming_the_recipe() {
+        let capture = EventCapture::default();
+        let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+        tracing::subscriber::with_default(capture.clone(), || {
+            runtime.block_on(async {
+                let smith = RecipeSmith::with_recipes(vec![recipe("Plank", &[("Log", 1)], "Plank")]);
+                join(&smith, "p1", vec![Item::new("Log")]).await;
+                smith.craft_item_batch("p1", "Plank", 1, &mut context()).await.unwrap();
+            })
+        });
+
+        let events = capture.0.lock().unwrap();
+        let crafted = events.iter().find(|fields| fields.get("message").map(String::as_str) == Some("Item crafted")).unwrap();
+        assert_eq!(crafted["level"], "INFO");
+        assert_eq!(crafted["recipe"], "Plank");
+        assert_eq!(crafted["player_id"], "p1");
+    }
+
+    #[test]
+    fn all_ingredients_lists_each_ingredient_once() {
+        let mut book = RecipeBook::new();
+        book.add_recipe(recipe("Bread", &[("Flour", 2), ("Water", 1)], "Bread"));
+        book.add_recipe(recipe("Cake", &[("Flour", 3), ("Egg", 2), ("Sugar", 1)], "Cake"));
+        book.add_recipe(recipe("Flour", &[("Wheat", 2)], "Flour"));
+
+        let expected: BTreeSet<String> = ["Egg", "Flour", "Sugar", "Water", "Wheat"].into_iter().map(String::from).collect();
+        assert_eq!(book.all_ingredients(), expected);
+        assert!(RecipeBook::new().all_ingredients().is_empty());
+    }
+
+    #[test]
+    fn recipes_needing_unobtainable_materials_are_reported() {
+        let mut book = RecipeBook::new();
+        book.add_recipe(recipe("Flour", &[("Wheat", 2)], "Flour"));
+        book.add_recipe(recipe("Bread", &[("Flour", 2)], "Bread"));
+        book.add_recipe(recipe("Mithril Ingot", &[("Mithril Ore", 2)], "Mithril Ingot"));
+        book.add_recipe(recipe("Mithril Sword", &[("Mithril Ingot", 2), ("Flour", 1)], "Mithril Sword"));
+        let obtainable: HashSet<String> = HashSet::from(["Wheat".to_string()]);
+
+        assert_eq!(book.find_unsatisfiable_recipes(&obtainable), ["Mithril Ingot", "Mithril Sword"]);
+        let with_ore: HashSet<String> = HashSet::from(["Wheat".to_string(), "Mithril Ore".to_string()]);
+        assert!(book.find_unsatisfiable_recipes(&with_ore).is_empty());
+    }
+
+    #[tokio::test]
+    async fn prefixed_events_round_trip() {
+        let smith = smith_with(RecipeSmithConfig {
+            event_prefix: "recipesmith.".to_string(),
+            ..RecipeSmithConfig::default()
+        }, vec![recipe("Plank", &[("Log", 1)], "Plank")]).await;
+        join(&smith, "p1", vec![Item::new("Log")]).await;
+        smith.start_recording_events();
+
+        smith.craft_item_batch("p1", "Plank", 1, &mut context()).await.unwrap();
+        // In-process listeners see the bare names; only the plugin bus gets the prefix
+        let events = smith.stop_recording_events();
+        assert!(event_types(&events).contains(&"item_crafted"));
+
+        // Our own prefixed events are handled and anyone else's are ignored
+        assert_eq!(smith.own_event_type("recipesmith.item_crafted"), Some("item_crafted"));
+        assert_eq!(smith.own_event_type("item_crafted"), None);
+        assert_eq!(smith.own_event_type("other.item_crafted"), None);
+        assert_eq!(smith.own_event_type("recipesmith.unknown"), None);
+        for event_type in ["recipesmith.item_crafted", "item_crafted", "other.item_crafted"] {
+            smith.on_game_event(&GameEvent::Custom(CustomEvent {
+                event_type: event_type.to_string(),
+                data: Arc::clone(&events[0].data),
+            })).await;
+        }
+
+        // Without a prefix every one of our event names is ours
+        let bare = RecipeSmith::with_recipes(Vec::new());
+        assert!(CUSTOM_EVENT_TYPES.iter().all(|event_type| bare.own_event_type(event_type) == Some(*event_type)));
+    }
+
+    #[tokio::test]
+    async fn containers_for_a_player_include_owned_and_shared_ones() {
+        let smith = RecipeSmith::with_recipes(Vec::new());
+        let chest = smith.create_owned_storage_container("p1", 10).await;
+        let barrel = smith.create_owned_storage_container("p1", 5).await;
+        let shared = smith.create_owned_storage_container("p2", 10).await;
+        smith.create_owned_storage_container("p2", 10).await;
+        smith.create_storage_container(10).await;
+        smith.grant_container_access(&shared.uuid, "p1").await.unwrap();
+
+        let mut expected = vec![chest.uuid, barrel.uuid, shared.uuid];
+        expected.sort();
+        let listed: Vec<Uuid> = smith.get_containers_for_player("p1").await.iter().map(|container| container.uuid).collect();
+        assert_eq!(listed, expected);
+
+        smith.revoke_container_access(&shared.uuid, "p1").await.unwrap();
+        assert_eq!(smith.get_containers_for_player("p1").await.len(), 2);
+        assert!(smith.get_containers_for_player("p3").await.is_empty());
+    }
+
+    #[test]
+    fn sorting_by_rarity_puts_the_rarest_first_and_untagged_last() {
+        let mut inventory = PlayerInventory::new(6);
+        inventory.add_item(0, Item::new("Stick"));
+        inventory.add_item(1, Item::new("Dagger").tag("rarity", "common"));
+        inventory.add_item(2, Item::new("Crown").tag("rarity", "Legendary"));
+        inventory.add_item(3, Item::new("Ring").tag("rarity", 3));
+        inventory.add_item(4, Item::new("Amulet").tag("rarity", "rare"));
+        inventory.add_item(5, Item::new("Pebble").tag("rarity", "mythic-ish"));
+
+        inventory.sort_and_consolidate(SortKey::Rarity, StackMergePolicy::ExactTags);
+        let order: Vec<&str> = (0..6).filter_map(|slot| inventory.get_item(slot)).map(|item| item.name.as_str()).collect();
+        assert_eq!(order, ["Crown", "Ring", "Amulet", "Dagger", "Pebble", "Stick"]);
+    }
+
+    #[test]
+    fn rarity_ranks_names_and_numbers() {
+        assert_eq!(Item::new("Crown").tag("rarity", "legendary").rarity(), Some(4));
+        assert_eq!(Item::new("Ring").tag("rarity", 7).rarity(), Some(7));
+        assert_eq!(Item::new("Stick").rarity(), None);
+        assert_eq!(Item::new("Pebble").tag("rarity", "shiny").rarity(), None);
+    }
+
+    #[tokio::test]
+    async fn outputs_per_minute_accounts_for_outputs_and_mastery() {
+        let mut bread = recipe("Bread", &[("Flour", 2)], "Bread");
+        bread.base_cook_time = 30;
+        bread.byproducts = vec!["Crumbs".to_string()];
+        let mut mastered = bread.clone();
+        mastered.name = "Mastered Bread".to_string();
+        mastered.cook_count = MASTERY_CRAFT_COUNT;
+        let smith = smith_with(RecipeSmithConfig {
+            mastery_cook_time_multiplier: 0.5,
+            ..RecipeSmithConfig::default()
+        }, vec![bread, mastered]).await;
+
+        // Two outputs every 30 seconds
+        assert_eq!(smith.outputs_per_minute("Bread").await, 4.0);
+        assert_eq!(smith.outputs_per_minute("Mastered Bread").await, 8.0);
+        // The book and the plugin agree on a mastered recipe
+        assert_eq!(smith.recipe_book.read().await.outputs_per_minute("Mastered Bread", 0.5), 8.0);
+        assert_eq!(smith.recipe_book.read().await.outputs_per_minute("Bread", 0.5), 4.0);
+        assert_eq!(smith.outputs_per_minute("Cake").await, 0.0);
+    }
+
+    #[tokio::test]
+    async fn crafting_through_an_alias() {
+        let smith = RecipeSmith::with_recipes(vec![recipe("Iron Sword", &[("Iron Ingot", 2)], "Iron Sword")]);
+        {
+            let mut recipe_book = smith.recipe_book.write().await;
+            recipe_book.add_recipe_alias("IronSword", "Iron Sword").unwrap();
+            assert!(recipe_book.add_recipe_alias("Sword", "Steel Sword").is_err());
+            assert_eq!(recipe_book.get_recipe("IronSword").map(|recipe| recipe.name), Some("Iron Sword".to_string()));
+        }
+        join(&smith, "p1", vec![Item::with_quantity("Iron Ingot", 2).max_stack(64)]).await;
+
+        assert_eq!(smith.craft_item_batch("p1", "IronSword", 1, &mut context()).await, Some(vec!["Iron Sword".to_string()]));
+        assert_eq!(smith.recipe_book.read().await.get_recipe_craft_count("Iron Sword"), 1);
+    }
+
+    #[test]
+    fn a_flaky_read_succeeds_on_the_second_attempt() {
+        let retry = ImportRetry { max_attempts: 3, initial_backoff: std::time::Duration::ZERO };
+        let mut attempts = 0;
+        let result = retry.run(|| {
+            attempts += 1;
+            if attempts == 1 {
+                Err(std::io::Error::new(std::io::ErrorKind::TimedOut, "network hiccup"))
+            } else {
+                Ok("recipes")
+            }
+        });
+        assert_eq!(result.unwrap(), "recipes");
+        assert_eq!(attempts, 2);
+    }
+
+    #[test]
+    fn missing_files_and_exhausted_retries_fail() {
+        let retry = ImportRetry { max_attempts: 3, initial_backoff: std::time::Duration::ZERO };
+        let mut attempts = 0;
+        let missing: std::io::Result<()> = retry.run(|| {
+            attempts += 1;
+            Err(std::io::Error::new(std::io::ErrorKind::NotFound, "gone"))
+        });
+        assert!(missing.is_err());
+        assert_eq!(attempts, 1);
+
+        attempts = 0;
+        let flaky: std::io::Result<()> = retry.run(|| {
+            attempts += 1;
+            Err(std::io::Error::new(std::io::ErrorKind::TimedOut, "still down"))
+        });
+        assert!(flaky.is_err());
+        assert_eq!(attempts, 3);
+    }
+
+    #[test]
+    fn only_transient_errors_are_retried_and_only_when_asked() {
+        let mut attempts = 0;
+        let unretried: std::io::Result<()> = ImportRetry::default().run(|| {
+            attempts += 1;
+            Err(std::io::Error::new(std::io::ErrorKind::TimedOut, "network hiccup"))
+        });
+        assert!(unretried.is_err());
+        assert_eq!(attempts, 1);
+
+        let retry = ImportRetry { max_attempts: 3, initial_backoff: std::time::Duration::ZERO };
+        for kind in [std::io::ErrorKind::InvalidData, std::io::ErrorKind::IsADirectory, std::io::ErrorKind::Other] {
+            attempts = 0;
+            let permanent: std::io::Result<()> = retry.run(|| {
+                attempts += 1;
+                Err(std::io::Error::new(kind, "won't get better"))
+            });
+            assert!(permanent.is_err());
+            assert_eq!(attempts, 1, "{:?} was retried", kind);
+        }
+    }
+
+    #[tokio::test]
+    async fn async_retries_wait_out_a_flaky_read() {
+        let retry = ImportRetry { max_attempts: 3, initial_backoff: std::time::Duration::from_millis(1) };
+        let attempts = std::sync::atomic::AtomicU32::new(0);
+        let result = retry.run_async(|| async {
+            match attempts.fetch_add(1, Ordering::SeqCst) {
+                0 => Err(std::io::Error::new(std::io::ErrorKind::Interrupted, "interrupted")),
+                _ => Ok("recipes"),
+            }
+        }).await;
+        assert_eq!(result.unwrap(), "recipes");
+        assert_eq!(attempts.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn the_preview_matches_the_real_craft() {
+        let smith = RecipeSmith::with_recipes(vec![recipe("Plank", &[("Log", 2)], "Plank")]);
+        join(&smith, "p1", vec![Item::with_quantity("Log", 5).max_stack(64), Item::new("Gem")]).await;
+        let before = smith.get_player_inventory("p1").await.unwrap();
+
+        let preview = smith.simulate_craft("p1", "Plank").await.unwrap();
+        assert_eq!(preview.consumed, [("Log".to_string(), 2)]);
+        assert_eq!(preview.produced, ["Plank"]);
+        assert_eq!(smith.get_player_inventory("p1").await.unwrap().checksum(), before.checksum());
+
+        smith.craft_item_batch("p1", "Plank", 1, &mut context()).await.unwrap();
+        let after = smith.get_player_inventory("p1").await.unwrap();
+        assert_eq!(preview.inventory.checksum(), after.checksum());
+        assert_eq!(preview.inventory.slots_containing("Plank"), after.slots_containing("Plank"));
+    }
+
+    #[tokio::test]
+    async fn crafting_failed_lists_the_shortfall() {
+        let smith = RecipeSmith::with_recipes(vec![recipe("Iron Sword", &[("Iron Ingot", 3), ("Leather", 1), ("Stick", 1)], "Iron Sword")]);
+        join(&smith, "p1", vec![Item::with_quantity("Iron Ingot", 1).max_stack(64), Item::new("Stick")]).await;
+        smith.start_recording_events();
+
+        assert!(smith.craft_item_batch("p1", "Iron Sword", 1, &mut context()).await.is_none());
+        let events = smith.stop_recording_events();
+        let failed = events.iter()
+            .find(|event| event.event_type == "crafting_failed")
+            .and_then(|event| event.data.downcast_ref::<CraftFailedEvent>())
+            .unwrap();
+        assert_eq!(failed.recipe_name, "Iron Sword");
+        let mut missing = failed.missing.clone();
+        missing.sort();
+        assert_eq!(missing, [("Iron Ingot".to_string(), 2), ("Leather".to_string(), 1)]);
+        assert!(!event_types(&events).contains(&"craft_started"));
+    }
+
+    #[test]
+    fn merging_keeps_existing_recipes() {
+        let (mut base, modded) = base_and_mod_books();
+        base.merge(modded, MergePolicy::KeepExisting).unwrap();
+
+        assert_eq!(base.get_recipe("Bread").unwrap().base_cook_time, 0);
+        assert!(base.get_recipe("Nails").is_some());
+        let forge = Crafter { name: "Forge".to_string() };
+        assert!(base.get_recipes_for_crafter(&forge).iter().any(|recipe| recipe.name == "Nails"));
+    }
+
+    #[test]
+    fn merging_can_overwrite_existing_recipes() {
+        let (mut base, modded) = base_and_mod_books();
+        let id = base.resolve_recipe_id("Bread");
+        base.merge(modded, MergePolicy::Overwrite).unwrap();
+
+        let bread = base.get_recipe("Bread").unwrap();
+        assert_eq!(bread.base_cook_time, 3);
+        assert_eq!(Some(bread.id), id);
+        assert_eq!(base.recipes_producing("Bread").len(), 1);
+    }
+
+    #[test]
+    fn merging_can_refuse_collisions() {
+        let (mut base, modded) = base_and_mod_books();
+        assert!(base.merge(modded, MergePolicy::Error).is_err());
+
+        assert_eq!(base.get_recipe("Bread").unwrap().base_cook_time, 0);
+        assert!(base.get_recipe("Nails").is_none());
+    }
+
+    #[tokio::test]
+    async fn craft_complete_callbacks_get_the_record() {
+        let smith = RecipeSmith::with_recipes(vec![recipe("Plank", &[("Log", 1)], "Plank")]);
+        join(&smith, "p1", vec![Item::new("Log")]).await;
+        let records = Arc::new(std::sync::Mutex::new(Vec::new()));
+        smith.on_craft_complete({
+            let records = Arc::clone(&records);
+            move |record: &CraftedEvent| records.lock().unwrap().push(record.clone())
+        }).await;
+
+        smith.craft_item_batch("p1", "Plank", 1, &mut context()).await.unwrap();
+        assert!(smith.craft_item_batch("p1", "Plank", 1, &mut context()).await.is_none());
+        let records = records.lock().unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!((records[0].player_id.as_str(), records[0].recipe_name.as_str()), ("p1", "Plank"));
+        assert_eq!(records[0].outputs, ["Plank"]);
+    }
+
+    #[tokio::test]
+    async fn category_caps_apply_while_slots_are_free() {
+        let smith = smith_with(RecipeSmithConfig {
+            category_caps: HashMap::from([("potion".to_string(), 3)]),
+            ..RecipeSmithConfig::default()
+        }, Vec::new()).await;
+        join(&smith, "p1", Vec::new()).await;
+        let potion = |name: &str| Item::new(name).tag("category", "potion");
+
+        smith.add_item_to_player_inventory("p1", potion("Healing Potion")).await.unwrap();
+        smith.add_item_to_player_inventory("p1", Item::with_quantity("Mana Potion", 2).max_stack(8).tag("category", "potion")).await.unwrap();
+        let error = smith.add_item_to_player_inventory("p1", potion("Speed Potion")).await.unwrap_err();
+        assert!(error.starts_with("Category limit reached"));
+        assert_eq!(smith.find_item_slots("p1", "Speed Potion").await, Some(Vec::new()));
+        // Other categories aren't affected
+        smith.add_item_to_player_inventory("p1", Item::new("Sword").tag("category", "weapon")).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn crafting_a_kit_registers_a_filled_container() {
+        let mut kit = recipe("Starter Kit", &[("Plank", 4)], "Starter Kit");
+        kit.container_output = Some(ContainerOutput {
+            num_slots: 6,
+            contents: vec![
+                ContainerContent { name: "Torch".to_string(), quantity: 3 },
+                ContainerContent { name: "Rope".to_string(), quantity: 1 },
+            ],
+        });
+        let smith = RecipeSmith::with_recipes(vec![kit]);
+        smith.register_item(ItemDefinition { name: "Torch".to_string(), model: None, max_stack: 16, meta_tags: HashMap::new() }).await;
+        join(&smith, "p1", vec![Item::with_quantity("Plank", 4).max_stack(64)]).await;
+
+        smith.craft_item_batch("p1", "Starter Kit", 1, &mut context()).await.unwrap();
+        let inventory = smith.get_player_inventory("p1").await.unwrap();
+        let kit = inventory.get_item(inventory.slots_containing("Starter Kit")[0]).unwrap();
+        let uuid: Uuid = kit.meta_tags["container_uuid"].as_str().unwrap().parse().unwrap();
+
+        let contents: Vec<(String, u32)> = smith.get_container_contents(&uuid).await.unwrap().into_iter().map(|item| (item.name, item.quantity)).collect();
+        assert_eq!(contents, [("Torch".to_string(), 3), ("Rope".to_string(), 1)]);
+        let owned: Vec<Uuid> = smith.get_containers_for_player("p1").await.iter().map(|container| container.uuid).collect();
+        assert_eq!(owned, [uuid]);
+    }
+
+    #[tokio::test]
+    async fn player_subscriptions_skip_other_players_events() {
+        let smith = RecipeSmith::with_recipes(vec![recipe("Plank", &[("Log", 1)], "Plank")]);
+        join(&smith, "p1", vec![Item::new("Log")]).await;
+        join(&smith, "p2", vec![Item::new("Log")]).await;
+        let mut events = smith.subscribe_for_player("p1");
+
+        smith.craft_item_batch("p2", "Plank", 1, &mut context()).await.unwrap();
+        smith.craft_item_batch("p1", "Plank", 1, &mut context()).await.unwrap();
+        loop {
+            let event = events.recv().await.unwrap();
+            assert_eq!(event.player_id.as_deref(), Some("p1"));
+            if event.event_type == "item_crafted" {
+                break;
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn crafted_items_get_a_resolved_model() {
+        let smith = smith_with(RecipeSmithConfig {
+            model_resolver: Some(Arc::new(ConventionModelResolver::default())),
+            ..RecipeSmithConfig::default()
+        }, vec![
+            recipe("Iron Sword", &[("Iron Ingot", 1)], "Iron Sword"),
+            recipe("Shield", &[("Plank", 1)], "Shield"),
+        ]).await;
+        smith.register_item(ItemDefinition { name: "Shield".to_string(), model: Some("shields/round.glb".to_string()), max_stack: 1, meta_tags: HashMap::new() }).await;
+        join(&smith, "p1", vec![Item::new("Iron Ingot"), Item::new("Plank")]).await;
+
+        smith.craft_item_batch("p1", "Iron Sword", 1, &mut context()).await.unwrap();
+        smith.craft_item_batch("p1", "Shield", 1, &mut context()).await.unwrap();
+        let inventory = smith.get_player_inventory("p1").await.unwrap();
+        let model = |name: &str| inventory.get_item(inventory.slots_containing(name)[0]).and_then(|item| item.model.clone());
+        assert_eq!(model("Iron Sword").as_deref(), Some("models/iron_sword.glb"));
+        // The registry's model wins over the resolver
+        assert_eq!(model("Shield").as_deref(), Some("shields/round.glb"));
+    }
+
+    #[tokio::test]
+    async fn a_batch_craft_emits_one_inventory_changed() {
+        let smith = RecipeSmith::with_recipes(vec![recipe("Plank", &[("Log", 1)], "Plank")]);
+        join(&smith, "p1", vec![Item::with_quantity("Log", 3).max_stack(64)]).await;
+        smith.start_recording_events();
+
+        smith.craft_item_batch("p1", "Plank", 3, &mut context()).await.unwrap();
+        let events = smith.stop_recording_events();
+        let changed: Vec<&InventoryChangedEvent> = events.iter()
+            .filter(|event| event.event_type == "inventory_changed")
+            .filter_map(|event| event.data.downcast_ref::<InventoryChangedEvent>())
+            .collect();
+        assert_eq!(changed.len(), 1);
+        assert_eq!(changed[0].changes, [("Log".to_string(), -3), ("Plank".to_string(), 3)]);
+    }
+
+    #[tokio::test]
+    async fn queued_crafts_report_what_landed_in_the_inventory() {
+        let mut stew = recipe("Stew", &[("Bucket of Water", 2)], "Stew");
+        stew.ingredients[0].transformed_to = Some("Bucket".to_string());
+        stew.base_cook_time = 1;
+        let smith = RecipeSmith::with_recipes(vec![stew]);
+        join(&smith, "p1", vec![Item::with_quantity("Bucket of Water", 2).max_stack(64)]).await;
+        smith.queue_craft("p1", "Stew", &mut context()).await.unwrap();
+        smith.start_recording_events();
+
+        assert_eq!(smith.process_craft_queue(1.0).await, 1);
+        smith.dispatch_completed_crafts(&mut context()).await;
+        let events = smith.stop_recording_events();
+        let changed: Vec<&InventoryChangedEvent> = events.iter()
+            .filter(|event| event.event_type == "inventory_changed")
+            .filter_map(|event| event.data.downcast_ref::<InventoryChangedEvent>())
+            .collect();
+        // The ingredients were taken when the craft was queued; the buckets come back with the stew
+        assert_eq!(changed.len(), 1);
+        assert_eq!(changed[0].changes, [("Bucket".to_string(), 2), ("Stew".to_string(), 1)]);
+    }
+
+    #[test]
+    fn recipes_without_crafters_are_handcraftable() {
+        let torch = recipe("Torch", &[("Stick", 1)], "Torch");
+        let mut ingot = recipe("Iron Ingot", &[("Iron Ore", 1)], "Iron Ingot");
+        ingot.crafters = vec![Crafter { name: "Furnace".to_string() }];
+        assert!(torch.is_handcraftable());
+        assert!(!ingot.is_handcraftable());
+
+        let mut book = RecipeBook::new();
+        book.add_recipe(torch.clone());
+        book.add_recipe(ingot.clone());
+        let anvil = Crafter { name: "Anvil".to_string() };
+        // Hand recipes can be made anywhere; the rest only at their own crafters
+        assert_eq!(book.check_crafter(&torch, None), Ok(()));
+        assert_eq!(book.check_crafter(&torch, Some(&anvil)), Ok(()));
+        assert_eq!(book.check_crafter(&ingot, Some(&anvil)), Err(CraftError::WrongCrafter));
+        assert_eq!(book.check_crafter(&ingot, Some(&Crafter { name: "Furnace".to_string() })), Ok(()));
+    }
+
+    #[test]
+    fn import_errors_can_be_matched_on() {
+        let dir = temp_dir("errors");
+        let notes = write_file(&dir, "recipes.txt", "Bread needs flour");
+        let broken = write_file(&dir, "recipes.json", "[{");
+        let mut book = RecipeBook::new();
+
+        assert!(matches!(book.import_recipes_from_file(&notes), Err(RecipeSmithError::UnsupportedFormat(path)) if path == notes));
+        assert!(matches!(book.import_recipes_from_file(&broken), Err(RecipeSmithError::Parse(_))));
+        let missing = dir.join("missing.json").to_string_lossy().to_string();
+        assert!(matches!(book.import_recipes_from_file(&missing), Err(RecipeSmithError::Io { .. })));
+    }
+
+    #[test]
+    fn a_child_recipe_inherits_and_adds_ingredients() {
+        let mut sword = recipe("Iron Sword", &[("Iron Ingot", 2), ("Stick", 1)], "Iron Sword");
+        sword.crafters = vec![Crafter { name: "Anvil".to_string() }];
+        let mut sharp = recipe("Sharp Iron Sword", &[("Whetstone", 1)], "Sharp Iron Sword");
+        sharp.extends = Some("Iron Sword".to_string());
+        let mut book = RecipeBook::new();
+
+        let resolved = book.resolve_inheritance(vec![sharp, sword]).unwrap();
+        for recipe in resolved {
+            book.add_recipe(recipe);
+        }
+        let sharp = book.get_recipe("Sharp Iron Sword").unwrap();
+        let ingredients: Vec<(&str, u32)> = sharp.ingredients.iter().map(|ingredient| (ingredient.name.as_str(), ingredient.quantity)).collect();
+        assert_eq!(ingredients, [("Iron Ingot", 2), ("Stick", 1), ("Whetstone", 1)]);
+        assert_eq!(sharp.crafters, [Crafter { name: "Anvil".to_string() }]);
+        assert_eq!(sharp.outcome, "Sharp Iron Sword");
+    }
+
+    #[test]
+    fn inheritance_cycles_are_rejected() {
+        let mut a = recipe("A", &[("Stick", 1)], "A");
+        a.extends = Some("B".to_string());
+        let mut b = recipe("B", &[("Stick", 1)], "B");
+        b.extends = Some("A".to_string());
+
+        let book = RecipeBook::new();
+        assert!(matches!(book.resolve_inheritance(vec![a, b]), Err(RecipeValidationError::InheritanceCycle { .. })));
+        let mut orphan = recipe("Orphan", &[], "Orphan");
+        orphan.extends = Some("Nobody".to_string());
+        assert!(matches!(book.resolve_inheritance(vec![orphan]), Err(RecipeValidationError::UnknownBase { .. })));
+    }
+
+    #[test]
+    fn equal_inventories_have_equal_checksums() {
+        let mut a = PlayerInventory::new(4);
+        a.add_item(0, Item::with_quantity("Coin", 5).max_stack(64).tag("mint", "royal").tag("year", 1));
+        a.add_item(2, Item::new("Gem"));
+        let mut b = PlayerInventory::new(4);
+        b.add_item(2, Item::new("Gem"));
+        b.add_item(0, Item::with_quantity("Coin", 5).max_stack(64).tag("year", 1.0).tag("mint", "royal"));
+        assert_eq!(a.checksum(), b.checksum());
+
+        b.add_item(2, Item::with_quantity("Gem", 2).max_stack(2));
+        assert_ne!(a.checksum(), b.checksum());
+        let mut moved = a.clone();
+        moved.move_item(2, 3, StackMergePolicy::ExactTags).unwrap();
+        assert_ne!(a.checksum(), moved.checksum());
+    }
+
+    #[tokio::test]
+    async fn inventory_checksum_follows_the_players_inventory() {
+        let smith = RecipeSmith::with_recipes(Vec::new());
+        join(&smith, "p1", vec![Item::new("Gem")]).await;
+        let before = smith.inventory_checksum("p1").await.unwrap();
+        assert_eq!(smith.inventory_checksum("p1").await, Some(before));
+
+        smith.add_item_to_player_inventory("p1", Item::new("Gem")).await.unwrap();
+        assert_ne!(smith.inventory_checksum("p1").await, Some(before));
+        assert_eq!(smith.inventory_checksum("p2").await, None);
+    }
+
+    #[tokio::test]
+    async fn outputs_depend_on_the_crafter_used() {
+        let basic = Crafter { name: "Basic Furnace".to_string() };
+        let advanced = Crafter { name: "Advanced Furnace".to_string() };
+        let mut smelt = recipe("Smelt Iron", &[("Iron Ore", 1)], "Iron Ingot");
+        smelt.crafters = vec![basic.clone(), advanced.clone()];
+        smelt.crafter_outputs = HashMap::from([("Advanced Furnace".to_string(), vec!["Iron Ingot".to_string(), "Slag".to_string()])]);
+        let smith = RecipeSmith::with_recipes(vec![smelt]);
+        join(&smith, "p1", vec![Item::with_quantity("Iron Ore", 2).max_stack(64)]).await;
+
+        assert_eq!(smith.craft_item_at("p1", "Smelt Iron", &basic, &mut context()).await.unwrap(), ["Iron Ingot"]);
+        assert_eq!(smith.craft_item_at("p1", "Smelt Iron", &advanced, &mut context()).await.unwrap(), ["Iron Ingot", "Slag"]);
+        let inventory = smith.get_player_inventory("p1").await.unwrap();
+        assert_eq!((inventory.count_item("Iron Ingot"), inventory.count_item("Slag")), (2, 1));
+    }
+
+    #[tokio::test]
+    async fn purging_removes_only_empty_containers() {
+        let smith = smith_with(RecipeSmithConfig {
+            emit_container_purge_events: true,
+            ..RecipeSmithConfig::default()
+        }, Vec::new()).await;
+        let empty = smith.create_owned_storage_container("p1", 4).await;
+        smith.create_storage_container(4).await;
+        let mut full = smith.create_storage_container(4).await;
+        full.inventory.add_item(1, Item::new("Gem"));
+        smith.update_storage_container(full.clone()).await;
+        smith.start_recording_events();
+
+        assert_eq!(smith.purge_empty_containers().await, 2);
+        assert!(smith.get_storage_container(&empty.uuid).await.is_none());
+        assert!(smith.get_storage_container(&full.uuid).await.is_some());
+        assert_eq!(event_types(&smith.stop_recording_events()), ["storage_container_purged", "storage_container_purged"]);
+        assert_eq!(smith.purge_empty_containers().await, 0);
+    }
+
+    #[tokio::test]
+    async fn low_quality_items_do_not_satisfy_a_fine_requirement() {
+        let mut blade = recipe("Fine Blade", &[("Iron Ingot", 2)], "Fine Blade");
+        blade.ingredients[0].min_quality = Some("fine".to_string());
+        let smith = RecipeSmith::with_recipes(vec![blade]);
+        join(&smith, "p1", vec![
+            Item::with_quantity("Iron Ingot", 3).max_stack(64).tag("quality", "common"),
+            Item::with_quantity("Iron Ingot", 1).max_stack(64).tag("quality", "superior"),
+        ]).await;
+
+        assert!(smith.craft_item_batch("p1", "Fine Blade", 1, &mut context()).await.is_none());
+        smith.add_item_to_player_inventory("p1", Item::with_quantity("Iron Ingot", 1).max_stack(64).tag("quality", "fine")).await.unwrap();
+        smith.craft_item_batch("p1", "Fine Blade", 1, &mut context()).await.unwrap();
+
+        // Only the qualifying ingots were used
+        let inventory = smith.get_player_inventory("p1").await.unwrap();
+        let left: Vec<(Option<u32>, u32)> = inventory.slots.values().flatten()
+            .filter(|item| item.name == "Iron Ingot")
+            .map(|item| (item.quality(), item.quantity))
+            .collect();
+        assert_eq!(left, [(Some(1), 3)]);
+    }
+
+    #[test]
+    fn items_can_be_built_with_defaults() {
+        let gem = Item::new("Gem");
+        assert_eq!((gem.quantity, gem.max_stack, gem.model.clone()), (1, 1, None));
+        assert!(gem.meta_tags.is_empty());
+
+        let potion = Item::with_quantity("Potion", 3)
+            .model("potion.glb")
+            .tag("effect", "healing")
+            .tag("rarity", "rare")
+            .max_stack(10);
+        assert_eq!(potion.name, "Potion");
+        assert_eq!((potion.quantity, potion.max_stack), (3, 10));
+        assert_eq!(potion.model.as_deref(), Some("potion.glb"));
+        assert_eq!(potion.meta_tags["effect"], "healing");
+        assert_eq!(potion.rarity(), Some(2));
+    }
+
+    #[tokio::test]
+    async fn craft_counts_are_kept_apart_from_the_recipe() {
+        let smith = RecipeSmith::with_recipes(vec![recipe("Plank", &[("Log", 1)], "Plank")]);
+        join(&smith, "p1", vec![Item::with_quantity("Log", 3).max_stack(64)]).await;
+
+        for _ in 0..3 {
+            smith.craft_item_batch("p1", "Plank", 1, &mut context()).await.unwrap();
+        }
+        let recipe_book = smith.recipe_book.read().await;
+        assert_eq!(recipe_book.get_recipe_craft_count("Plank"), 3);
+        assert_eq!(recipe_book.get_recipe("Plank").unwrap().cook_count, 0);
+        assert!(!recipe_book.is_mastered("Plank"));
+    }
+
+    #[test]
+    fn mastery_counts_the_starting_cook_count_and_crafts() {
+        let mut plank = recipe("Plank", &[("Log", 1)], "Plank");
+        plank.cook_count = MASTERY_CRAFT_COUNT - 2;
+        let mut book = RecipeBook::new();
+        book.add_recipe(plank);
+
+        book.record_craft("Plank", 1);
+        assert!(!book.is_mastered("Plank"));
+        book.record_craft("Plank", 1);
+        assert!(book.is_mastered("Plank"));
+        assert_eq!(book.get_recipe("Plank").unwrap().cook_count, MASTERY_CRAFT_COUNT - 2);
+    }
+
+    #[tokio::test]
+    async fn transferring_between_players_moves_and_stacks() {
+        let smith = RecipeSmith::with_recipes(Vec::new());
+        join(&smith, "p1", vec![Item::with_quantity("Coin", 30).max_stack(64)]).await;
+        join(&smith, "p2", vec![Item::new("Gem"), Item::with_quantity("Coin", 10).max_stack(64)]).await;
+        smith.start_recording_events();
+
+        smith.transfer_between_players("p1", "p2", 0, 12, &mut context()).await.unwrap();
+        assert_eq!(smith.get_player_inventory("p1").await.unwrap().count_item("Coin"), 18);
+        assert_eq!(smith.find_item_slots("p2", "Coin").await, Some(vec![1]));
+        assert_eq!(smith.get_player_inventory("p2").await.unwrap().count_item("Coin"), 22);
+        let changed: Vec<Option<String>> = smith.stop_recording_events().into_iter()
+            .filter(|event| event.event_type == "inventory_changed")
+            .map(|event| event.player_id)
+            .collect();
+        assert_eq!(changed, [Some("p1".to_string()), Some("p2".to_string())]);
+    }
+
+    #[tokio::test]
+    async fn a_full_destination_leaves_both_players_unchanged() {
+        let smith = RecipeSmith::with_recipes(Vec::new());
+        join(&smith, "p1", vec![Item::new("Sword")]).await;
+        smith.create_player_inventory("p2", 1).await;
+        smith.add_item_to_player_inventory("p2", Item::new("Gem")).await.unwrap();
+        let (p1, p2) = (smith.inventory_checksum("p1").await, smith.inventory_checksum("p2").await);
+
+        assert!(smith.transfer_between_players("p1", "p2", 0, 1, &mut context()).await.is_err());
+        assert_eq!(smith.inventory_checksum("p1").await, p1);
+        assert_eq!(smith.inventory_checksum("p2").await, p2);
+        assert!(smith.transfer_between_players("p1", "p2", 5, 1, &mut context()).await.is_err());
+    }
+
+    #[test]
+    fn canonical_tags_ignore_key_order() {
+        let a: HashMap<String, serde_json::Value> = serde_json::from_str(r#"{"a": 1, "b": {"x": [1, 2], "y": 2}}"#).unwrap();
+        let b: HashMap<String, serde_json::Value> = serde_json::from_str(r#"{"b": {"y": 2.0, "x": [1, 2]}, "a": 1}"#).unwrap();
+        assert_eq!(canonical_tags(&a), canonical_tags(&b));
+
+        let reordered: HashMap<String, serde_json::Value> = serde_json::from_str(r#"{"a": 1, "b": {"x": [2, 1], "y": 2}}"#).unwrap();
+        let stringly: HashMap<String, serde_json::Value> = serde_json::from_str(r#"{"a": "1", "b": {"x": [1, 2], "y": 2}}"#).unwrap();
+        assert_ne!(canonical_tags(&a), canonical_tags(&reordered));
+        assert_ne!(canonical_tags(&a), canonical_tags(&stringly));
+    }
+
+    #[tokio::test]
+    async fn craft_events_carry_the_recipe_sound_and_animation() {
+        let mut plank = recipe("Plank", &[("Log", 1)], "Plank");
+        plank.sound = Some("saw.ogg".to_string());
+        plank.animation = Some("sawing".to_string());
+        let smith = RecipeSmith::with_recipes(vec![plank]);
+        join(&smith, "p1", vec![Item::with_quantity("Log", 1).max_stack(64)]).await;
+        smith.start_recording_events();
+
+        smith.craft_item("p1", "Plank", &mut context()).await.unwrap();
+        let events = smith.stop_recording_events();
+        let started = events.iter().find_map(|event| event.data.downcast_ref::<CraftStartedEvent>()).unwrap();
+        assert_eq!(started.sound.as_deref(), Some("saw.ogg"));
+        assert_eq!(started.animation.as_deref(), Some("sawing"));
+        let crafted = events.iter().find_map(|event| event.data.downcast_ref::<CraftedEvent>()).unwrap();
+        assert_eq!(crafted.sound.as_deref(), Some("saw.ogg"));
+        assert_eq!(crafted.animation.as_deref(), Some("sawing"));
+    }
+
+    #[tokio::test]
+    async fn craft_started_comes_before_item_crafted() {
+        let smith = RecipeSmith::with_recipes(vec![recipe("Plank", &[("Log", 1)], "Plank")]);
+        join(&smith, "p1", vec![Item::with_quantity("Log", 1).max_stack(64)]).await;
+        smith.start_recording_events();
+
+        smith.craft_item("p1", "Plank", &mut context()).await.unwrap();
+        // Without logs left the craft never starts
+        assert!(smith.craft_item("p1", "Plank", &mut context()).await.is_none());
+
+        let events = smith.stop_recording_events();
+        let types: Vec<&str> = event_types(&events).into_iter()
+            .filter(|event_type| ["craft_started", "item_crafted", "crafting_failed"].contains(event_type))
+            .collect();
+        assert_eq!(types, ["craft_started", "item_crafted", "crafting_failed"]);
+        let started = events.iter().find_map(|event| event.data.downcast_ref::<CraftStartedEvent>()).unwrap();
+        assert_eq!(started.player_id, "p1");
+        assert_eq!(started.recipe_name, "Plank");
+        assert_eq!(started.total_cook_time, 0.0);
+    }
+
+    #[test]
+    fn compact_form_of_a_sparse_inventory_is_smaller_and_round_trips() {
+        let mut inventory = PlayerInventory::with_hotbar(100, 10);
+        inventory.add_item(3, Item::with_quantity("Flour", 12).max_stack(64));
+        inventory.add_item(71, Item::new("Sword"));
+
+        let verbose = serde_json::to_string(&inventory).unwrap();
+        let compact = serde_json::to_string(&inventory.to_compact()).unwrap();
+        assert!(compact.len() < verbose.len());
+
+        let restored = PlayerInventory::from(serde_json::from_str::<CompactInventory>(&compact).unwrap());
+        assert_eq!(restored.slots.len(), 100);
+        assert_eq!(restored.hotbar_size, 10);
+        assert_eq!(restored.checksum(), inventory.checksum());
+        assert_eq!(restored.to_compact(), inventory.to_compact());
+    }
+
+    #[test]
+    fn search_ranks_name_matches_above_ingredient_matches() {
+        let mut book = RecipeBook::new();
+        book.add_recipe(recipe("Sword Rack", &[("Plank", 2)], "Sword Rack"));
+        book.add_recipe(recipe("Iron Sword", &[("Iron Ingot", 2)], "Iron Sword"));
+        book.add_recipe(recipe("Mounted Trophy", &[("Broken Sword", 1)], "Mounted Trophy"));
+        book.add_recipe(recipe("Sword", &[("Iron Ingot", 1)], "Sword"));
+
+        let names: Vec<String> = book.search_recipes("sword").into_iter().map(|recipe| recipe.name).collect();
+        assert_eq!(names, ["Sword", "Sword Rack", "Iron Sword", "Mounted Trophy"]);
+    }
+
+    #[tokio::test]
+    async fn learning_one_recipe_in_a_group_locks_the_others() {
+        let mut fire = recipe("Fire Mastery", &[], "Fire Tome");
+        fire.group = Some("specialization".to_string());
+        let mut ice = recipe("Ice Mastery", &[], "Ice Tome");
+        ice.group = Some("specialization".to_string());
+        let smith = RecipeSmith::with_recipes(vec![fire, ice, recipe("Bread", &[("Flour", 1)], "Bread")]);
+
+        assert!(smith.learn_recipe("p1", "Fire Mastery", &mut context()).await.unwrap());
+        assert!(smith.is_recipe_locked("p1", "Ice Mastery").await);
+        assert!(!smith.is_recipe_locked("p1", "Fire Mastery").await);
+        assert!(!smith.is_recipe_locked("p1", "Bread").await);
+        assert!(smith.learn_recipe("p1", "Ice Mastery", &mut context()).await.is_err());
+        assert!(!smith.has_learned_recipe("p1", "Ice Mastery").await);
+        // Other players still get to choose
+        assert!(!smith.is_recipe_locked("p2", "Ice Mastery").await);
+    }
+
+    #[tokio::test]
+    async fn max_craftable_counts_whole_crafts_without_crafting() {
+        let smith = RecipeSmith::with_recipes(vec![recipe("Bread", &[("Flour", 2)], "Bread")]);
+        join(&smith, "p1", vec![Item::with_quantity("Flour", 9).max_stack(64)]).await;
+
+        assert_eq!(smith.max_craftable("p1", "Bread").await, 4);
+        assert_eq!(smith.get_player_inventory("p1").await.unwrap().count_item("Flour"), 9);
+        assert_eq!(smith.max_craftable("p1", "Cake").await, 0);
+        assert_eq!(smith.max_craftable("p2", "Bread").await, 0);
+
+        assert_eq!(smith.craft_all("p1", "Bread", &mut context()).await.unwrap().len(), 4);
+        assert_eq!(smith.get_player_inventory("p1").await.unwrap().count_item("Flour"), 1);
+    }
+
+    #[tokio::test]
+    async fn non_stackable_outputs_take_separate_slots() {
+        let smith = RecipeSmith::with_recipes(vec![recipe("Excalibur", &[("Starmetal", 1)], "Excalibur")]);
+        smith.recipe_book.write().await.items.register(ItemDefinition {
+            name: "Excalibur".to_string(),
+            model: None,
+            max_stack: 1,
+            meta_tags: HashMap::new(),
+        });
+        join(&smith, "p1", vec![Item::with_quantity("Starmetal", 2).max_stack(64)]).await;
+
+        smith.craft_item("p1", "Excalibur", &mut context()).await.unwrap();
+        smith.craft_item("p1", "Excalibur", &mut context()).await.unwrap();
+        let inventory = smith.get_player_inventory("p1").await.unwrap();
+        let slots = inventory.slots_containing("Excalibur");
+        assert_eq!(slots.len(), 2);
+        assert!(slots.iter().all(|slot| inventory.get_item(*slot).unwrap().quantity == 1));
+    }
+
+    #[test]
+    fn snapshots_ignore_later_changes_to_the_book() {
+        let mut book = RecipeBook::new();
+        book.add_recipe(recipe("Bread", &[("Flour", 2)], "Bread"));
+        let snapshot = book.snapshot();
+
+        book.remove_recipe("Bread");
+        book.add_recipe(recipe("Cake", &[("Flour", 3)], "Cake"));
+
+        assert!(snapshot.get_recipe("Bread").is_some());
+        assert!(snapshot.get_recipe("Cake").is_none());
+        assert_eq!(recipe_names(snapshot.search_recipes("flour")), ["Bread"]);
+        let fresh = book.snapshot();
+        assert!(fresh.get_recipe("Bread").is_none());
+        assert!(fresh.get_recipe("Cake").is_some());
+    }
+
+    #[tokio::test]
+    async fn zero_cook_time_crafts_finish_without_queueing() {
+        let mut plank = recipe("Plank", &[("Log", 1)], "Plank");
+        plank.cook_time_variance = 0.5;
+        let smith = RecipeSmith::with_recipes(vec![plank.clone()]);
+        join(&smith, "p1", vec![Item::with_quantity("Log", 1).max_stack(64)]).await;
+
+        assert_eq!(smith.effective_cook_time(&plank, 1, false), 0.0);
+        smith.queue_craft("p1", "Plank", &mut context()).await.unwrap();
+        assert!(smith.peek_queue("p1").await.is_empty());
+        let inventory = smith.get_player_inventory("p1").await.unwrap();
+        assert_eq!(inventory.count_item("Plank"), 1);
+        assert_eq!(inventory.count_item("Log"), 0);
+    }
+
+    #[test]
+    fn cheapest_recipe_is_picked_by_ingredient_cost() {
+        let mut book = RecipeBook::new();
+        book.add_recipe(recipe("Gold Lamp", &[("Gold Ingot", 1), ("Oil", 1)], "Lamp"));
+        book.add_recipe(recipe("Iron Lamp", &[("Iron Ingot", 2), ("Oil", 1)], "Lamp"));
+        book.add_recipe(recipe("Candle", &[("Wax", 1)], "Candle"));
+        let cost = |ingredient: &Ingredient| {
+            let each = match ingredient.name.as_str() {
+                "Gold Ingot" => 10.0,
+                "Iron Ingot" => 2.0,
+                _ => 1.0,
+            };
+            each * ingredient.quantity as f64
+        };
+
+        assert_eq!(book.cheapest_recipe_for("Lamp", cost).unwrap().name, "Iron Lamp");
+        assert!(book.cheapest_recipe_for("Torch", cost).is_none());
+    }
+
+    #[tokio::test]
+    async fn exported_inventories_import_back_unchanged() {
+        let smith = RecipeSmith::with_recipes(Vec::new());
+        join(&smith, "p1", vec![Item::with_quantity("Flour", 12).max_stack(64), Item::new("Sword")]).await;
+        join(&smith, "p2", Vec::new()).await;
+
+        let exported = smith.export_player_inventory("p1").await.unwrap();
+        smith.import_player_inventory("p2", &exported, &mut context()).await.unwrap();
+        assert_eq!(smith.inventory_checksum("p2").await, smith.inventory_checksum("p1").await);
+        assert!(smith.export_player_inventory("nobody").await.is_none());
+    }
+
+    #[tokio::test]
+    async fn malformed_or_oversized_imports_are_rejected() {
+        let smith = RecipeSmith::with_recipes(Vec::new());
+        join(&smith, "p1", vec![Item::new("Sword")]).await;
+        let before = smith.inventory_checksum("p1").await;
+        let encode = |json: &str| base64::engine::general_purpose::STANDARD.encode(json);
+
+        assert!(smith.import_player_inventory("p1", "not base64!", &mut context()).await.is_err());
+        assert!(smith.import_player_inventory("p1", &encode("{\"slots\": 3"), &mut context()).await.is_err());
+        let outside = encode(r#"{"capacity": 2, "items": {"5": {"name": "Sword", "quantity": 1}}}"#);
+        assert!(smith.import_player_inventory("p1", &outside, &mut context()).await.is_err());
+        let oversized = encode(r#"{"capacity": 4000000000, "items": {}}"#);
+        assert!(smith.import_player_inventory("p1", &oversized, &mut context()).await.is_err());
+        assert_eq!(smith.inventory_checksum("p1").await, before);
+    }
+
+    #[tokio::test]
+    async fn cooperative_crafts_pool_ingredients_from_every_participant() {
+        let smith = RecipeSmith::with_recipes(vec![recipe("Raft", &[("Log", 4)], "Raft")]);
+        join(&smith, "p1", vec![Item::with_quantity("Log", 3).max_stack(64)]).await;
+        join(&smith, "p2", vec![Item::with_quantity("Log", 2).max_stack(64)]).await;
+        let participants = ["p1".to_string(), "p2".to_string()];
+
+        let crafted = smith.craft_cooperative(&participants, "Raft", "p2", &mut context()).await.unwrap();
+        assert_eq!(crafted, ["Raft"]);
+        let p1 = smith.get_player_inventory("p1").await.unwrap();
+        let p2 = smith.get_player_inventory("p2").await.unwrap();
+        assert_eq!(p1.count_item("Log") + p2.count_item("Log"), 1);
+        assert_eq!(p1.count_item("Raft"), 0);
+        assert_eq!(p2.count_item("Raft"), 1);
+
+        // One log left between them isn't enough for another raft
+        assert!(smith.craft_cooperative(&participants, "Raft", "p1", &mut context()).await.is_err());
+        assert_eq!(smith.get_player_inventory("p1").await.unwrap().count_item("Log") + smith.get_player_inventory("p2").await.unwrap().count_item("Log"), 1);
+    }
+
+    #[tokio::test]
+    async fn disabled_crafters_count_as_missing() {
+        let mut ingot = recipe("Iron Ingot", &[("Iron Ore", 1)], "Iron Ingot");
+        ingot.crafters = vec![crafter("Furnace")];
+        let smith = RecipeSmith::with_recipes(vec![ingot]);
+        join(&smith, "p1", vec![Item::with_quantity("Iron Ore", 2).max_stack(64)]).await;
+
+        smith.set_crafter_enabled(&crafter("Furnace"), false).await;
+        let result = smith.craft_item_at("p1", "Iron Ingot", &crafter("Furnace"), &mut context()).await;
+        assert_eq!(result, Err(CraftError::NoCrafterAvailable.to_string()));
+        assert!(smith.craft_item("p1", "Iron Ingot", &mut context()).await.is_none());
+        assert_eq!(smith.get_player_inventory("p1").await.unwrap().count_item("Iron Ore"), 2);
+
+        smith.set_crafter_enabled(&crafter("Furnace"), true).await;
+        assert!(smith.craft_item_at("p1", "Iron Ingot", &crafter("Furnace"), &mut context()).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn for_each_inventory_visits_every_player() {
+        let smith = RecipeSmith::with_recipes(Vec::new());
+        join(&smith, "p2", vec![Item::new("Sword")]).await;
+        join(&smith, "p1", Vec::new()).await;
+        join(&smith, "p3", vec![Item::with_quantity("Coin", 5).max_stack(64)]).await;
+
+        let mut visited = Vec::new();
+        let mut items = 0;
+        smith.for_each_inventory(|player_id, inventory| {
+            visited.push(player_id.to_string());
+            items += inventory.slots.values().flatten().count();
+        }).await;
+        assert_eq!(visited, ["p1", "p2", "p3"]);
+        assert_eq!(items, 2);
+    }
+
+    #[tokio::test]
+    async fn auto_save_only_writes_changed_inventories_once_the_interval_passes() {
+        let store = Arc::new(RecordingStore::default());
+        let clock = ManualClock::new();
+        let config = RecipeSmithConfig {
+            auto_save_interval: Some(std::time::Duration::from_secs(60)),
+            inventory_store: Some(store.clone()),
+            ..RecipeSmithConfig::default()
+        };
+        let smith = smith_with(config, Vec::new()).await.with_clock(clock.clone());
+        join(&smith, "p1", Vec::new()).await;
+        join(&smith, "p2", Vec::new()).await;
+
+        // The first tick only starts the interval
+        smith.on_game_tick(0.0).await;
+        clock.advance(std::time::Duration::from_secs(61));
+        smith.on_game_tick(0.0).await;
+        assert_eq!(store.saved(), ["p1", "p2"]);
+
+        smith.add_item_to_player_inventory("p1", Item::new("Sword")).await.unwrap();
+        clock.advance(std::time::Duration::from_secs(30));
+        smith.on_game_tick(0.0).await;
+        assert_eq!(store.saved().len(), 2);
+        clock.advance(std::time::Duration::from_secs(31));
+        smith.on_game_tick(0.0).await;
+        assert_eq!(store.saved(), ["p1", "p2", "p1"]);
+    }
+
+    #[tokio::test]
+    async fn required_level_blocks_low_level_players() {
+        let mut sword = recipe("Iron Sword", &[("Iron Ingot", 1)], "Iron Sword");
+        sword.required_level = Some(10);
+        let smith = RecipeSmith::with_recipes(vec![sword]);
+        join(&smith, "novice", vec![Item::with_quantity("Iron Ingot", 1).max_stack(64)]).await;
+        join(&smith, "veteran", vec![Item::with_quantity("Iron Ingot", 1).max_stack(64)]).await;
+        smith.set_player_level("novice", 9).await;
+        smith.set_player_level("veteran", 10).await;
+
+        let blocked = smith.craft_item_inner("novice", "Iron Sword", 1, None, None, &mut context()).await;
+        assert_eq!(blocked, Err(CraftError::LevelTooLow.to_string()));
+        assert_eq!(smith.get_player_inventory("novice").await.unwrap().count_item("Iron Ingot"), 1);
+        assert_eq!(smith.max_craftable("novice", "Iron Sword").await, 0);
+        assert_eq!(smith.craft_item("veteran", "Iron Sword", &mut context()).await.as_deref(), Some("Iron Sword"));
+    }
+
+    #[test]
+    fn duplicates_group_recipes_with_the_same_ingredients_and_output() {
+        let mut book = RecipeBook::new();
+        book.add_recipe(recipe("Plank", &[("Log", 1)], "Plank"));
+        let mut slow_plank = recipe("Slow Plank", &[("Log", 1)], "Plank");
+        slow_plank.base_cook_time = 5;
+        book.add_recipe(slow_plank);
+        book.add_recipe(recipe("Beam", &[("Log", 2)], "Beam"));
+        // The same two logs, just listed separately
+        book.add_recipe(recipe("Split Beam", &[("Log", 1), ("Log", 1)], "Beam"));
+        book.add_recipe(recipe("Post", &[("Log", 1)], "Post"));
+
+        assert_eq!(book.find_duplicates(), [vec!["Beam".to_string(), "Split Beam".to_string()], vec!["Plank".to_string(), "Slow Plank".to_string()]]);
+    }
+
+    #[tokio::test]
+    async fn inventory_log_records_operations_in_order() {
+        let config = RecipeSmithConfig { inventory_log_capacity: 10, ..RecipeSmithConfig::default() };
+        let smith = smith_with(config, vec![recipe("Plank", &[("Log", 1)], "Plank")]).await;
+        join(&smith, "p1", vec![Item::with_quantity("Log", 2).max_stack(64)]).await;
+        smith.move_item("p1", 0, 5, &mut context()).await.unwrap();
+        smith.craft_item("p1", "Plank", &mut context()).await.unwrap();
+        smith.remove_item_from_slot("p1", 0, &mut context()).await.unwrap();
+
+        let log = smith.get_inventory_log("p1").await;
+        let operations: Vec<InventoryOperation> = log.iter().map(|entry| entry.operation).collect();
+        assert_eq!(operations, [InventoryOperation::Add, InventoryOperation::Move, InventoryOperation::Craft, InventoryOperation::Remove]);
+        assert_eq!(log[2].changes, [("Log".to_string(), -1), ("Plank".to_string(), 1)]);
+        assert!(log.windows(2).all(|pair| pair[0].checksum_after == pair[1].checksum_before));
+        assert_eq!(Some(log[3].checksum_after), smith.inventory_checksum("p1").await);
+    }
+
+    #[tokio::test]
+    async fn inventory_log_keeps_only_the_newest_entries() {
+        let config = RecipeSmithConfig { inventory_log_capacity: 2, ..RecipeSmithConfig::default() };
+        let smith = smith_with(config, Vec::new()).await;
+        join(&smith, "p1", vec![Item::new("Sword")]).await;
+        smith.move_item("p1", 0, 1, &mut context()).await.unwrap();
+        smith.remove_item_from_slot("p1", 1, &mut context()).await.unwrap();
+
+        let operations: Vec<InventoryOperation> = smith.get_inventory_log("p1").await.iter().map(|entry| entry.operation).collect();
+        assert_eq!(operations, [InventoryOperation::Move, InventoryOperation::Remove]);
+        assert!(RecipeSmith::with_recipes(Vec::new()).get_inventory_log("p1").await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn recorder_sees_the_exact_sequence_of_a_mastering_craft() {
+        let mut plank = recipe("Plank", &[("Log", 1)], "Plank");
+        plank.cook_count = MASTERY_CRAFT_COUNT - 1;
+        let smith = RecipeSmith::with_recipes(vec![plank]);
+        join(&smith, "p1", vec![Item::with_quantity("Log", 1).max_stack(64)]).await;
+        smith.start_recording_events();
+
+        smith.craft_item("p1", "Plank", &mut context()).await.unwrap();
+        let events = smith.stop_recording_events();
+        assert_eq!(event_types(&events), ["craft_started", "item_crafted", "inventory_changed", "recipe_mastered"]);
+        assert!(events.iter().all(|event| event.player_id.as_deref() == Some("p1")));
+        let mastered = events[3].data.downcast_ref::<(String, String)>().unwrap();
+        assert_eq!(mastered, &("p1".to_string(), "Plank".to_string()));
+        // Nothing is recorded once recording stops
+        assert!(smith.stop_recording_events().is_empty());
+    }
+
+    #[tokio::test]
+    async fn output_quality_rises_with_the_craft_count() {
+        let config = RecipeSmithConfig {
+            quality_progression: QualityProgression::new(vec![(2, "fine".to_string()), (3, "superior".to_string())]),
+            ..RecipeSmithConfig::default()
+        };
+        let smith = smith_with(config, vec![recipe("Plank", &[("Log", 1)], "Plank")]).await;
+        // A log is left over so the planks fill slots 1 to 3 in crafting order
+        join(&smith, "p1", vec![Item::with_quantity("Log", 4).max_stack(64)]).await;
+
+        for _ in 0..3 {
+            smith.craft_item("p1", "Plank", &mut context()).await.unwrap();
+        }
+        let inventory = smith.get_player_inventory("p1").await.unwrap();
+        let mut slots = inventory.slots_containing("Plank");
+        slots.sort_unstable();
+        let qualities: Vec<Option<u32>> = slots.iter().map(|slot| inventory.get_item(*slot).unwrap().quality()).collect();
+        assert_eq!(qualities, [None, quality_rank("fine"), quality_rank("superior")]);
+    }
+
+    #[tokio::test]
+    async fn crafting_before_initialize_is_rejected() {
+        let smith = RecipeSmith::new();
+        smith.add_new_recipe(recipe("Plank", &[("Log", 1)], "Plank")).await.unwrap();
+        join(&smith, "p1", vec![Item::with_quantity("Log", 1).max_stack(64)]).await;
+
+        let result = smith.craft_item_inner("p1", "Plank", 1, None, None, &mut context()).await;
+        assert_eq!(result, Err(CraftError::NotInitialized.to_string()));
+        assert!(smith.queue_craft("p1", "Plank", &mut context()).await.is_err());
+        assert!(smith.get_all_recipes().await.is_empty());
+        assert!(smith.search_recipes("Plank").await.is_empty());
+        assert!(smith.get_recipes_by_crafter("Workbench").await.is_empty());
+        assert_eq!(smith.max_craftable("p1", "Plank").await, 0);
+        assert_eq!(smith.learn_recipe("p1", "Plank", &mut context()).await, Err(CraftError::NotInitialized.to_string()));
+        assert!(smith.get_player_recipe_book("p1").await.is_empty());
+        assert_eq!(smith.get_player_inventory("p1").await.unwrap().count_item("Log"), 1);
+
+        let ready = RecipeSmith::with_recipes(vec![recipe("Plank", &[("Log", 1)], "Plank")]);
+        assert!(ready.is_initialized());
+        assert_eq!(ready.get_all_recipes().await.len(), 1);
+        assert_eq!(ready.search_recipes("Plank").await.len(), 1);
+        assert_eq!(ready.learn_recipe("p1", "Plank", &mut context()).await, Ok(true));
+    }
+
+    #[tokio::test]
+    async fn one_handler_is_shared_by_every_event_type() {
+        let smith = RecipeSmith::with_recipes(Vec::new());
+        let mut context = context();
+
+        smith.register_custom_events(CUSTOM_EVENT_TYPES, &mut context).await;
+        smith.register_custom_event("extra", &mut context).await;
+        let handler = smith.event_handler();
+        // The context holds the same handler once per registration, and nothing else does
+        assert_eq!(Arc::strong_count(&handler), CUSTOM_EVENT_TYPES.len() + 2);
+        assert!(Arc::ptr_eq(&handler, &smith.clone().event_handler()));
+
+        // Once the context lets go, a later registration gets a fresh handler
+        drop(context);
+        let weak = Arc::downgrade(&handler);
+        drop(handler);
+        assert!(weak.upgrade().is_none());
+    }
+
+    #[tokio::test]
+    async fn mastered_recipes_are_listed_once_mastered() {
+        let mut plank = recipe("Plank", &[("Log", 1)], "Plank");
+        plank.cook_count = MASTERY_CRAFT_COUNT - 1;
+        let smith = RecipeSmith::with_recipes(vec![plank, recipe("Beam", &[("Log", 2)], "Beam")]);
+        join(&smith, "p1", vec![Item::with_quantity("Log", 1).max_stack(64)]).await;
+        assert!(smith.get_mastered_recipes("p1").await.is_empty());
+
+        smith.craft_item("p1", "Plank", &mut context()).await.unwrap();
+        assert_eq!(smith.get_mastered_recipes("p1").await, ["Plank"]);
+    }
+
+    #[tokio::test]
+    async fn items_that_do_not_fit_are_dropped_in_drop_mode() {
+        let config = RecipeSmithConfig { overflow_policy: OverflowPolicy::Drop, ..RecipeSmithConfig::default() };
+        let smith = smith_with(config, vec![recipe("Plank", &[("Log", 1)], "Plank")]).await;
+        smith.create_player_inventory("p1", 1).await;
+        smith.add_item_to_player_inventory("p1", Item::with_quantity("Log", 2).max_stack(64)).await.unwrap();
+        smith.start_recording_events();
+
+        smith.craft_item("p1", "Plank", &mut context()).await.unwrap();
+        smith.add_item_to_player_inventory("p1", Item::new("Sword")).await.unwrap();
+        let dropped: Vec<(String, String)> = smith.stop_recording_events().iter()
+            .filter(|event| event.event_type == "item_dropped")
+            .filter_map(|event| event.data.downcast_ref::<ItemDroppedEvent>())
+            .map(|dropped| (dropped.player_id.clone(), dropped.item.name.clone()))
+            .collect();
+        assert_eq!(dropped, [("p1".to_string(), "Plank".to_string()), ("p1".to_string(), "Sword".to_string())]);
+        assert_eq!(smith.get_player_inventory("p1").await.unwrap().count_item("Log"), 1);
+    }
+
+    #[tokio::test]
+    async fn gives_that_do_not_fit_fail_in_error_mode() {
+        let smith = RecipeSmith::with_recipes(Vec::new());
+        smith.create_player_inventory("p1", 1).await;
+        smith.add_item_to_player_inventory("p1", Item::new("Sword")).await.unwrap();
+        smith.start_recording_events();
+
+        assert!(smith.add_item_to_player_inventory("p1", Item::new("Shield")).await.is_err());
+        assert!(smith.stop_recording_events().is_empty());
+    }
+
+    #[tokio::test]
+    async fn crafts_that_do_not_fit_consume_nothing_in_error_mode() {
+        let smith = RecipeSmith::with_recipes(vec![recipe("Plank", &[("Log", 1)], "Plank")]);
+        smith.create_player_inventory("p1", 1).await;
+        smith.add_item_to_player_inventory("p1", Item::with_quantity("Log", 2).max_stack(64)).await.unwrap();
+        smith.start_recording_events();
+
+        let result = smith.craft_item_inner("p1", "Plank", 1, None, None, &mut context()).await;
+        assert_eq!(result, Err("Not enough inventory space for the outputs".to_string()));
+        let inventory = smith.get_player_inventory("p1").await.unwrap();
+        assert_eq!(inventory.count_item("Log"), 2);
+        assert_eq!(inventory.count_item("Plank"), 0);
+        assert_eq!(event_types(&smith.stop_recording_events()), ["crafting_failed"]);
+        assert_eq!(smith.get_recipe_craft_count("Plank").await, 0);
+
+        // Using up the last log frees its slot for the plank
+        smith.remove_item_from_slot("p1", 0, &mut context()).await.unwrap();
+        smith.add_item_to_player_inventory("p1", Item::with_quantity("Log", 1).max_stack(64)).await.unwrap();
+        assert_eq!(smith.craft_item("p1", "Plank", &mut context()).await.as_deref(), Some("Plank"));
+    }
+
+    #[tokio::test]
+    async fn yield_buffs_apply_until_they_expire() {
+        let clock = ManualClock::new();
+        let smith = RecipeSmith::with_recipes(vec![recipe("Plank", &[("Log", 1)], "Plank")]).with_clock(clock.clone());
+        join(&smith, "p1", vec![Item::with_quantity("Log", 2).max_stack(64)]).await;
+        smith.apply_buff("p1", "Lumberjack", BuffEffect::Yield(2), std::time::Duration::from_secs(60)).await;
+
+        assert_eq!(smith.craft_item_batch("p1", "Plank", 1, &mut context()).await.unwrap(), ["Plank", "Plank"]);
+
+        clock.advance(std::time::Duration::from_secs(61));
+        smith.start_recording_events();
+        smith.on_game_tick(0.0).await;
+        let events = smith.stop_recording_events();
+        assert_eq!(event_types(&events), ["buff_expired"]);
+        assert!(smith.get_active_buffs("p1").await.is_empty());
+        assert_eq!(smith.craft_item_batch("p1", "Plank", 1, &mut context()).await.unwrap(), ["Plank"]);
+        assert_eq!(smith.get_player_inventory("p1").await.unwrap().count_item("Plank"), 3);
+    }
+
+    #[tokio::test]
+    async fn buff_expiry_reaches_the_plugin_bus_with_the_next_dispatch() {
+        let clock = ManualClock::new();
+        let smith = RecipeSmith::with_recipes(Vec::new()).with_clock(clock.clone());
+        smith.apply_buff("p1", "Lumberjack", BuffEffect::Yield(2), std::time::Duration::from_secs(60)).await;
+        clock.advance(std::time::Duration::from_secs(60));
+
+        smith.on_game_tick(0.0).await;
+        let deferred = event_types(&smith.deferred_events.read().await).join(",");
+        assert_eq!(deferred, "buff_expired");
+        assert!(CUSTOM_EVENT_TYPES.contains(&"buff_expired"));
+
+        smith.dispatch_completed_crafts(&mut context()).await;
+        assert!(smith.deferred_events.read().await.is_empty());
+    }
+
+    #[test]
+    fn registry_validation_lists_undefined_items() {
+        let mut book = RecipeBook::new();
+        for name in ["Log", "Plank"] {
+            book.items.register(ItemDefinition { name: name.to_string(), model: None, max_stack: 64, meta_tags: HashMap::new() });
+        }
+        book.add_recipe(recipe("Plank", &[("Log", 1)], "Plank"));
+        book.add_recipe(recipe("Chair", &[("Plank", 4), ("Nail", 2)], "Chair"));
+
+        let report = book.validate_against_registry(&book.items);
+        assert_eq!(report.unresolved, [
+            UnresolvedReference { recipe: "Chair".to_string(), item: "Nail".to_string(), kind: ReferenceKind::Ingredient },
+            UnresolvedReference { recipe: "Chair".to_string(), item: "Chair".to_string(), kind: ReferenceKind::Output },
+        ]);
+        assert_eq!(report.unresolved[0].to_string(), "Recipe Chair needs undefined item Nail");
+
+        book.remove_recipe("Chair");
+        assert!(book.validate_against_registry(&book.items).is_empty());
+    }
+
+    #[tokio::test]
+    async fn queued_crafts_resume_after_a_restart() {
+        let store: Arc<dyn InventoryStore> = Arc::new(JsonFileStore::new(temp_dir("queues")));
+        let config = || RecipeSmithConfig { inventory_store: Some(Arc::clone(&store)), ..RecipeSmithConfig::default() };
+        let mut plank = recipe("Plank", &[("Log", 1)], "Plank");
+        plank.base_cook_time = 10;
+
+        let before = smith_with(config(), vec![plank.clone()]).await.with_clock(ManualClock::new());
+        join(&before, "p1", vec![Item::with_quantity("Log", 1).max_stack(64)]).await;
+        before.queue_craft("p1", "Plank", &mut context()).await.unwrap();
+        before.process_craft_queue(4.0).await;
+        before.save_dirty_inventories().await;
+
+        // Down for 3 of the 6 seconds left
+        let clock = ManualClock::new();
+        clock.advance(std::time::Duration::from_secs(3));
+        let mut after = RecipeSmith::with_config(RecipeSmithConfig { auto_load_recipes: false, ..config() }).with_clock(clock);
+        after.add_new_recipe(plank).await.unwrap();
+        after.create_player_inventory("p1", 20).await;
+        after.initialize_recipe_smith(&mut context()).await;
+
+        let queued = after.peek_queue("p1").await;
+        assert_eq!(queued.len(), 1);
+        assert!((queued[0].remaining_time - 3.0).abs() < 1e-9);
+        assert_eq!(after.get_player_inventory("p1").await.unwrap().count_item("Log"), 0);
+
+        after.process_craft_queue(3.0).await;
+        after.dispatch_completed_crafts(&mut context()).await;
+        assert!(after.peek_queue("p1").await.is_empty());
+        assert_eq!(after.get_player_inventory("p1").await.unwrap().count_item("Plank"), 1);
+    }
+
+    #[tokio::test]
+    async fn crafts_due_during_downtime_complete_on_restart() {
+        let store: Arc<dyn InventoryStore> = Arc::new(JsonFileStore::new(temp_dir("downtime")));
+        let config = || RecipeSmithConfig { inventory_store: Some(Arc::clone(&store)), ..RecipeSmithConfig::default() };
+        let mut plank = recipe("Plank", &[("Log", 1)], "Plank");
+        plank.base_cook_time = 10;
+
+        let before = smith_with(config(), vec![plank.clone()]).await.with_clock(ManualClock::new());
+        join(&before, "p1", vec![Item::with_quantity("Log", 1).max_stack(64)]).await;
+        before.queue_craft("p1", "Plank", &mut context()).await.unwrap();
+        before.save_dirty_inventories().await;
+
+        let clock = ManualClock::new();
+        clock.advance(std::time::Duration::from_secs(60));
+        let mut after = RecipeSmith::with_config(RecipeSmithConfig { auto_load_recipes: false, ..config() }).with_clock(clock);
+        after.add_new_recipe(plank).await.unwrap();
+        after.create_player_inventory("p1", 20).await;
+        after.initialize_recipe_smith(&mut context()).await;
+
+        assert!(after.peek_queue("p1").await.is_empty());
+        assert_eq!(after.get_player_inventory("p1").await.unwrap().count_item("Plank"), 1);
+    }
+
+    #[tokio::test]
+    async fn global_cap_is_shared_by_every_player() {
+        let mut coin = recipe("Ancient Coin", &[("Gold", 1)], "Ancient Coin");
+        coin.max_global = Some(3);
+        let smith = RecipeSmith::with_recipes(vec![coin]);
+        join(&smith, "p1", vec![Item::with_quantity("Gold", 5).max_stack(64)]).await;
+        join(&smith, "p2", vec![Item::with_quantity("Gold", 5).max_stack(64)]).await;
+
+        smith.craft_item_batch("p1", "Ancient Coin", 2, &mut context()).await.unwrap();
+        assert_eq!(smith.max_craftable("p2", "Ancient Coin").await, 1);
+        smith.craft_item("p2", "Ancient Coin", &mut context()).await.unwrap();
+
+        let rejected = smith.craft_item_inner("p2", "Ancient Coin", 1, None, None, &mut context()).await;
+        assert_eq!(rejected, Err(CraftError::GlobalLimitReached.to_string()));
+        assert!(smith.queue_craft("p1", "Ancient Coin", &mut context()).await.is_err());
+        assert_eq!(smith.get_player_inventory("p2").await.unwrap().count_item("Gold"), 4);
+    }
+
+    #[tokio::test]
+    async fn global_craft_counts_survive_a_restart() {
+        let store: Arc<dyn InventoryStore> = Arc::new(JsonFileStore::new(temp_dir("global")));
+        let config = || RecipeSmithConfig { inventory_store: Some(Arc::clone(&store)), ..RecipeSmithConfig::default() };
+        let mut coin = recipe("Ancient Coin", &[("Gold", 1)], "Ancient Coin");
+        coin.max_global = Some(1);
+
+        let before = smith_with(config(), vec![coin.clone()]).await;
+        join(&before, "p1", vec![Item::with_quantity("Gold", 2).max_stack(64)]).await;
+        before.craft_item("p1", "Ancient Coin", &mut context()).await.unwrap();
+        before.save_dirty_inventories().await;
+
+        let mut after = RecipeSmith::with_config(RecipeSmithConfig { auto_load_recipes: false, ..config() });
+        after.add_new_recipe(coin).await.unwrap();
+        after.create_player_inventory("p1", 20).await;
+        after.initialize_recipe_smith(&mut context()).await;
+        assert!(after.craft_item("p1", "Ancient Coin", &mut context()).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn resetting_the_cook_count_clears_mastery() {
+        let mut plank = recipe("Plank", &[("Log", 1)], "Plank");
+        plank.cook_count = MASTERY_CRAFT_COUNT - 1;
+        let smith = RecipeSmith::with_recipes(vec![plank]);
+        join(&smith, "p1", vec![Item::with_quantity("Log", 1).max_stack(64)]).await;
+        smith.craft_item("p1", "Plank", &mut context()).await.unwrap();
+        assert!(smith.recipe_book.read().await.is_mastered("Plank"));
+        smith.start_recording_events();
+
+        smith.reset_cook_count("Plank").await.unwrap();
+        assert!(!smith.recipe_book.read().await.is_mastered("Plank"));
+        // Back to where the definition started it
+        assert_eq!(smith.get_recipe_craft_count("Plank").await, MASTERY_CRAFT_COUNT - 1);
+        assert_eq!(smith.recipe_book.read().await.get_recipe("Plank").unwrap().cook_count, MASTERY_CRAFT_COUNT - 1);
+        // Nothing to unmaster the second time
+        smith.reset_cook_count("Plank").await.unwrap();
+        let events = smith.stop_recording_events();
+        assert_eq!(event_types(&events), ["recipe_unmastered"]);
+        assert_eq!(events[0].data.downcast_ref::<String>().map(String::as_str), Some("Plank"));
+        assert_eq!(smith.reset_cook_count("Cake").await, Err(CraftError::RecipeNotFound));
+
+        assert_eq!(event_types(&smith.deferred_events.read().await), ["recipe_unmastered"]);
+        assert!(CUSTOM_EVENT_TYPES.contains(&"recipe_unmastered"));
+        smith.dispatch_completed_crafts(&mut context()).await;
+        assert!(smith.deferred_events.read().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn water_is_used_up_but_the_bucket_and_tools_come_back() {
+        let mut clay = recipe("Clay", &[("Water Bucket", 1), ("Dirt", 1), ("Shovel", 1)], "Clay");
+        clay.ingredients[0].transformed_to = Some("Bucket".to_string());
+        clay.ingredients[2].consumed = false;
+        let smith = RecipeSmith::with_recipes(vec![clay]);
+        join(&smith, "p1", vec![Item::new("Water Bucket"), Item::with_quantity("Dirt", 2).max_stack(64), Item::new("Shovel")]).await;
+
+        smith.craft_item("p1", "Clay", &mut context()).await.unwrap();
+        let inventory = smith.get_player_inventory("p1").await.unwrap();
+        assert_eq!(inventory.count_item("Water Bucket"), 0);
+        assert_eq!(inventory.count_item("Bucket"), 1);
+        assert_eq!(inventory.count_item("Dirt"), 1);
+        assert_eq!(inventory.count_item("Shovel"), 1);
+        assert_eq!(inventory.count_item("Clay"), 1);
+        // The empty bucket can't be used again
+        assert!(smith.craft_item("p1", "Clay", &mut context()).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn case_insensitive_lookup_crafts_whatever_the_case() {
+        let config = RecipeSmithConfig { case_insensitive_recipe_names: true, ..RecipeSmithConfig::default() };
+        let smith = smith_with(config, vec![recipe("Iron Sword", &[("Iron Ingot", 1)], "Iron Sword")]).await;
+        join(&smith, "p1", vec![Item::with_quantity("Iron Ingot", 1).max_stack(64)]).await;
+        smith.start_recording_events();
+
+        assert_eq!(smith.craft_item("p1", "iron SWORD", &mut context()).await.as_deref(), Some("Iron Sword"));
+        let events = smith.stop_recording_events();
+        let crafted = events.iter().find_map(|event| event.data.downcast_ref::<CraftedEvent>()).unwrap();
+        assert_eq!(crafted.recipe_name, "Iron Sword");
+        assert_eq!(smith.recipe_book.read().await.get_recipe("IRON sword").unwrap().name, "Iron Sword");
+    }
+
+    #[tokio::test]
+    async fn lookup_is_case_sensitive_by_default() {
+        let smith = RecipeSmith::with_recipes(vec![recipe("Iron Sword", &[("Iron Ingot", 1)], "Iron Sword")]);
+        join(&smith, "p1", vec![Item::with_quantity("Iron Ingot", 1).max_stack(64)]).await;
+
+        assert!(smith.craft_item("p1", "iron sword", &mut context()).await.is_none());
+        assert!(smith.craft_item("p1", "Iron Sword", &mut context()).await.is_some());
+    }
 }